@@ -46,9 +46,29 @@ fn bench_file(b: &mut Criterion, path: &Path) {
             }
         })
     });
+    #[cfg(all(feature = "mmap", feature = "rayon"))]
+    group.bench_function("parallel mmap read", |b| {
+        b.iter(|| {
+            let mut rdr = unsafe { cif_parser::ParallelReader::open(path, Default::default()) }
+                .unwrap_or_else(|e| panic!("map {:?}: {}", path, e));
+            while let Some(data) = rdr.read_next().expect("read") {
+                black_box(data);
+            }
+        })
+    });
+    #[cfg(feature = "mmap")]
+    group.bench_function("mmap read", |b| {
+        b.iter(|| {
+            let mut rdr = unsafe { cif_parser::MmapReader::open(path) }
+                .unwrap_or_else(|e| panic!("map {:?}: {}", path, e));
+            while let Some(data) = rdr.read_next().expect("read") {
+                black_box(data);
+            }
+        })
+    });
 }
 
-fn bench_reader(mut group: BenchmarkGroup<WallTime>, data: &[u8]) {
+fn bench_reader(mut group: BenchmarkGroup<WallTime>, data: &'static [u8]) {
     group.throughput(Throughput::Bytes(
         data.len().try_into().expect("data len to byte count"),
     ));
@@ -60,6 +80,34 @@ fn bench_reader(mut group: BenchmarkGroup<WallTime>, data: &[u8]) {
             }
         })
     });
+    #[cfg(feature = "rayon")]
+    group.bench_function("parallel read", |b| {
+        let data = bytes::Bytes::from_static(data);
+        b.iter(|| {
+            let mut rdr = cif_parser::ParallelReader::new(data.clone());
+            while let Some(data) = rdr.read_next().expect("read") {
+                black_box(data);
+            }
+        })
+    });
+    group.bench_function("assemble", |b| {
+        b.iter(|| {
+            let mut rdr = cif_parser::ScheduleAssembler::new(cif_parser::Reader::new(data));
+            while let Some(data) = rdr.read_next().expect("assemble") {
+                black_box(data);
+            }
+        })
+    });
+    #[cfg(feature = "rayon")]
+    group.bench_function("parallel assemble", |b| {
+        let data = bytes::Bytes::from_static(data);
+        b.iter(|| {
+            let mut rdr = cif_parser::ParallelReader::new(data.clone()).par_schedules();
+            while let Some(data) = rdr.read_next().expect("assemble") {
+                black_box(data);
+            }
+        })
+    });
 }
 
 criterion_group!(benches, read_sample, read_larger, bench_file_reader);
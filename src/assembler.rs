@@ -0,0 +1,391 @@
+use std::io::Read;
+
+use fallible_iterator::FallibleIterator;
+use thiserror::Error;
+
+use crate::{
+    BasicSchedule, ChangeEnRoute, LocationIntermediate, LocationOrigin, LocationTerminating,
+    Position, Reader, ReaderError, Record, ScheduleExtra,
+};
+
+#[derive(Error, Debug)]
+pub enum AssemblerError {
+    #[error("Reader: {0}")]
+    Reader(#[from] ReaderError),
+    #[error("Record at byte {offset} does not belong to any schedule")]
+    Orphaned { offset: usize },
+    #[error("Record at byte {offset} is out of order in schedule at byte {schedule_offset}")]
+    OutOfOrder {
+        offset: usize,
+        schedule_offset: usize,
+    },
+    #[error("Schedule at byte {schedule_offset} has no terminating location")]
+    Unterminated { schedule_offset: usize },
+}
+
+pub type AssemblerResult<T> = std::result::Result<T, AssemblerError>;
+
+/// An intermediate location, along with any change en route that takes
+/// effect there.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Intermediate {
+    change_en_route: Option<ChangeEnRoute>,
+    location: LocationIntermediate,
+}
+
+/// A basic schedule together with its extra details and location records.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Schedule {
+    offset: usize,
+    schedule: BasicSchedule,
+    extra: Option<ScheduleExtra>,
+    origin: Option<LocationOrigin>,
+    intermediates: Vec<Intermediate>,
+    terminating: Option<LocationTerminating>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Assembled {
+    Schedule(Schedule),
+    Other(Record),
+}
+
+/// Groups the flat record stream from a [`Reader`] into whole schedules,
+/// passing through any records that aren't part of a schedule.
+pub struct ScheduleAssembler<R> {
+    reader: Reader<R>,
+    current: Option<Schedule>,
+    pending_change: Option<ChangeEnRoute>,
+    queued: Option<(Record, Position)>,
+    // Where the schedule in progress started.
+    schedule_position: Position,
+    // Where the item last returned started.
+    position: Option<Position>,
+}
+
+impl Intermediate {
+    pub fn change_en_route(&self) -> Option<&ChangeEnRoute> {
+        self.change_en_route.as_ref()
+    }
+    pub fn location(&self) -> &LocationIntermediate {
+        &self.location
+    }
+}
+
+impl Schedule {
+    pub(crate) fn new(offset: usize, schedule: BasicSchedule) -> Self {
+        Schedule {
+            offset,
+            schedule,
+            extra: None,
+            origin: None,
+            intermediates: Vec::new(),
+            terminating: None,
+        }
+    }
+
+    /// The byte offset of the basic schedule record in the source.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    pub fn schedule(&self) -> &BasicSchedule {
+        &self.schedule
+    }
+    pub fn extra(&self) -> Option<&ScheduleExtra> {
+        self.extra.as_ref()
+    }
+    pub fn origin(&self) -> Option<&LocationOrigin> {
+        self.origin.as_ref()
+    }
+    pub fn intermediates(&self) -> &[Intermediate] {
+        &self.intermediates
+    }
+    pub fn terminating(&self) -> Option<&LocationTerminating> {
+        self.terminating.as_ref()
+    }
+
+    fn out_of_order(&self, offset: usize) -> AssemblerError {
+        AssemblerError::OutOfOrder {
+            offset,
+            schedule_offset: self.offset,
+        }
+    }
+}
+
+impl<R: Read> ScheduleAssembler<R> {
+    pub fn new(reader: Reader<R>) -> Self {
+        ScheduleAssembler {
+            reader,
+            current: None,
+            pending_change: None,
+            queued: None,
+            schedule_position: Position { offset: 0, line: 1 },
+            position: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &Reader<R> {
+        &self.reader
+    }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn get_mut(&mut self) -> &mut Reader<R> {
+        &mut self.reader
+    }
+
+    // Whether everything read so far has been handed out.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn is_idle(&self) -> bool {
+        self.current.is_none() && self.pending_change.is_none() && self.queued.is_none()
+    }
+
+    // Where the last item returned started in the input; for a schedule,
+    // that's its basic schedule record.
+    pub(crate) fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    pub fn read_next(&mut self) -> AssemblerResult<Option<Assembled>> {
+        if let Some((record, position)) = self.queued.take() {
+            self.position = Some(position);
+            return Ok(Some(Assembled::Other(record)));
+        }
+
+        loop {
+            let position = self.reader.position();
+            let offset = position.offset;
+            let record = match self.reader.read_next()? {
+                Some(record) => record,
+                None => {
+                    self.position = Some(self.schedule_position);
+                    return Ok(self.finish()?.map(Assembled::Schedule));
+                }
+            };
+
+            match record {
+                Record::Schedule(schedule) => {
+                    let prev = self.finish()?;
+                    let prev_position = std::mem::replace(&mut self.schedule_position, position);
+                    self.current = Some(Schedule::new(offset, schedule));
+                    if let Some(prev) = prev {
+                        self.position = Some(prev_position);
+                        return Ok(Some(Assembled::Schedule(prev)));
+                    }
+                }
+                Record::ScheduleExtra(extra) => {
+                    let current = self.expect_current(offset)?;
+                    if current.extra.is_some() || current.origin.is_some() {
+                        return Err(current.out_of_order(offset));
+                    }
+                    current.extra = Some(extra);
+                }
+                Record::LocationOrigin(origin) => {
+                    let current = self.expect_current(offset)?;
+                    if current.origin.is_some() {
+                        return Err(current.out_of_order(offset));
+                    }
+                    current.origin = Some(origin);
+                }
+                Record::ChangeEnRoute(change) => {
+                    let has_pending = self.pending_change.is_some();
+                    let current = self.expect_current(offset)?;
+                    if current.origin.is_none() || has_pending {
+                        return Err(current.out_of_order(offset));
+                    }
+                    self.pending_change = Some(change);
+                }
+                Record::LocationIntermediate(location) => {
+                    let change_en_route = self.pending_change.take();
+                    let current = self.expect_current(offset)?;
+                    if current.origin.is_none() {
+                        return Err(current.out_of_order(offset));
+                    }
+                    current.intermediates.push(Intermediate {
+                        change_en_route,
+                        location,
+                    });
+                }
+                Record::LocationTerminating(terminating) => {
+                    let has_pending = self.pending_change.is_some();
+                    let mut current = self
+                        .current
+                        .take()
+                        .ok_or(AssemblerError::Orphaned { offset })?;
+                    if current.origin.is_none() || has_pending {
+                        return Err(current.out_of_order(offset));
+                    }
+                    current.terminating = Some(terminating);
+                    self.position = Some(self.schedule_position);
+                    return Ok(Some(Assembled::Schedule(current)));
+                }
+                // The line may well have been part of a schedule, but we've
+                // no way to tell, so pass it on without disturbing it.
+                malformed @ Record::Malformed { .. } => {
+                    self.position = Some(position);
+                    return Ok(Some(Assembled::Other(malformed)));
+                }
+                other => {
+                    if let Some(prev) = self.finish()? {
+                        self.queued = Some((other, position));
+                        self.position = Some(self.schedule_position);
+                        return Ok(Some(Assembled::Schedule(prev)));
+                    }
+                    self.position = Some(position);
+                    return Ok(Some(Assembled::Other(other)));
+                }
+            }
+        }
+    }
+
+    fn expect_current(&mut self, offset: usize) -> AssemblerResult<&mut Schedule> {
+        self.current
+            .as_mut()
+            .ok_or(AssemblerError::Orphaned { offset })
+    }
+
+    // Closes off the schedule in progress, which is only valid if it either
+    // has no locations (eg: a cancellation) or is already terminated.
+    fn finish(&mut self) -> AssemblerResult<Option<Schedule>> {
+        match self.current.take() {
+            Some(schedule) if schedule.origin.is_some() => Err(AssemblerError::Unterminated {
+                schedule_offset: schedule.offset,
+            }),
+            other => Ok(other),
+        }
+    }
+}
+
+impl<R: Read> FallibleIterator for ScheduleAssembler<R> {
+    type Item = Assembled;
+
+    type Error = AssemblerError;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        self.read_next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assemble(data: &[u8]) -> AssemblerResult<Vec<Assembled>> {
+        ScheduleAssembler::new(Reader::new(data)).collect()
+    }
+
+    #[test]
+    fn should_group_schedule_records() {
+        let data: &[u8] = b"\
+BSNC000462005172012060000001 PEE5J11    111841820 DMUE   090                   P\n\
+BX         NTY                                                                  \n\
+LOHOLBSDG 0944 0000          TB                                                 \n\
+LIESJLEDS 0948 0951      00000000   DM    OPRM                                  \n\
+CRLEEDSWJ OO1J11    111841820 DMUE   090                                        \n\
+LILEEDSWJ           0952H00000000   E                                           \n\
+LTLEEDS   0954 000012A   TF                                                     \n\
+ZZ                                                                              \n";
+
+        let items = assemble(data).expect("assemble");
+        assert_eq!(items.len(), 2);
+        let schedule = match &items[0] {
+            Assembled::Schedule(schedule) => schedule,
+            other => panic!("Expected schedule, got {:?}", other),
+        };
+        assert_eq!(schedule.offset(), 0);
+        assert_eq!(schedule.schedule().uid().unwrap(), "C00046");
+        assert!(schedule.extra().is_some());
+        assert!(schedule.origin().is_some());
+        assert_eq!(schedule.intermediates().len(), 2);
+        assert!(schedule.intermediates()[0].change_en_route().is_none());
+        assert!(schedule.intermediates()[1].change_en_route().is_some());
+        assert!(schedule.terminating().is_some());
+        assert!(matches!(items[1], Assembled::Other(Record::Trailer(_))));
+    }
+
+    #[test]
+    fn should_yield_cancellation_without_locations() {
+        let data: &[u8] = b"\
+BSNC000462005172006210000001            1                                      C\n\
+ZZ                                                                              \n";
+
+        let items = assemble(data).expect("assemble");
+        assert_eq!(items.len(), 2);
+        assert!(
+            matches!(&items[0], Assembled::Schedule(s) if s.origin().is_none()),
+            "{:?} should be a cancellation",
+            items[0]
+        );
+        assert!(matches!(items[1], Assembled::Other(Record::Trailer(_))));
+    }
+
+    #[test]
+    fn should_reject_orphaned_location() {
+        let data: &[u8] = b"\
+LILEEDSWJ           0952H00000000   E                                           \n";
+
+        let e = assemble(data).unwrap_err();
+        assert!(
+            matches!(e, AssemblerError::Orphaned { offset: 0 }),
+            "{:?} should be orphaned at 0",
+            e
+        );
+    }
+
+    #[test]
+    fn should_reject_intermediate_before_origin() {
+        let data: &[u8] = b"\
+BSNC000462005172012060000001 PEE5J11    111841820 DMUE   090                   P\n\
+LILEEDSWJ           0952H00000000   E                                           \n";
+
+        let e = assemble(data).unwrap_err();
+        assert!(
+            matches!(
+                e,
+                AssemblerError::OutOfOrder {
+                    offset: 81,
+                    schedule_offset: 0
+                }
+            ),
+            "{:?} should be out of order at 81",
+            e
+        );
+    }
+
+    #[test]
+    fn should_reject_unterminated_schedule() {
+        let data: &[u8] = b"\
+BSNC000462005172012060000001 PEE5J11    111841820 DMUE   090                   P\n\
+LOHOLBSDG 0944 0000          TB                                                 \n\
+BSNC000902005172012060000001 PEE5H67    122974000 DMUE   090                   P\n";
+
+        let e = assemble(data).unwrap_err();
+        assert!(
+            matches!(e, AssemblerError::Unterminated { schedule_offset: 0 }),
+            "{:?} should be unterminated at 0",
+            e
+        );
+    }
+
+    #[test]
+    fn should_reject_change_en_route_before_terminating() {
+        let data: &[u8] = b"\
+BSNC000462005172012060000001 PEE5J11    111841820 DMUE   090                   P\n\
+LOHOLBSDG 0944 0000          TB                                                 \n\
+CRLEEDSWJ OO1J11    111841820 DMUE   090                                        \n\
+LTLEEDS   0954 000012A   TF                                                     \n";
+
+        let e = assemble(data).unwrap_err();
+        assert!(
+            matches!(
+                e,
+                AssemblerError::OutOfOrder {
+                    offset: 243,
+                    schedule_offset: 0
+                }
+            ),
+            "{:?} should be out of order at 243",
+            e
+        );
+    }
+}
@@ -1,6 +1,70 @@
+use std::fmt;
+
 use bytes::Bytes;
+use chrono::NaiveDate;
+
+use crate::helpers::{
+    days_from_slice, parse_field, string_of_slice, string_of_slice_opt, yymmdd_from_slice,
+    RecordBuf,
+};
+use crate::{
+    errors::{BuildError, CIFParseError},
+    Days, Stp, Tiploc, TransactionType,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssociationCategory {
+    Join,
+    Divide,
+    Next,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DateIndicator {
+    SameDay,
+    NextDay,
+    PreviousDay,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssociationType {
+    Passenger,
+    Operating,
+}
+
+impl AssociationCategory {
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            AssociationCategory::Join => "JJ",
+            AssociationCategory::Divide => "VV",
+            AssociationCategory::Next => "NP",
+        }
+    }
+}
+
+impl DateIndicator {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            DateIndicator::SameDay => b'S',
+            DateIndicator::NextDay => b'N',
+            DateIndicator::PreviousDay => b'P',
+        }
+    }
+}
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+impl AssociationType {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            AssociationType::Passenger => b'P',
+            AssociationType::Operating => b'O',
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq)]
 pub struct Association {
     record: Bytes,
 }
@@ -12,6 +76,175 @@ impl Association {
     pub fn buf(&self) -> &Bytes {
         &self.record
     }
+
+    pub fn transaction_type(&self) -> Result<TransactionType, CIFParseError> {
+        parse_field(&self.record, "transaction_type", 2..3, |s| match s[0] {
+            b'N' => Ok(TransactionType::New),
+            b'D' => Ok(TransactionType::Delete),
+            b'R' => Ok(TransactionType::Revise),
+            _ => Err(CIFParseError::InvalidItem),
+        })
+    }
+    pub fn main_uid(&self) -> Result<&str, CIFParseError> {
+        parse_field(&self.record, "main_uid", 3..9, |s| Ok(string_of_slice(s)?))
+    }
+    pub fn associated_uid(&self) -> Result<&str, CIFParseError> {
+        parse_field(&self.record, "associated_uid", 9..15, |s| {
+            Ok(string_of_slice(s)?)
+        })
+    }
+    pub fn start_date(&self) -> Result<NaiveDate, CIFParseError> {
+        parse_field(&self.record, "start_date", 15..21, yymmdd_from_slice)
+    }
+    pub fn end_date(&self) -> Result<Option<NaiveDate>, CIFParseError> {
+        parse_field(&self.record, "end_date", 21..27, |s| {
+            if let Some(s) = string_of_slice_opt(s)? {
+                let dt = yymmdd_from_slice(s.as_bytes())?;
+                Ok(Some(dt))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+    pub fn days(&self) -> Result<Days, CIFParseError> {
+        parse_field(&self.record, "days", 27..34, days_from_slice)
+    }
+    pub fn category(&self) -> Result<Option<AssociationCategory>, CIFParseError> {
+        parse_field(&self.record, "category", 34..36, |s| match s {
+            b"JJ" => Ok(Some(AssociationCategory::Join)),
+            b"VV" => Ok(Some(AssociationCategory::Divide)),
+            b"NP" => Ok(Some(AssociationCategory::Next)),
+            b"  " => Ok(None),
+            _ => Err(CIFParseError::InvalidItem),
+        })
+    }
+    pub fn date_indicator(&self) -> Result<Option<DateIndicator>, CIFParseError> {
+        parse_field(&self.record, "date_indicator", 36..37, |s| match s[0] {
+            b'S' => Ok(Some(DateIndicator::SameDay)),
+            b'N' => Ok(Some(DateIndicator::NextDay)),
+            b'P' => Ok(Some(DateIndicator::PreviousDay)),
+            b' ' => Ok(None),
+            _ => Err(CIFParseError::InvalidItem),
+        })
+    }
+    pub fn location(&self) -> Result<Tiploc<'_>, CIFParseError> {
+        parse_field(&self.record, "location", 37..44, |s| {
+            let s = string_of_slice(s)?;
+            Ok(Tiploc::of_string(s.to_owned()))
+        })
+    }
+    pub fn base_location_suffix(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "base_location_suffix", 44..45, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn assoc_location_suffix(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "assoc_location_suffix", 45..46, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn diagram_type(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "diagram_type", 46..47, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn association_type(&self) -> Result<Option<AssociationType>, CIFParseError> {
+        parse_field(&self.record, "association_type", 47..48, |s| match s[0] {
+            b'P' => Ok(Some(AssociationType::Passenger)),
+            b'O' => Ok(Some(AssociationType::Operating)),
+            b' ' => Ok(None),
+            _ => Err(CIFParseError::InvalidItem),
+        })
+    }
+    pub fn stp(&self) -> Result<Stp, CIFParseError> {
+        parse_field(&self.record, "stp", 79..80, |s| {
+            Stp::from_code(s[0]).ok_or(CIFParseError::InvalidItem)
+        })
+    }
+}
+
+impl fmt::Debug for Association {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Association");
+        s.field("transaction_type", &self.transaction_type());
+        s.field("main_uid", &self.main_uid());
+        s.field("associated_uid", &self.associated_uid());
+        s.field("start_date", &self.start_date());
+        s.field("end_date", &self.end_date());
+        s.field("days", &self.days());
+        s.field("category", &self.category());
+        s.field("date_indicator", &self.date_indicator());
+        s.field("location", &self.location());
+        s.field("base_location_suffix", &self.base_location_suffix());
+        s.field("assoc_location_suffix", &self.assoc_location_suffix());
+        s.field("diagram_type", &self.diagram_type());
+        s.field("association_type", &self.association_type());
+        s.field("stp", &self.stp());
+        s.finish()
+    }
+}
+
+pub struct AssociationBuilder {
+    record: RecordBuf,
+}
+
+impl AssociationBuilder {
+    pub fn new(
+        transaction_type: TransactionType,
+        main_uid: &str,
+        associated_uid: &str,
+        start_date: NaiveDate,
+        location: &Tiploc<'_>,
+        stp: Stp,
+    ) -> Self {
+        let mut record = RecordBuf::new(b"AA");
+        record.put_byte(2, transaction_type.code());
+        record.put_uid("main_uid", 3..9, main_uid);
+        record.put_uid("associated_uid", 9..15, associated_uid);
+        record.put_yymmdd("start_date", 15..21, start_date);
+        record.put_tiploc("location", 37..44, location);
+        record.put("diagram_type", 46..47, "T");
+        record.put_byte(79, stp.code());
+        AssociationBuilder { record }
+    }
+
+    pub fn end_date(mut self, end_date: NaiveDate) -> Self {
+        self.record.put_yymmdd("end_date", 21..27, end_date);
+        self
+    }
+    pub fn days(mut self, days: Days) -> Self {
+        self.record.put_days("days", 27..34, days);
+        self
+    }
+    pub fn category(mut self, category: AssociationCategory) -> Self {
+        self.record.put("category", 34..36, category.code());
+        self
+    }
+    pub fn date_indicator(mut self, date_indicator: DateIndicator) -> Self {
+        self.record.put_byte(36, date_indicator.code());
+        self
+    }
+    pub fn base_location_suffix(mut self, suffix: &str) -> Self {
+        self.record.put("base_location_suffix", 44..45, suffix);
+        self
+    }
+    pub fn assoc_location_suffix(mut self, suffix: &str) -> Self {
+        self.record.put("assoc_location_suffix", 45..46, suffix);
+        self
+    }
+    /// Defaults to `T`, the only type in use.
+    pub fn diagram_type(mut self, diagram_type: &str) -> Self {
+        self.record.put("diagram_type", 46..47, diagram_type);
+        self
+    }
+    pub fn association_type(mut self, association_type: AssociationType) -> Self {
+        self.record.put_byte(47, association_type.code());
+        self
+    }
+
+    pub fn build(self) -> Result<Association, BuildError> {
+        Ok(Association::from_record(self.record.finish()?))
+    }
 }
 
 #[cfg(test)]
@@ -23,6 +256,117 @@ pub mod test {
             b"AANY80987Y808801601041602121111100JJSPRST     TP                               P";
         assert_eq!(80, assoc.len());
         let example = Association::from_record(Bytes::from(assoc.as_ref()));
-        drop(example);
+        println!("{:?}", example);
+        assert_eq!(example.transaction_type().unwrap(), TransactionType::New);
+        assert_eq!(example.main_uid().unwrap(), "Y80987");
+        assert_eq!(example.associated_uid().unwrap(), "Y80880");
+        assert_eq!(
+            example.start_date().unwrap(),
+            NaiveDate::from_ymd_opt(2016, 1, 4).unwrap()
+        );
+        assert_eq!(
+            example.end_date().unwrap(),
+            Some(NaiveDate::from_ymd_opt(2016, 2, 12).unwrap())
+        );
+        assert_eq!(
+            example.days().unwrap(),
+            Days::MON | Days::TUE | Days::WED | Days::THU | Days::FRI
+        );
+        assert_eq!(example.category().unwrap(), Some(AssociationCategory::Join));
+        assert_eq!(
+            example.date_indicator().unwrap(),
+            Some(DateIndicator::SameDay)
+        );
+        assert_eq!(example.location().unwrap(), Tiploc::from("PRST"));
+        assert_eq!(example.base_location_suffix().unwrap(), None);
+        assert_eq!(example.assoc_location_suffix().unwrap(), None);
+        assert_eq!(
+            example.association_type().unwrap(),
+            Some(AssociationType::Passenger)
+        );
+        assert_eq!(example.stp().unwrap(), Stp::Permanent);
+    }
+
+    #[test]
+    fn should_parse_next_association() {
+        // From sample-larger.cif
+        let assoc =
+            b"AANW88898W889122006292007031111100NPSGRMSBYT  TO                               O";
+        assert_eq!(80, assoc.len());
+        let example = Association::from_record(Bytes::from(assoc.as_ref()));
+        assert_eq!(example.transaction_type().unwrap(), TransactionType::New);
+        assert_eq!(example.main_uid().unwrap(), "W88898");
+        assert_eq!(example.associated_uid().unwrap(), "W88912");
+        assert_eq!(
+            example.start_date().unwrap(),
+            NaiveDate::from_ymd_opt(2020, 6, 29).unwrap()
+        );
+        assert_eq!(
+            example.end_date().unwrap(),
+            Some(NaiveDate::from_ymd_opt(2020, 7, 3).unwrap())
+        );
+        assert_eq!(
+            example.days().unwrap(),
+            Days::MON | Days::TUE | Days::WED | Days::THU | Days::FRI
+        );
+        assert_eq!(example.category().unwrap(), Some(AssociationCategory::Next));
+        assert_eq!(
+            example.date_indicator().unwrap(),
+            Some(DateIndicator::SameDay)
+        );
+        assert_eq!(example.location().unwrap(), Tiploc::from("GRMSBYT"));
+        assert_eq!(example.base_location_suffix().unwrap(), None);
+        assert_eq!(example.assoc_location_suffix().unwrap(), None);
+        assert_eq!(
+            example.association_type().unwrap(),
+            Some(AssociationType::Operating)
+        );
+        assert_eq!(example.stp().unwrap(), Stp::Overlay);
+    }
+
+    #[test]
+    fn should_parse_cancelled_association() {
+        // From sample-larger.cif
+        let assoc =
+            b"AANY12541Y182422005182007311111100   AYRR     T                                C";
+        assert_eq!(80, assoc.len());
+        let example = Association::from_record(Bytes::from(assoc.as_ref()));
+        assert_eq!(example.transaction_type().unwrap(), TransactionType::New);
+        assert_eq!(example.main_uid().unwrap(), "Y12541");
+        assert_eq!(example.associated_uid().unwrap(), "Y18242");
+        assert_eq!(
+            example.start_date().unwrap(),
+            NaiveDate::from_ymd_opt(2020, 5, 18).unwrap()
+        );
+        assert_eq!(
+            example.end_date().unwrap(),
+            Some(NaiveDate::from_ymd_opt(2020, 7, 31).unwrap())
+        );
+        assert_eq!(example.category().unwrap(), None);
+        assert_eq!(example.date_indicator().unwrap(), None);
+        assert_eq!(example.location().unwrap(), Tiploc::from("AYRR"));
+        assert_eq!(example.association_type().unwrap(), None);
+        assert_eq!(example.stp().unwrap(), Stp::Cancellation);
+    }
+
+    #[test]
+    fn should_parse_deleted_association() {
+        // From sample-larger.cif
+        let assoc =
+            b"AADC27786C27738200622                BHAMINT  T                                C";
+        assert_eq!(80, assoc.len());
+        let example = Association::from_record(Bytes::from(assoc.as_ref()));
+        assert_eq!(example.transaction_type().unwrap(), TransactionType::Delete);
+        assert_eq!(example.main_uid().unwrap(), "C27786");
+        assert_eq!(example.associated_uid().unwrap(), "C27738");
+        assert_eq!(
+            example.start_date().unwrap(),
+            NaiveDate::from_ymd_opt(2020, 6, 22).unwrap()
+        );
+        assert_eq!(example.end_date().unwrap(), None);
+        assert_eq!(example.days().unwrap(), Days::empty());
+        assert_eq!(example.category().unwrap(), None);
+        assert_eq!(example.location().unwrap(), Tiploc::from("BHAMINT"));
+        assert_eq!(example.stp().unwrap(), Stp::Cancellation);
     }
 }
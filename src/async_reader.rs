@@ -0,0 +1,197 @@
+//! Reading CIF records from a tokio [`AsyncRead`], either through
+//! [`AsyncReader`] or by plugging [`CifCodec`] into your own framing.
+
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::BytesMut;
+use futures_core::Stream;
+use tokio::io::AsyncRead;
+use tokio_util::codec::{Decoder, FramedRead};
+
+use crate::reader::Framer;
+use crate::{ReaderError, ReaderOptions, Record, Repair};
+
+/// Splits a byte stream into [`Record`]s, applying the same rules as the
+/// blocking [`Reader`](crate::Reader) for the given [`ReaderOptions`].
+#[derive(Debug, Clone)]
+pub struct CifCodec {
+    framer: Framer,
+}
+
+/// Reads [`Record`]s from an [`AsyncRead`] as a [`Stream`].
+pub struct AsyncReader<R> {
+    inner: FramedRead<R, CifCodec>,
+}
+
+impl CifCodec {
+    pub fn new() -> Self {
+        Self::with_options(ReaderOptions::default())
+    }
+
+    pub fn with_options(options: ReaderOptions) -> Self {
+        CifCodec {
+            framer: Framer::new(options),
+        }
+    }
+
+    /// The number of malformed lines skipped so far in recovery mode.
+    pub fn malformed_count(&self) -> usize {
+        self.framer.malformed_count()
+    }
+
+    /// Removes and returns the repairs made since the last call.
+    pub fn take_repairs(&mut self) -> Vec<Repair> {
+        self.framer.take_repairs()
+    }
+}
+
+impl Default for CifCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for CifCodec {
+    type Item = Record;
+
+    type Error = ReaderError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.framer.decode(src, false)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.framer.decode(src, true)
+    }
+}
+
+impl<R: AsyncRead> AsyncReader<R> {
+    pub fn new(rdr: R) -> Self {
+        Self::with_options(rdr, ReaderOptions::default())
+    }
+
+    pub fn with_options(rdr: R, options: ReaderOptions) -> Self {
+        AsyncReader {
+            inner: FramedRead::new(rdr, CifCodec::with_options(options)),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    pub fn codec(&self) -> &CifCodec {
+        self.inner.decoder()
+    }
+
+    /// The number of malformed lines skipped so far in recovery mode.
+    pub fn malformed_count(&self) -> usize {
+        self.codec().malformed_count()
+    }
+
+    /// Removes and returns the repairs made since the last call.
+    pub fn take_repairs(&mut self) -> Vec<Repair> {
+        self.inner.decoder_mut().take_repairs()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncReader<R> {
+    pub async fn read_next(&mut self) -> Result<Option<Record>, ReaderError> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+            .await
+            .transpose()
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncReader<R> {
+    type Item = Result<Record, ReaderError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fallible_iterator::FallibleIterator;
+    use futures_util::TryStreamExt;
+
+    use super::*;
+    use crate::{Reader, RepairKind};
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample-larger.cif");
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime")
+            .block_on(f)
+    }
+
+    #[test]
+    fn should_read_same_records_as_blocking_reader() {
+        let expected: Vec<Record> = Reader::new(SAMPLE_FILE).collect().expect("read");
+        let actual: Vec<Record> =
+            block_on(AsyncReader::new(SAMPLE_FILE).try_collect()).expect("read");
+        assert_eq!(actual.len(), expected.len());
+        assert!(actual == expected, "Async records differ from blocking");
+    }
+
+    #[test]
+    fn should_report_invalid_record() {
+        let data: &[u8] = b"\
+ZZ                                                                              \n\
+ZZ                                                                             \n ";
+        let mut rdr = AsyncReader::new(data);
+        block_on(async {
+            assert!(matches!(
+                rdr.read_next().await,
+                Ok(Some(Record::Trailer(_)))
+            ));
+            let e = rdr.read_next().await.unwrap_err();
+            assert!(
+                matches!(
+                    e,
+                    ReaderError::InvalidRecord {
+                        offset: 81,
+                        line: 2,
+                        ..
+                    }
+                ),
+                "{:?} should be invalid record at 81",
+                e
+            );
+        });
+    }
+
+    #[test]
+    fn should_repair_lines_leniently() {
+        let data: &[u8] = b"ZZ\r\nZZ";
+        let mut rdr = AsyncReader::with_options(data, ReaderOptions::new().lenient(true));
+        let records: Vec<Record> = block_on((&mut rdr).try_collect()).expect("read");
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| matches!(r, Record::Trailer(_))));
+
+        let kinds: Vec<RepairKind> = rdr.take_repairs().into_iter().map(|r| r.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RepairKind::CarriageReturn,
+                RepairKind::Padded { len: 2 },
+                RepairKind::MissingFinalNewline,
+                RepairKind::Padded { len: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_ignore_partial_line_at_end() {
+        let data: &[u8] = b"\
+ZZ                                                                              \n\
+ZZ   ";
+        let records: Vec<Record> = block_on(AsyncReader::new(data).try_collect()).expect("read");
+        assert_eq!(records.len(), 1);
+    }
+}
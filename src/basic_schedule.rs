@@ -1,10 +1,21 @@
 use std::fmt;
 
 use bytes::Bytes;
-use chrono::NaiveDate;
+use chrono::{Datelike, NaiveDate};
 
-use crate::helpers::{days_from_slice, string_of_slice_opt, yymmdd_from_slice, Days};
-use crate::{errors::CIFParseError, helpers::string_of_slice};
+use crate::codes::{
+    catering_from_slice, catering_to_string, operating_chars_from_slice, operating_chars_to_string,
+    BankHolidayRunning, Branding, CateringCode, OperatingCharacteristics, PowerType,
+    ReservationPolicy, SeatingClass, SleeperClass, TrainCategory, TrainStatus,
+};
+use crate::helpers::{
+    days_from_slice, parse_field, speed_from_slice_opt, string_of_slice_opt, yymmdd_from_slice,
+    Days, RecordBuf, Speed,
+};
+use crate::{
+    errors::{BuildError, CIFParseError},
+    helpers::string_of_slice,
+};
 
 use super::{Stp, TransactionType};
 
@@ -22,74 +33,132 @@ impl BasicSchedule {
     }
 
     pub fn transaction_type(&self) -> Result<TransactionType, CIFParseError> {
-        match self.record[2] {
+        parse_field(&self.record, "transaction_type", 2..3, |s| match s[0] {
             b'N' => Ok(TransactionType::New),
             b'D' => Ok(TransactionType::Delete),
             b'R' => Ok(TransactionType::Revise),
             _ => Err(CIFParseError::InvalidItem),
-        }
+        })
     }
     pub fn uid(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[3..9])?)
+        parse_field(&self.record, "uid", 3..9, |s| Ok(string_of_slice(s)?))
     }
     pub fn start_date(&self) -> Result<NaiveDate, CIFParseError> {
-        yymmdd_from_slice(&self.record[9..15])
+        parse_field(&self.record, "start_date", 9..15, yymmdd_from_slice)
     }
     pub fn end_date(&self) -> Result<Option<NaiveDate>, CIFParseError> {
-        if let Some(s) = string_of_slice_opt(&self.record[15..21])? {
-            let dt = yymmdd_from_slice(s.as_bytes())?;
-            Ok(Some(dt))
-        } else {
-            Ok(None)
-        }
+        parse_field(&self.record, "end_date", 15..21, |s| {
+            if let Some(s) = string_of_slice_opt(s)? {
+                let dt = yymmdd_from_slice(s.as_bytes())?;
+                Ok(Some(dt))
+            } else {
+                Ok(None)
+            }
+        })
     }
     pub fn days(&self) -> Result<Days, CIFParseError> {
-        days_from_slice(&self.record[21..28])
+        parse_field(&self.record, "days", 21..28, days_from_slice)
     }
-    pub fn bank_holiday(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[28..29])?)
+    pub fn bank_holiday(&self) -> Result<Option<BankHolidayRunning>, CIFParseError> {
+        parse_field(&self.record, "bank_holiday", 28..29, |s| {
+            Ok(string_of_slice_opt(s)?.map(BankHolidayRunning::from_code))
+        })
     }
-    pub fn status(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[29..30])?)
+    pub fn status(&self) -> Result<Option<TrainStatus>, CIFParseError> {
+        parse_field(&self.record, "status", 29..30, |s| {
+            Ok(string_of_slice_opt(s)?.map(TrainStatus::from_code))
+        })
     }
-    pub fn category(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[30..32])?)
+    pub fn category(&self) -> Result<Option<TrainCategory>, CIFParseError> {
+        parse_field(&self.record, "category", 30..32, |s| {
+            Ok(string_of_slice_opt(s)?.map(TrainCategory::from_code))
+        })
     }
     pub fn identity(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[32..36])?)
+        parse_field(&self.record, "identity", 32..36, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn headcode(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[36..40])?)
+        parse_field(&self.record, "headcode", 36..40, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn course_indicator(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "course_indicator", 40..41, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn service_code(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[41..49])?)
+        parse_field(&self.record, "service_code", 41..49, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn portion_id(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "portion_id", 49..50, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn power_type(&self) -> Result<Option<PowerType>, CIFParseError> {
+        parse_field(&self.record, "power_type", 50..53, |s| {
+            Ok(string_of_slice_opt(s)?.map(PowerType::from_code))
+        })
+    }
+    pub fn timing_load(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "timing_load", 53..57, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn speed(&self) -> Result<Option<Speed>, CIFParseError> {
+        parse_field(&self.record, "speed", 57..60, speed_from_slice_opt)
+    }
+    pub fn operating_chars(&self) -> Result<OperatingCharacteristics, CIFParseError> {
+        parse_field(
+            &self.record,
+            "operating_chars",
+            60..66,
+            operating_chars_from_slice,
+        )
     }
-    pub fn speed(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[57..60])?)
+    pub fn seating_class(&self) -> Result<Option<SeatingClass>, CIFParseError> {
+        parse_field(&self.record, "seating_class", 66..67, |s| {
+            Ok(string_of_slice_opt(s)?.map(SeatingClass::from_code))
+        })
     }
-    pub fn seating_class(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[66..67])?)
+    pub fn sleepers(&self) -> Result<Option<SleeperClass>, CIFParseError> {
+        parse_field(&self.record, "sleepers", 67..68, |s| {
+            Ok(string_of_slice_opt(s)?.map(SleeperClass::from_code))
+        })
     }
-    pub fn sleepers(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[67..68])?)
+    pub fn reservations(&self) -> Result<Option<ReservationPolicy>, CIFParseError> {
+        parse_field(&self.record, "reservations", 68..69, |s| {
+            Ok(string_of_slice_opt(s)?.map(ReservationPolicy::from_code))
+        })
     }
-    pub fn reservations(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[68..69])?)
+    pub fn connection_indicator(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "connection_indicator", 69..70, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
-    pub fn catering(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[70..74])?)
+    pub fn catering(&self) -> Result<CateringCode, CIFParseError> {
+        parse_field(&self.record, "catering", 70..74, catering_from_slice)
     }
-    pub fn branding(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[74..78])?)
+    pub fn branding(&self) -> Result<Option<Branding>, CIFParseError> {
+        parse_field(&self.record, "branding", 74..78, |s| {
+            Ok(string_of_slice_opt(s)?.map(Branding::from_code))
+        })
+    }
+    /// Whether the schedule's date range and days run cover `date`. This
+    /// doesn't account for bank holiday running, or any STP overrides.
+    pub fn runs_on(&self, date: NaiveDate) -> Result<bool, CIFParseError> {
+        let start = self.start_date()?;
+        let end = self.end_date()?.unwrap_or(start);
+        Ok(start <= date && date <= end && self.days()?.contains(date.weekday().into()))
     }
     pub fn stp(&self) -> Result<Stp, CIFParseError> {
-        match self.record[79] {
-            b'C' => Ok(Stp::Cancellation),
-            b'N' => Ok(Stp::New),
-            b'O' => Ok(Stp::Overlay),
-            b'P' => Ok(Stp::Permanent),
-            _ => Err(CIFParseError::InvalidItem),
-        }
+        parse_field(&self.record, "stp", 79..80, |s| {
+            Stp::from_code(s[0]).ok_or(CIFParseError::InvalidItem)
+        })
     }
 }
 
@@ -106,11 +175,17 @@ impl fmt::Debug for BasicSchedule {
         s.field("category", &self.category());
         s.field("identity", &self.identity());
         s.field("headcode", &self.headcode());
+        s.field("course_indicator", &self.course_indicator());
         s.field("service_code", &self.service_code());
+        s.field("portion_id", &self.portion_id());
+        s.field("power_type", &self.power_type());
+        s.field("timing_load", &self.timing_load());
         s.field("speed", &self.speed());
+        s.field("operating_chars", &self.operating_chars());
         s.field("seating_class", &self.seating_class());
         s.field("sleepers", &self.sleepers());
         s.field("reservations", &self.reservations());
+        s.field("connection_indicator", &self.connection_indicator());
         s.field("catering", &self.catering());
         s.field("branding", &self.branding());
         s.field("stp", &self.stp());
@@ -119,6 +194,115 @@ impl fmt::Debug for BasicSchedule {
     }
 }
 
+pub struct BasicScheduleBuilder {
+    record: RecordBuf,
+}
+
+impl BasicScheduleBuilder {
+    pub fn new(
+        transaction_type: TransactionType,
+        uid: &str,
+        start_date: NaiveDate,
+        stp: Stp,
+    ) -> Self {
+        let mut record = RecordBuf::new(b"BS");
+        record.put_byte(2, transaction_type.code());
+        record.put_uid("uid", 3..9, uid);
+        record.put_yymmdd("start_date", 9..15, start_date);
+        record.put_byte(79, stp.code());
+        BasicScheduleBuilder { record }
+    }
+
+    pub fn end_date(mut self, end_date: NaiveDate) -> Self {
+        self.record.put_yymmdd("end_date", 15..21, end_date);
+        self
+    }
+    pub fn days(mut self, days: Days) -> Self {
+        self.record.put_days("days", 21..28, days);
+        self
+    }
+    pub fn bank_holiday(mut self, bank_holiday: &BankHolidayRunning) -> Self {
+        self.record.put("bank_holiday", 28..29, bank_holiday.code());
+        self
+    }
+    pub fn status(mut self, status: &TrainStatus) -> Self {
+        self.record.put("status", 29..30, status.code());
+        self
+    }
+    pub fn category(mut self, category: &TrainCategory) -> Self {
+        self.record.put("category", 30..32, category.code());
+        self
+    }
+    pub fn identity(mut self, identity: &str) -> Self {
+        self.record.put("identity", 32..36, identity);
+        self
+    }
+    pub fn headcode(mut self, headcode: &str) -> Self {
+        self.record.put("headcode", 36..40, headcode);
+        self
+    }
+    pub fn course_indicator(mut self, course_indicator: &str) -> Self {
+        self.record
+            .put("course_indicator", 40..41, course_indicator);
+        self
+    }
+    pub fn service_code(mut self, service_code: &str) -> Self {
+        self.record.put("service_code", 41..49, service_code);
+        self
+    }
+    pub fn portion_id(mut self, portion_id: &str) -> Self {
+        self.record.put("portion_id", 49..50, portion_id);
+        self
+    }
+    pub fn power_type(mut self, power_type: &PowerType) -> Self {
+        self.record.put("power_type", 50..53, power_type.code());
+        self
+    }
+    pub fn timing_load(mut self, timing_load: &str) -> Self {
+        self.record.put("timing_load", 53..57, timing_load);
+        self
+    }
+    pub fn speed(mut self, speed: Speed) -> Self {
+        self.record.put_speed("speed", 57..60, speed);
+        self
+    }
+    pub fn operating_chars(mut self, chars: OperatingCharacteristics) -> Self {
+        self.record
+            .put("operating_chars", 60..66, &operating_chars_to_string(chars));
+        self
+    }
+    pub fn seating_class(mut self, class: &SeatingClass) -> Self {
+        self.record.put("seating_class", 66..67, class.code());
+        self
+    }
+    pub fn sleepers(mut self, sleepers: &SleeperClass) -> Self {
+        self.record.put("sleepers", 67..68, sleepers.code());
+        self
+    }
+    pub fn reservations(mut self, reservations: &ReservationPolicy) -> Self {
+        self.record.put("reservations", 68..69, reservations.code());
+        self
+    }
+    pub fn connection_indicator(mut self, connection_indicator: &str) -> Self {
+        self.record
+            .put("connection_indicator", 69..70, connection_indicator);
+        self
+    }
+    pub fn catering(mut self, catering: CateringCode) -> Self {
+        self.record
+            .put("catering", 70..74, &catering_to_string(catering));
+        self
+    }
+    pub fn branding(mut self, branding: &Branding) -> Self {
+        self.record.put("branding", 74..78, branding.code());
+        self
+    }
+
+    pub fn build(self) -> Result<BasicSchedule, BuildError> {
+        Ok(BasicSchedule::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -141,20 +325,60 @@ mod test {
         );
         assert_eq!(example.days().unwrap(), Days::MON | Days::TUE | Days::FRI);
         assert_eq!(example.bank_holiday().unwrap(), None);
-        assert_eq!(example.status().unwrap(), Some("P"));
-        assert_eq!(example.category().unwrap(), Some("OO"));
+        assert_eq!(
+            example.status().unwrap(),
+            Some(TrainStatus::PassengerAndParcels)
+        );
+        assert_eq!(
+            example.category().unwrap(),
+            Some(TrainCategory::OrdinaryPassenger)
+        );
         assert_eq!(example.identity().unwrap(), Some("2N75"));
         assert_eq!(example.headcode().unwrap(), None);
+        assert_eq!(example.course_indicator().unwrap(), Some("1"));
         assert_eq!(example.service_code().unwrap(), Some("13575825"));
-        assert_eq!(example.speed().unwrap(), Some("090"));
-        assert_eq!(example.seating_class().unwrap(), Some("S"));
+        assert_eq!(example.portion_id().unwrap(), None);
+        assert_eq!(
+            example.power_type().unwrap(),
+            Some(PowerType::DieselMechanicalMultipleUnit)
+        );
+        assert_eq!(example.timing_load().unwrap(), Some("E"));
+        assert_eq!(example.speed().unwrap(), Some(Speed(90)));
+        assert_eq!(
+            example.operating_chars().unwrap(),
+            OperatingCharacteristics::empty()
+        );
+        assert_eq!(
+            example.seating_class().unwrap(),
+            Some(SeatingClass::StandardOnly)
+        );
         assert_eq!(example.sleepers().unwrap(), None);
         assert_eq!(example.reservations().unwrap(), None);
-        assert_eq!(example.catering().unwrap(), None);
+        assert_eq!(example.connection_indicator().unwrap(), None);
+        assert_eq!(example.catering().unwrap(), CateringCode::empty());
         assert_eq!(example.branding().unwrap(), None);
         assert_eq!(example.stp().unwrap(), Stp::Overlay);
     }
 
+    #[test]
+    fn should_run_on_days_in_range() {
+        let sched =
+            b"BSRG828851510191510231100100 POO2N75    113575825 DMUE   090      S            O";
+        let example = BasicSchedule::from_record(Bytes::from(sched.as_ref()));
+        // Monday
+        assert!(example
+            .runs_on(NaiveDate::from_ymd_opt(2015, 10, 19).unwrap())
+            .unwrap());
+        // Wednesday
+        assert!(!example
+            .runs_on(NaiveDate::from_ymd_opt(2015, 10, 21).unwrap())
+            .unwrap());
+        // Monday, after the end date
+        assert!(!example
+            .runs_on(NaiveDate::from_ymd_opt(2015, 10, 26).unwrap())
+            .unwrap());
+    }
+
     #[test]
     fn should_parse_cancellation_schedule() {
         let sched =
@@ -178,12 +402,21 @@ mod test {
         assert_eq!(example.category().unwrap(), None);
         assert_eq!(example.identity().unwrap(), None);
         assert_eq!(example.headcode().unwrap(), None);
+        assert_eq!(example.course_indicator().unwrap(), Some("1"));
         assert_eq!(example.service_code().unwrap(), None);
+        assert_eq!(example.portion_id().unwrap(), None);
+        assert_eq!(example.power_type().unwrap(), None);
+        assert_eq!(example.timing_load().unwrap(), None);
         assert_eq!(example.speed().unwrap(), None);
+        assert_eq!(
+            example.operating_chars().unwrap(),
+            OperatingCharacteristics::empty()
+        );
         assert_eq!(example.seating_class().unwrap(), None);
         assert_eq!(example.sleepers().unwrap(), None);
         assert_eq!(example.reservations().unwrap(), None);
-        assert_eq!(example.catering().unwrap(), None);
+        assert_eq!(example.connection_indicator().unwrap(), None);
+        assert_eq!(example.catering().unwrap(), CateringCode::empty());
         assert_eq!(example.branding().unwrap(), None);
         assert_eq!(example.stp().unwrap(), Stp::Cancellation);
     }
@@ -206,16 +439,34 @@ mod test {
         );
         assert_eq!(example.days().unwrap(), Days::SUN);
         assert_eq!(example.bank_holiday().unwrap(), None);
-        assert_eq!(example.status().unwrap(), "P".into());
-        assert_eq!(example.category().unwrap(), "OO".into());
+        assert_eq!(
+            example.status().unwrap(),
+            Some(TrainStatus::PassengerAndParcels)
+        );
+        assert_eq!(
+            example.category().unwrap(),
+            Some(TrainCategory::OrdinaryPassenger)
+        );
         assert_eq!(example.identity().unwrap(), "2Y16".into());
         assert_eq!(example.headcode().unwrap(), None);
+        assert_eq!(example.course_indicator().unwrap(), Some("1"));
         assert_eq!(example.service_code().unwrap(), "22214000".into());
-        assert_eq!(example.speed().unwrap(), "075".into());
+        assert_eq!(example.portion_id().unwrap(), None);
+        assert_eq!(
+            example.power_type().unwrap(),
+            Some(PowerType::ElectricMultipleUnit)
+        );
+        assert_eq!(example.timing_load().unwrap(), Some("375"));
+        assert_eq!(example.speed().unwrap(), Some(Speed(75)));
+        assert_eq!(
+            example.operating_chars().unwrap(),
+            OperatingCharacteristics::DRIVER_ONLY
+        );
         assert_eq!(example.seating_class().unwrap(), None);
         assert_eq!(example.sleepers().unwrap(), None);
         assert_eq!(example.reservations().unwrap(), None);
-        assert_eq!(example.catering().unwrap(), None);
+        assert_eq!(example.connection_indicator().unwrap(), None);
+        assert_eq!(example.catering().unwrap(), CateringCode::empty());
         assert_eq!(example.branding().unwrap(), None);
         assert_eq!(example.stp().unwrap(), Stp::Permanent);
     }
@@ -240,16 +491,25 @@ mod test {
             Days::MON | Days::TUE | Days::WED | Days::THU | Days::FRI
         );
         assert_eq!(example.bank_holiday().unwrap(), None);
-        assert_eq!(example.status().unwrap(), "F".into());
+        assert_eq!(example.status().unwrap(), Some(TrainStatus::Freight));
         assert_eq!(example.category().unwrap(), None);
         assert_eq!(example.identity().unwrap(), None);
         assert_eq!(example.headcode().unwrap(), None);
+        assert_eq!(example.course_indicator().unwrap(), Some("1"));
         assert_eq!(example.service_code().unwrap(), None);
-        assert_eq!(example.speed().unwrap(), "060".into());
+        assert_eq!(example.portion_id().unwrap(), None);
+        assert_eq!(example.power_type().unwrap(), Some(PowerType::Diesel));
+        assert_eq!(example.timing_load().unwrap(), Some("600"));
+        assert_eq!(example.speed().unwrap(), Some(Speed(60)));
+        assert_eq!(
+            example.operating_chars().unwrap(),
+            OperatingCharacteristics::empty()
+        );
         assert_eq!(example.seating_class().unwrap(), None);
         assert_eq!(example.sleepers().unwrap(), None);
         assert_eq!(example.reservations().unwrap(), None);
-        assert_eq!(example.catering().unwrap(), None);
+        assert_eq!(example.connection_indicator().unwrap(), None);
+        assert_eq!(example.catering().unwrap(), CateringCode::empty());
         assert_eq!(example.branding().unwrap(), None);
         assert_eq!(example.stp().unwrap(), Stp::Permanent);
     }
@@ -272,16 +532,25 @@ mod test {
         );
         assert_eq!(example.days().unwrap(), Days::SUN);
         assert_eq!(example.bank_holiday().unwrap(), None);
-        assert_eq!(example.status().unwrap(), "B".into());
-        assert_eq!(example.category().unwrap(), Some("BS"));
+        assert_eq!(example.status().unwrap(), Some(TrainStatus::Bus));
+        assert_eq!(example.category().unwrap(), Some(TrainCategory::BusService));
         assert_eq!(example.identity().unwrap(), Some("0B00"));
         assert_eq!(example.headcode().unwrap(), None);
+        assert_eq!(example.course_indicator().unwrap(), Some("1"));
         assert_eq!(example.service_code().unwrap(), Some("22180008"));
+        assert_eq!(example.portion_id().unwrap(), None);
+        assert_eq!(example.power_type().unwrap(), None);
+        assert_eq!(example.timing_load().unwrap(), None);
         assert_eq!(example.speed().unwrap(), None);
+        assert_eq!(
+            example.operating_chars().unwrap(),
+            OperatingCharacteristics::empty()
+        );
         assert_eq!(example.seating_class().unwrap(), None);
         assert_eq!(example.sleepers().unwrap(), None);
         assert_eq!(example.reservations().unwrap(), None);
-        assert_eq!(example.catering().unwrap(), None);
+        assert_eq!(example.connection_indicator().unwrap(), None);
+        assert_eq!(example.catering().unwrap(), CateringCode::empty());
         assert_eq!(example.branding().unwrap(), None);
         assert_eq!(example.stp().unwrap(), Stp::Permanent);
     }
@@ -305,13 +574,82 @@ mod test {
         assert_eq!(example.category().unwrap(), None);
         assert_eq!(example.identity().unwrap(), None);
         assert_eq!(example.headcode().unwrap(), None);
+        assert_eq!(example.course_indicator().unwrap(), None);
         assert_eq!(example.service_code().unwrap(), None);
+        assert_eq!(example.portion_id().unwrap(), None);
+        assert_eq!(example.power_type().unwrap(), None);
+        assert_eq!(example.timing_load().unwrap(), None);
         assert_eq!(example.speed().unwrap(), None);
+        assert_eq!(
+            example.operating_chars().unwrap(),
+            OperatingCharacteristics::empty()
+        );
         assert_eq!(example.seating_class().unwrap(), None);
         assert_eq!(example.sleepers().unwrap(), None);
         assert_eq!(example.reservations().unwrap(), None);
-        assert_eq!(example.catering().unwrap(), None);
+        assert_eq!(example.connection_indicator().unwrap(), None);
+        assert_eq!(example.catering().unwrap(), CateringCode::empty());
         assert_eq!(example.branding().unwrap(), None);
         assert_eq!(example.stp().unwrap(), Stp::New);
     }
+
+    #[test]
+    fn should_build_basic_schedule() {
+        let built = BasicScheduleBuilder::new(
+            TransactionType::Revise,
+            "G82885",
+            NaiveDate::from_ymd_opt(2015, 10, 19).unwrap(),
+            Stp::Overlay,
+        )
+        .end_date(NaiveDate::from_ymd_opt(2015, 10, 23).unwrap())
+        .days(Days::MON | Days::TUE | Days::FRI)
+        .status(&TrainStatus::PassengerAndParcels)
+        .category(&TrainCategory::OrdinaryPassenger)
+        .identity("2N75")
+        .course_indicator("1")
+        .service_code("13575825")
+        .power_type(&PowerType::DieselMechanicalMultipleUnit)
+        .timing_load("E")
+        .speed(Speed(90))
+        .seating_class(&SeatingClass::StandardOnly)
+        .build()
+        .expect("build");
+        assert_eq!(
+            &built.buf()[..],
+            &b"BSRG828851510191510231100100 POO2N75    113575825 DMUE   090      S            O\n"
+                [..]
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_fields() {
+        let start = NaiveDate::from_ymd_opt(2015, 10, 19).unwrap();
+        let e = BasicScheduleBuilder::new(TransactionType::New, "G82885", start, Stp::Permanent)
+            .identity("2N75X")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            e,
+            BuildError::TooLong {
+                field: "identity",
+                value: "2N75X".to_owned(),
+                max: 4
+            }
+        );
+
+        let e = BasicScheduleBuilder::new(TransactionType::New, "G8288", start, Stp::Permanent)
+            .build()
+            .unwrap_err();
+        assert!(matches!(e, BuildError::InvalidUid { field: "uid", .. }));
+
+        let end = NaiveDate::from_ymd_opt(2115, 10, 19).unwrap();
+        let e = BasicScheduleBuilder::new(TransactionType::New, "G82885", start, Stp::Permanent)
+            .end_date(end)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "end_date 2115-10-19 is outside the years 2000 to 2099"
+        );
+    }
 }
@@ -3,8 +3,15 @@ use std::fmt;
 use bytes::Bytes;
 
 use crate::{
-    helpers::{string_of_slice, string_of_slice_opt},
-    CIFParseError, Tiploc,
+    codes::{
+        catering_from_slice, catering_to_string, operating_chars_from_slice,
+        operating_chars_to_string, Branding, CateringCode, OperatingCharacteristics, PowerType,
+        ReservationPolicy, SeatingClass, SleeperClass, TrainCategory,
+    },
+    helpers::{
+        parse_field, speed_from_slice_opt, string_of_slice, string_of_slice_opt, RecordBuf, Speed,
+    },
+    BuildError, CIFParseError, Tiploc,
 };
 
 #[derive(Clone, Eq, PartialEq)]
@@ -20,74 +27,123 @@ impl ChangeEnRoute {
         &self.record
     }
 
-    pub fn tiploc(&self) -> Result<Tiploc, CIFParseError> {
-        Ok(Tiploc::from(string_of_slice(&self.record[2..10])?))
+    pub fn tiploc(&self) -> Result<Tiploc<'_>, CIFParseError> {
+        parse_field(&self.record, "tiploc", 2..9, |s| {
+            Ok(Tiploc::from(string_of_slice(s)?))
+        })
     }
-    pub fn train_category(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[10..12])?)
+    pub fn tiploc_suffix(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "tiploc_suffix", 9..10, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn train_category(&self) -> Result<Option<TrainCategory>, CIFParseError> {
+        parse_field(&self.record, "train_category", 10..12, |s| {
+            Ok(string_of_slice_opt(s)?.map(TrainCategory::from_code))
+        })
     }
     pub fn train_identity(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[12..16])?)
+        parse_field(&self.record, "train_identity", 12..16, |s| {
+            Ok(string_of_slice(s)?)
+        })
     }
     pub fn headcode(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[16..20])?)
+        parse_field(&self.record, "headcode", 16..20, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn course_indicator(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[20..21])?)
+        parse_field(&self.record, "course_indicator", 20..21, |s| {
+            Ok(string_of_slice(s)?)
+        })
     }
     pub fn service_code(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[21..29])?)
+        parse_field(&self.record, "service_code", 21..29, |s| {
+            Ok(string_of_slice(s)?)
+        })
     }
     pub fn biz_sector(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[29..30])?)
+        parse_field(&self.record, "biz_sector", 29..30, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn power_type(&self) -> Result<Option<PowerType>, CIFParseError> {
+        parse_field(&self.record, "power_type", 30..33, |s| {
+            Ok(string_of_slice_opt(s)?.map(PowerType::from_code))
+        })
     }
     pub fn timing_load(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[33..37])?)
+        parse_field(&self.record, "timing_load", 33..37, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
-    pub fn speed(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[37..40])?)
+    pub fn speed(&self) -> Result<Option<Speed>, CIFParseError> {
+        parse_field(&self.record, "speed", 37..40, speed_from_slice_opt)
     }
-    pub fn operating_chars(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[40..46])?)
+    pub fn operating_chars(&self) -> Result<OperatingCharacteristics, CIFParseError> {
+        parse_field(
+            &self.record,
+            "operating_chars",
+            40..46,
+            operating_chars_from_slice,
+        )
     }
-    pub fn class(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[46..47])?)
+    pub fn class(&self) -> Result<Option<SeatingClass>, CIFParseError> {
+        parse_field(&self.record, "class", 46..47, |s| {
+            Ok(string_of_slice_opt(s)?.map(SeatingClass::from_code))
+        })
     }
-    pub fn sleepers(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[47..48])?)
+    pub fn sleepers(&self) -> Result<Option<SleeperClass>, CIFParseError> {
+        parse_field(&self.record, "sleepers", 47..48, |s| {
+            Ok(string_of_slice_opt(s)?.map(SleeperClass::from_code))
+        })
     }
-    pub fn reservations(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[48..49])?)
+    pub fn reservations(&self) -> Result<Option<ReservationPolicy>, CIFParseError> {
+        parse_field(&self.record, "reservations", 48..49, |s| {
+            Ok(string_of_slice_opt(s)?.map(ReservationPolicy::from_code))
+        })
     }
     pub fn connect(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[49..50])?)
+        parse_field(&self.record, "connect", 49..50, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
-    pub fn catering(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[50..54])?)
+    pub fn catering(&self) -> Result<CateringCode, CIFParseError> {
+        parse_field(&self.record, "catering", 50..54, catering_from_slice)
     }
-    pub fn branding(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[54..58])?)
+    pub fn branding(&self) -> Result<Option<Branding>, CIFParseError> {
+        parse_field(&self.record, "branding", 54..58, |s| {
+            Ok(string_of_slice_opt(s)?.map(Branding::from_code))
+        })
     }
     pub fn traction(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[58..62])?)
+        parse_field(&self.record, "traction", 58..62, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn uic_code(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[62..67])?)
+        parse_field(&self.record, "uic_code", 62..67, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn retail_id(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[67..75])?)
+        parse_field(&self.record, "retail_id", 67..75, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
 }
 impl fmt::Debug for ChangeEnRoute {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s = f.debug_struct("TiplocInsert");
         s.field("tiploc", &self.tiploc());
+        s.field("tiploc_suffix", &self.tiploc_suffix());
         s.field("train_category", &self.train_category());
         s.field("train_identity", &self.train_identity());
         s.field("headcode", &self.headcode());
         s.field("course_indicator", &self.course_indicator());
         s.field("service_code", &self.service_code());
         s.field("biz_sector", &self.biz_sector());
+        s.field("power_type", &self.power_type());
         s.field("timing_load", &self.timing_load());
         s.field("speed", &self.speed());
         s.field("operating_chars", &self.operating_chars());
@@ -104,6 +160,101 @@ impl fmt::Debug for ChangeEnRoute {
     }
 }
 
+pub struct ChangeEnRouteBuilder {
+    record: RecordBuf,
+}
+
+impl ChangeEnRouteBuilder {
+    pub fn new(
+        tiploc: &Tiploc<'_>,
+        train_identity: &str,
+        course_indicator: &str,
+        service_code: &str,
+    ) -> Self {
+        let mut record = RecordBuf::new(b"CR");
+        record.put_tiploc("tiploc", 2..9, tiploc);
+        record.put("train_identity", 12..16, train_identity);
+        record.put("course_indicator", 20..21, course_indicator);
+        record.put("service_code", 21..29, service_code);
+        ChangeEnRouteBuilder { record }
+    }
+
+    pub fn tiploc_suffix(mut self, suffix: &str) -> Self {
+        self.record.put("tiploc_suffix", 9..10, suffix);
+        self
+    }
+    pub fn train_category(mut self, category: &TrainCategory) -> Self {
+        self.record.put("train_category", 10..12, category.code());
+        self
+    }
+    pub fn headcode(mut self, headcode: &str) -> Self {
+        self.record.put("headcode", 16..20, headcode);
+        self
+    }
+    pub fn biz_sector(mut self, biz_sector: &str) -> Self {
+        self.record.put("biz_sector", 29..30, biz_sector);
+        self
+    }
+    pub fn power_type(mut self, power_type: &PowerType) -> Self {
+        self.record.put("power_type", 30..33, power_type.code());
+        self
+    }
+    pub fn timing_load(mut self, timing_load: &str) -> Self {
+        self.record.put("timing_load", 33..37, timing_load);
+        self
+    }
+    pub fn speed(mut self, speed: Speed) -> Self {
+        self.record.put_speed("speed", 37..40, speed);
+        self
+    }
+    pub fn operating_chars(mut self, chars: OperatingCharacteristics) -> Self {
+        self.record
+            .put("operating_chars", 40..46, &operating_chars_to_string(chars));
+        self
+    }
+    pub fn class(mut self, class: &SeatingClass) -> Self {
+        self.record.put("class", 46..47, class.code());
+        self
+    }
+    pub fn sleepers(mut self, sleepers: &SleeperClass) -> Self {
+        self.record.put("sleepers", 47..48, sleepers.code());
+        self
+    }
+    pub fn reservations(mut self, reservations: &ReservationPolicy) -> Self {
+        self.record.put("reservations", 48..49, reservations.code());
+        self
+    }
+    pub fn connect(mut self, connect: &str) -> Self {
+        self.record.put("connect", 49..50, connect);
+        self
+    }
+    pub fn catering(mut self, catering: CateringCode) -> Self {
+        self.record
+            .put("catering", 50..54, &catering_to_string(catering));
+        self
+    }
+    pub fn branding(mut self, branding: &Branding) -> Self {
+        self.record.put("branding", 54..58, branding.code());
+        self
+    }
+    pub fn traction(mut self, traction: &str) -> Self {
+        self.record.put("traction", 58..62, traction);
+        self
+    }
+    pub fn uic_code(mut self, uic_code: &str) -> Self {
+        self.record.put("uic_code", 62..67, uic_code);
+        self
+    }
+    pub fn retail_id(mut self, retail_id: &str) -> Self {
+        self.record.put("retail_id", 67..75, retail_id);
+        self
+    }
+
+    pub fn build(self) -> Result<ChangeEnRoute, BuildError> {
+        Ok(ChangeEnRoute::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::Tiploc;
@@ -118,20 +269,23 @@ mod test {
         println!("{:?}", example);
 
         assert_eq!(example.tiploc().unwrap(), Tiploc::from("CTRDJN"));
-        assert_eq!(example.train_category().unwrap(), "DT");
+        assert_eq!(example.train_category().unwrap(), Some(TrainCategory::Test));
         assert_eq!(example.train_identity().unwrap(), "3Q27");
         assert_eq!(example.headcode().unwrap(), None);
         assert_eq!(example.course_indicator().unwrap(), "1");
         assert_eq!(example.service_code().unwrap(), "52495112");
         assert_eq!(example.biz_sector().unwrap(), None);
         assert_eq!(example.timing_load().unwrap(), None);
-        assert_eq!(example.speed().unwrap(), "030");
-        assert_eq!(example.operating_chars().unwrap(), None);
+        assert_eq!(example.speed().unwrap(), Some(Speed(30)));
+        assert_eq!(
+            example.operating_chars().unwrap(),
+            OperatingCharacteristics::empty()
+        );
         assert_eq!(example.class().unwrap(), None);
         assert_eq!(example.sleepers().unwrap(), None);
         assert_eq!(example.reservations().unwrap(), None);
         assert_eq!(example.connect().unwrap(), None);
-        assert_eq!(example.catering().unwrap(), None);
+        assert_eq!(example.catering().unwrap(), CateringCode::empty());
         assert_eq!(example.branding().unwrap(), None);
         assert_eq!(example.traction().unwrap(), None);
         assert_eq!(example.uic_code().unwrap(), None);
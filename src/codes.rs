@@ -0,0 +1,490 @@
+use std::iter::FromIterator;
+
+use bitflags::bitflags;
+
+use crate::errors::CIFParseError;
+
+// Declares an enum over a closed CIF code table, with an `Other` variant to
+// carry any code we don't know about.
+macro_rules! code_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($variant:ident => $code:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl $name {
+            pub fn from_code(code: &str) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    other => $name::Other(other.to_owned()),
+                }
+            }
+
+            pub fn code(&self) -> &str {
+                match self {
+                    $($name::$variant => $code,)*
+                    $name::Other(code) => code,
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.code())
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let code = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+                Ok($name::from_code(&code))
+            }
+        }
+    };
+}
+
+code_enum! {
+    pub enum TrainStatus {
+        Bus => "B",
+        Freight => "F",
+        PassengerAndParcels => "P",
+        Ship => "S",
+        Trip => "T",
+        StpPassengerAndParcels => "1",
+        StpFreight => "2",
+        StpTrip => "3",
+        StpShip => "4",
+        StpBus => "5",
+    }
+}
+
+code_enum! {
+    pub enum TrainCategory {
+        LondonUnderground => "OL",
+        UnadvertisedOrdinaryPassenger => "OU",
+        OrdinaryPassenger => "OO",
+        StaffTrain => "OS",
+        Mixed => "OW",
+        ChannelTunnel => "XC",
+        SleeperEurope => "XD",
+        International => "XI",
+        Motorail => "XR",
+        UnadvertisedExpress => "XU",
+        ExpressPassenger => "XX",
+        SleeperDomestic => "XZ",
+        BusReplacement => "BR",
+        BusService => "BS",
+        Ship => "SS",
+        EmptyCoachingStock => "EE",
+        EmptyCoachingStockLondonUnderground => "EL",
+        EmptyCoachingStockAndStaff => "ES",
+        Postal => "JJ",
+        PostOfficeParcels => "PM",
+        Parcels => "PP",
+        EmptyNpccs => "PV",
+        Departmental => "DD",
+        CivilEngineer => "DH",
+        MechanicalAndElectricalEngineer => "DI",
+        Stores => "DQ",
+        Test => "DT",
+        SignalAndTelecomsEngineer => "DY",
+        LocomotiveAndBrakeVan => "ZB",
+        LightLocomotive => "ZZ",
+        AutomotiveComponents => "J2",
+        AutomotiveVehicles => "H2",
+        EdibleProducts => "J3",
+        IndustrialMinerals => "J4",
+        Chemicals => "J5",
+        BuildingMaterials => "J6",
+        GeneralMerchandise => "J8",
+        European => "H8",
+        FreightlinerContracts => "J9",
+        FreightlinerOther => "H9",
+        CoalDistributive => "A0",
+        CoalElectricity => "E0",
+        CoalOtherAndNuclear => "B0",
+        Metals => "B1",
+        Aggregates => "B4",
+        Waste => "B5",
+        TrainloadBuildingMaterials => "B6",
+        PetroleumProducts => "B7",
+        ChannelTunnelMixedBusiness => "H0",
+        ChannelTunnelIntermodal => "H1",
+        ChannelTunnelAutomotive => "H3",
+        ChannelTunnelContractServices => "H4",
+        ChannelTunnelHaulmark => "H5",
+        ChannelTunnelJointVenture => "H6",
+    }
+}
+
+code_enum! {
+    pub enum PowerType {
+        Diesel => "D",
+        DieselElectricMultipleUnit => "DEM",
+        DieselMechanicalMultipleUnit => "DMU",
+        Electric => "E",
+        ElectroDiesel => "ED",
+        ElectricMultipleUnitWithLocomotive => "EML",
+        ElectricMultipleUnit => "EMU",
+        ElectricParcelsUnit => "EPU",
+        HighSpeedTrain => "HST",
+        DieselShuntingLocomotive => "LDS",
+    }
+}
+
+code_enum! {
+    pub enum BankHolidayRunning {
+        NotOnBankHolidayMondays => "X",
+        NotOnEdinburghHolidays => "E",
+        NotOnGlasgowHolidays => "G",
+    }
+}
+
+code_enum! {
+    pub enum SeatingClass {
+        FirstAndStandard => "B",
+        StandardOnly => "S",
+    }
+}
+
+code_enum! {
+    pub enum SleeperClass {
+        FirstAndStandard => "B",
+        FirstOnly => "F",
+        StandardOnly => "S",
+    }
+}
+
+code_enum! {
+    pub enum ReservationPolicy {
+        Compulsory => "A",
+        BicyclesEssential => "E",
+        Recommended => "R",
+        PossibleFromAnyStation => "S",
+    }
+}
+
+code_enum! {
+    pub enum Branding {
+        Eurostar => "E",
+    }
+}
+
+code_enum! {
+    pub enum Activity {
+        StopsForOtherTrainsToPass => "A",
+        AttachOrDetachAssistingLocomotive => "AE",
+        ShowsAsXOnArrival => "AX",
+        StopsForBankingLocomotive => "BL",
+        StopsToChangeTrainmen => "C",
+        SetsDown => "D",
+        DetachesVehicles => "-D",
+        StopsForExamination => "E",
+        NationalRailTimetableDataToAdd => "G",
+        NotionalActivity => "H",
+        NotionalActivityThirdColumn => "HH",
+        PassengerCountPoint => "K",
+        TicketCollectionAndExamination => "KC",
+        TicketExamination => "KE",
+        TicketExaminationFirstClass => "KF",
+        SelectiveTicketExamination => "KS",
+        StopsToChangeLocomotives => "L",
+        StopNotAdvertised => "N",
+        StopsForOtherOperatingReasons => "OP",
+        LocomotiveOnRear => "OR",
+        PropellingBetweenPoints => "PR",
+        RequestStop => "R",
+        ReversingMovement => "RM",
+        StopsForLocomotiveToRunRound => "RR",
+        StopsForRailwayPersonnelOnly => "S",
+        TakesUpAndSetsDown => "T",
+        AttachesAndDetachesVehicles => "-T",
+        TrainBegins => "TB",
+        TrainFinishes => "TF",
+        DetailConsistForTops => "TS",
+        StopsForTabletStaffOrToken => "TW",
+        TakesUp => "U",
+        AttachesVehicles => "-U",
+        StopsForWatering => "W",
+        PassesAtCrossingPoint => "X",
+    }
+}
+
+/// The set of activities at a location, from the six two-character slots of
+/// the activity field.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Activities(Vec<Activity>);
+
+impl Activities {
+    pub fn iter(&self) -> impl Iterator<Item = &Activity> {
+        self.0.iter()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn contains(&self, activity: &Activity) -> bool {
+        self.0.contains(activity)
+    }
+
+    pub fn picks_up(&self) -> bool {
+        self.contains(&Activity::TakesUpAndSetsDown)
+            || self.contains(&Activity::TakesUp)
+            || self.contains(&Activity::TrainBegins)
+    }
+    pub fn sets_down(&self) -> bool {
+        self.contains(&Activity::TakesUpAndSetsDown)
+            || self.contains(&Activity::SetsDown)
+            || self.contains(&Activity::TrainFinishes)
+    }
+    pub fn is_request_stop(&self) -> bool {
+        self.contains(&Activity::RequestStop)
+    }
+    pub fn is_public_stop(&self) -> bool {
+        (self.picks_up() || self.sets_down()) && !self.contains(&Activity::StopNotAdvertised)
+    }
+}
+
+impl FromIterator<Activity> for Activities {
+    fn from_iter<I: IntoIterator<Item = Activity>>(iter: I) -> Self {
+        Activities(iter.into_iter().collect())
+    }
+}
+
+pub(crate) fn activities_from_slice(slice: &[u8]) -> Result<Activities, CIFParseError> {
+    let mut activities = Vec::new();
+    for chunk in slice.chunks(2) {
+        let code = std::str::from_utf8(chunk)?.trim();
+        if !code.is_empty() {
+            activities.push(Activity::from_code(code));
+        }
+    }
+    Ok(Activities(activities))
+}
+
+// Flags made up of single letter codes. Each flag's bit is the position of
+// its letter in the alphabet, so that letters we don't know about can be
+// kept as they are rather than being lost.
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct CateringCode: u32 {
+        const BUFFET = letter(b'C');
+        const FIRST_CLASS_RESTAURANT = letter(b'F');
+        const HOT_FOOD = letter(b'H');
+        const FIRST_CLASS_MEAL = letter(b'M');
+        const WHEELCHAIR_RESERVATIONS = letter(b'P');
+        const RESTAURANT = letter(b'R');
+        const TROLLEY = letter(b'T');
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct OperatingCharacteristics: u32 {
+        const VACUUM_BRAKED = letter(b'B');
+        const TIMED_AT_100_MPH = letter(b'C');
+        const DRIVER_ONLY = letter(b'D');
+        const CONVEYS_MARK_4_COACHES = letter(b'E');
+        const GUARD_REQUIRED = letter(b'G');
+        const TIMED_AT_110_MPH = letter(b'M');
+        const PUSH_PULL = letter(b'P');
+        const RUNS_AS_REQUIRED = letter(b'Q');
+        const AIR_CONDITIONED_WITH_PA = letter(b'R');
+        const STEAM_HEATED = letter(b'S');
+        const RUNS_TO_TERMINALS_AS_REQUIRED = letter(b'Y');
+        const SB1C_GAUGE = letter(b'Z');
+    }
+}
+
+impl CateringCode {
+    /// Any codes that aren't in the published table, in alphabetical order.
+    pub fn other_codes(self) -> String {
+        letters_to_string(self.bits() & !Self::all().bits())
+    }
+}
+
+impl OperatingCharacteristics {
+    /// Any codes that aren't in the published table, in alphabetical order.
+    pub fn other_codes(self) -> String {
+        letters_to_string(self.bits() & !Self::all().bits())
+    }
+}
+
+const fn letter(code: u8) -> u32 {
+    1 << (code - b'A')
+}
+
+// Each flag is a single letter, in any order, padded out with spaces.
+fn letters_from_slice(slice: &[u8]) -> Result<u32, CIFParseError> {
+    let mut bits = 0;
+    for ch in slice.iter().filter(|ch| **ch != b' ') {
+        if !ch.is_ascii_uppercase() {
+            return Err(CIFParseError::InvalidItem);
+        }
+        bits |= letter(*ch);
+    }
+    Ok(bits)
+}
+
+fn letters_to_string(bits: u32) -> String {
+    (b'A'..=b'Z')
+        .filter(|ch| bits & letter(*ch) != 0)
+        .map(char::from)
+        .collect()
+}
+
+pub(crate) fn operating_chars_from_slice(
+    slice: &[u8],
+) -> Result<OperatingCharacteristics, CIFParseError> {
+    letters_from_slice(slice).map(OperatingCharacteristics::from_bits_retain)
+}
+
+pub(crate) fn operating_chars_to_string(chars: OperatingCharacteristics) -> String {
+    letters_to_string(chars.bits())
+}
+
+pub(crate) fn catering_from_slice(slice: &[u8]) -> Result<CateringCode, CIFParseError> {
+    letters_from_slice(slice).map(CateringCode::from_bits_retain)
+}
+
+pub(crate) fn catering_to_string(catering: CateringCode) -> String {
+    letters_to_string(catering.bits())
+}
+
+pub(crate) fn activities_to_string(activities: &Activities) -> String {
+    activities
+        .iter()
+        .map(|activity| format!("{:<2}", activity.code()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_known_category() {
+        assert_eq!(
+            TrainCategory::from_code("OO"),
+            TrainCategory::OrdinaryPassenger
+        );
+        assert_eq!(TrainCategory::OrdinaryPassenger.code(), "OO");
+    }
+
+    #[test]
+    fn should_keep_unknown_code() {
+        let status = TrainStatus::from_code("Q");
+        assert_eq!(status, TrainStatus::Other("Q".to_owned()));
+        assert_eq!(status.code(), "Q");
+    }
+
+    #[test]
+    fn operating_chars_should_parse_combined_codes() {
+        let result = operating_chars_from_slice(b"QY    ").expect("parse");
+        assert_eq!(
+            result,
+            OperatingCharacteristics::RUNS_AS_REQUIRED
+                | OperatingCharacteristics::RUNS_TO_TERMINALS_AS_REQUIRED
+        );
+    }
+
+    #[test]
+    fn activities_should_parse_each_slot() {
+        let result = activities_from_slice(b"-DOPRMT     ").expect("parse");
+        assert_eq!(
+            result.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                Activity::DetachesVehicles,
+                Activity::StopsForOtherOperatingReasons,
+                Activity::ReversingMovement,
+                Activity::TakesUpAndSetsDown,
+            ]
+        );
+    }
+
+    #[test]
+    fn activities_should_parse_empty() {
+        let result = activities_from_slice(b"            ").expect("parse");
+        assert!(result.is_empty());
+        assert!(!result.is_public_stop());
+    }
+
+    #[test]
+    fn activities_should_describe_public_stop() {
+        let result = activities_from_slice(b"T           ").expect("parse");
+        assert!(result.is_public_stop());
+        assert!(result.picks_up());
+        assert!(result.sets_down());
+        assert!(!result.is_request_stop());
+    }
+
+    #[test]
+    fn activities_should_describe_set_down_request_stop() {
+        let result = activities_from_slice(b"D R         ").expect("parse");
+        assert!(result.is_public_stop());
+        assert!(!result.picks_up());
+        assert!(result.sets_down());
+        assert!(result.is_request_stop());
+    }
+
+    #[test]
+    fn activities_should_hide_unadvertised_stop() {
+        let result = activities_from_slice(b"T N         ").expect("parse");
+        assert!(!result.is_public_stop());
+    }
+
+    #[test]
+    fn activities_should_encode_each_slot() {
+        let activities = activities_from_slice(b"-DOPRMT     ").expect("parse");
+        assert_eq!(activities_to_string(&activities), "-DOPRMT ");
+    }
+
+    #[test]
+    fn flags_should_encode_in_code_order() {
+        let chars = operating_chars_from_slice(b"YQ    ").expect("parse");
+        assert_eq!(operating_chars_to_string(chars), "QY");
+        let catering = catering_from_slice(b"TC  ").expect("parse");
+        assert_eq!(catering_to_string(catering), "CT");
+    }
+
+    #[test]
+    fn catering_should_parse_combined_codes() {
+        let result = catering_from_slice(b"CT  ").expect("parse");
+        assert_eq!(result, CateringCode::BUFFET | CateringCode::TROLLEY);
+    }
+
+    #[test]
+    fn catering_should_parse_empty() {
+        let result = catering_from_slice(b"    ").expect("parse");
+        assert_eq!(result, CateringCode::empty());
+    }
+
+    #[test]
+    fn catering_should_keep_unknown_code() {
+        let result = catering_from_slice(b"ZC  ").expect("parse");
+        assert!(result.contains(CateringCode::BUFFET));
+        assert_eq!(result.other_codes(), "Z");
+        assert_eq!(catering_to_string(result), "CZ");
+    }
+
+    #[test]
+    fn operating_chars_should_keep_unknown_code() {
+        let result = operating_chars_from_slice(b"DA    ").expect("parse");
+        assert!(result.contains(OperatingCharacteristics::DRIVER_ONLY));
+        assert_eq!(result.other_codes(), "A");
+        assert_eq!(operating_chars_to_string(result), "AD");
+    }
+
+    #[test]
+    fn flags_should_reject_non_letters() {
+        assert!(catering_from_slice(b"C1  ").is_err());
+    }
+}
@@ -1,16 +1,157 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
 use bytes::Bytes;
+use chrono::{NaiveDate, NaiveTime};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum CIFParseError {
-    #[error("UTF conversion: {}", 0)]
+    #[error("UTF conversion: {0}")]
     Utf8(std::str::Utf8Error),
-    #[error("Invalid number: {}", 0)]
+    #[error("Invalid number: {0}")]
     InvalidNumber(lexical_core::Error),
-    #[error("Invalid time: {:?}", 0)]
+    #[error("Invalid time: {0:?}")]
     InvalidTime(Bytes),
     #[error("Invalid item")]
     InvalidItem,
+    #[error(transparent)]
+    Field(Box<FieldError>),
+}
+
+/// Where a line starts in the input.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Position {
+    pub offset: usize,
+    /// The one-based line number.
+    pub line: usize,
+}
+
+/// A field of a record that couldn't be parsed, along with the record it
+/// came from.
+#[derive(Debug, Error)]
+pub struct FieldError {
+    pub record: Bytes,
+    pub field: &'static str,
+    /// The zero-based columns of the field within the record.
+    pub columns: Range<usize>,
+    /// Where the record was in the input, if known.
+    pub position: Option<Position>,
+    #[source]
+    pub source: CIFParseError,
+}
+
+impl CIFParseError {
+    /// Notes where the record that failed to parse was found, for errors
+    /// that came from a record field.
+    pub fn at(mut self, position: Position) -> Self {
+        if let CIFParseError::Field(ref mut e) = self {
+            e.position = Some(position);
+        }
+        self
+    }
+
+    /// Renders the offending record with the failed field underlined.
+    pub fn snippet(&self) -> Option<String> {
+        match self {
+            CIFParseError::Field(e) => Some(e.snippet()),
+            _ => None,
+        }
+    }
+}
+
+impl FieldError {
+    /// The two letter record identity, eg: `BS`.
+    pub fn record_type(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.record[..self.record.len().min(2)])
+    }
+
+    /// The contents of the field.
+    pub fn bytes(&self) -> Bytes {
+        self.record.slice(self.columns.clone())
+    }
+
+    pub fn snippet(&self) -> String {
+        snippet(&self.record, self.columns.clone())
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(position) = self.position {
+            write!(f, "Line {} (byte {}): ", position.line, position.offset)?;
+        }
+        write!(
+            f,
+            "Invalid {} in {} record at columns {}-{} ({:?}): {}",
+            self.field,
+            self.record_type(),
+            self.columns.start + 1,
+            self.columns.end,
+            String::from_utf8_lossy(&self.bytes()),
+            self.source
+        )
+    }
+}
+
+/// Renders `line` with carets under the zero-based `columns`, eg:
+///
+/// ```text
+/// BSNC000462005X72012060000001 PEE5J11    111841820 DMUE   090                   P
+///          ^^^^^^
+/// ```
+///
+/// Any trailing newline is dropped, and control characters are shown as `?`
+/// so that the carets stay lined up.
+pub fn snippet(line: &[u8], columns: Range<usize>) -> String {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let text = line
+        .iter()
+        .map(|&b| {
+            if b.is_ascii() && !b.is_ascii_control() {
+                char::from(b)
+            } else {
+                '?'
+            }
+        })
+        .collect::<String>();
+    let width = columns.end.saturating_sub(columns.start).max(1);
+    format!(
+        "{}\n{}{}",
+        text,
+        " ".repeat(columns.start),
+        "^".repeat(width)
+    )
+}
+
+/// Why a builder couldn't produce a record.
+#[derive(Debug, Error, Clone, Eq, PartialEq)]
+pub enum BuildError {
+    #[error("{field} {value:?} is longer than {max} characters")]
+    TooLong {
+        field: &'static str,
+        value: String,
+        max: usize,
+    },
+    #[error("{field} {value:?} contains characters that can't appear in this field")]
+    InvalidChars { field: &'static str, value: String },
+    #[error("{field} {value:?} is not a letter followed by five digits")]
+    InvalidUid { field: &'static str, value: String },
+    #[error("{field} {date} is outside the years 2000 to 2099")]
+    DateOutOfRange {
+        field: &'static str,
+        date: NaiveDate,
+    },
+    #[error("{field} {time} is more precise than the field allows")]
+    InvalidTime {
+        field: &'static str,
+        time: NaiveTime,
+    },
+    #[error("Timing point needs either a pass time, or both arrival and departure times")]
+    MissingTimes,
+    #[error("Timing point has a pass time as well as arrival or departure times")]
+    ConflictingTimes,
 }
 
 impl std::convert::From<std::str::Utf8Error> for CIFParseError {
@@ -23,3 +164,53 @@ impl std::convert::From<lexical_core::Error> for CIFParseError {
         CIFParseError::InvalidNumber(e)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BasicSchedule;
+
+    fn bad_schedule() -> BasicSchedule {
+        BasicSchedule::from_record(Bytes::from_static(
+            b"BSNC000462005X72012060000001 PEE5J11    111841820 DMUE   090                   P\n",
+        ))
+    }
+
+    #[test]
+    fn field_error_should_describe_field() {
+        let e = bad_schedule().start_date().unwrap_err();
+        let field = match &e {
+            CIFParseError::Field(field) => field,
+            other => panic!("Expected field error, got {:?}", other),
+        };
+        assert_eq!(field.record_type(), "BS");
+        assert_eq!(field.field, "start_date");
+        assert_eq!(field.columns, 9..15);
+        assert_eq!(&field.bytes()[..], b"2005X7");
+        assert!(
+            e.to_string()
+                .starts_with("Invalid start_date in BS record at columns 10-15 (\"2005X7\"): "),
+            "{}",
+            e
+        );
+
+        let e = e.at(Position {
+            offset: 810,
+            line: 11,
+        });
+        assert!(e.to_string().starts_with("Line 11 (byte 810): "), "{}", e);
+    }
+
+    #[test]
+    fn should_render_snippet() {
+        let e = bad_schedule().start_date().unwrap_err();
+        assert_eq!(
+            e.snippet().unwrap(),
+            "\
+BSNC000462005X72012060000001 PEE5J11    111841820 DMUE   090                   P
+         ^^^^^^"
+        );
+        assert_eq!(snippet(b"ZZ\t\n", 2..3), "ZZ?\n  ^");
+        assert!(CIFParseError::InvalidItem.snippet().is_none());
+    }
+}
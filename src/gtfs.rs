@@ -0,0 +1,679 @@
+//! Builds a static GTFS feed from the passenger schedules in a [`Timetable`].
+//!
+//! Each permanent, new or overlay schedule becomes a trip with its own
+//! service, whose calendar covers the schedule's date range and days run.
+//! Dates on which a short term plan or cancellation takes precedence are
+//! removed again in `calendar_dates.txt`, so that exactly one trip runs for
+//! each train on any date, as [`crate::resolve_on_date`] would pick it.
+//! Bank holiday running isn't modelled.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use thiserror::Error;
+
+use crate::resolver::precedence;
+use crate::{Activities, CIFParseError, Days, Schedule, Stp, Timetable, TrainCategory};
+
+#[derive(Error, Debug)]
+pub enum GtfsError {
+    #[error("I/O: {0}")]
+    Io(#[from] io::Error),
+    #[error("CSV: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Parsing record: {0}")]
+    Parse(#[from] CIFParseError),
+}
+
+pub type GtfsResult<T> = std::result::Result<T, GtfsError>;
+
+/// Details that the CIF doesn't carry, but GTFS needs.
+#[derive(Debug, Clone)]
+pub struct GtfsOptions {
+    /// Names for operators, keyed by ATOC code. Operators without a name are
+    /// listed under their code.
+    pub agency_names: BTreeMap<String, String>,
+    pub agency_url: String,
+    pub agency_timezone: String,
+    /// The agency for schedules without a BX record.
+    pub default_agency: String,
+    /// Latitude and longitude of stations, keyed by CRS code.
+    pub stop_coordinates: BTreeMap<String, (f64, f64)>,
+}
+
+impl Default for GtfsOptions {
+    fn default() -> Self {
+        GtfsOptions {
+            agency_names: BTreeMap::new(),
+            agency_url: "https://www.nationalrail.co.uk/".to_owned(),
+            agency_timezone: "Europe/London".to_owned(),
+            default_agency: "ZZ".to_owned(),
+            stop_coordinates: BTreeMap::new(),
+        }
+    }
+}
+
+/// The files that make up a feed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GtfsTable {
+    Agency,
+    Stops,
+    Routes,
+    Trips,
+    StopTimes,
+    Calendar,
+    CalendarDates,
+}
+
+impl GtfsTable {
+    pub const ALL: [GtfsTable; 7] = [
+        GtfsTable::Agency,
+        GtfsTable::Stops,
+        GtfsTable::Routes,
+        GtfsTable::Trips,
+        GtfsTable::StopTimes,
+        GtfsTable::Calendar,
+        GtfsTable::CalendarDates,
+    ];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            GtfsTable::Agency => "agency.txt",
+            GtfsTable::Stops => "stops.txt",
+            GtfsTable::Routes => "routes.txt",
+            GtfsTable::Trips => "trips.txt",
+            GtfsTable::StopTimes => "stop_times.txt",
+            GtfsTable::Calendar => "calendar.txt",
+            GtfsTable::CalendarDates => "calendar_dates.txt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agency {
+    pub agency_id: String,
+    pub agency_name: String,
+    pub agency_url: String,
+    pub agency_timezone: String,
+}
+
+/// A station, identified by its CRS code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stop {
+    pub stop_id: String,
+    pub stop_name: String,
+    pub stop_lat: Option<f64>,
+    pub stop_lon: Option<f64>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Route {
+    pub route_id: String,
+    pub agency_id: String,
+    pub route_long_name: String,
+    pub route_type: u16,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Trip {
+    pub route_id: String,
+    pub service_id: String,
+    pub trip_id: String,
+    pub trip_short_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StopTime {
+    pub trip_id: String,
+    /// Measured from midnight at the start of the service day, so may be
+    /// more than 24 hours.
+    pub arrival_time: Duration,
+    pub departure_time: Duration,
+    pub stop_id: String,
+    pub stop_sequence: u32,
+    pub pickup_type: u8,
+    pub drop_off_type: u8,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Calendar {
+    pub service_id: String,
+    pub days: Days,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CalendarDate {
+    pub service_id: String,
+    pub date: NaiveDate,
+    pub exception_type: u8,
+}
+
+// GTFS pickup and drop off types.
+const REGULAR: u8 = 0;
+const NONE: u8 = 1;
+const COORDINATE_WITH_DRIVER: u8 = 3;
+
+// GTFS calendar_dates exception type.
+const SERVICE_REMOVED: u8 = 2;
+
+#[derive(Debug, Clone, Default)]
+pub struct GtfsFeed {
+    agencies: Vec<Agency>,
+    stops: Vec<Stop>,
+    routes: Vec<Route>,
+    trips: Vec<Trip>,
+    stop_times: Vec<StopTime>,
+    calendar: Vec<Calendar>,
+    calendar_dates: Vec<CalendarDate>,
+}
+
+impl GtfsFeed {
+    /// Builds a feed from every schedule in `timetable` with at least two
+    /// advertised calls at stations with a CRS code.
+    pub fn from_timetable(timetable: &Timetable, options: &GtfsOptions) -> GtfsResult<Self> {
+        let mut feed = GtfsFeed::default();
+
+        let mut crs_of_tiploc = BTreeMap::new();
+        let mut stop_names = BTreeMap::new();
+        for insert in timetable.tiplocs() {
+            let crs = match insert.crs()? {
+                Some(crs) => crs.to_owned(),
+                None => continue,
+            };
+            crs_of_tiploc.insert(insert.tiploc()?.as_ref().to_owned(), crs.clone());
+            let name = insert.nlc_desc()?.unwrap_or(insert.tps_description()?);
+            stop_names.entry(crs).or_insert_with(|| name.to_owned());
+        }
+
+        let mut by_uid = BTreeMap::<&str, Vec<&Schedule>>::new();
+        for (key, schedule) in timetable.schedules() {
+            by_uid.entry(&key.uid).or_default().push(schedule);
+        }
+
+        let mut agencies = BTreeSet::new();
+        let mut routes = BTreeMap::new();
+        for (_, schedule) in timetable.schedules() {
+            let bs = schedule.schedule();
+            if bs.stp()? == Stp::Cancellation {
+                continue;
+            }
+            let trip_id = trip_id(schedule)?;
+            let stop_times = stop_times(schedule, &trip_id, &crs_of_tiploc)?;
+            let (first, last) = match (stop_times.first(), stop_times.last()) {
+                (Some(first), Some(last)) if stop_times.len() >= 2 => (first, last),
+                _ => continue,
+            };
+
+            let agency_id = match schedule.extra() {
+                Some(extra) => extra.atoc_code()?.to_owned(),
+                None => options.default_agency.clone(),
+            };
+            let route_type = route_type(bs.category()?);
+            let route_id = format!(
+                "{}_{}_{}_{}",
+                agency_id, first.stop_id, last.stop_id, route_type
+            );
+            routes.entry(route_id.clone()).or_insert_with(|| Route {
+                route_id: route_id.clone(),
+                agency_id: agency_id.clone(),
+                route_long_name: format!(
+                    "{} to {}",
+                    stop_names[&first.stop_id], stop_names[&last.stop_id]
+                ),
+                route_type,
+            });
+            agencies.insert(agency_id);
+
+            feed.trips.push(Trip {
+                route_id,
+                service_id: trip_id.clone(),
+                trip_id: trip_id.clone(),
+                trip_short_name: bs.identity()?.map(str::to_owned),
+            });
+            feed.stop_times.extend(stop_times);
+
+            let start_date = bs.start_date()?;
+            let end_date = bs.end_date()?.unwrap_or(start_date);
+            feed.calendar.push(Calendar {
+                service_id: trip_id.clone(),
+                days: bs.days()?,
+                start_date,
+                end_date,
+            });
+            let others = &by_uid[bs.uid()?];
+            for date in start_date.iter_days().take_while(|d| *d <= end_date) {
+                if bs.runs_on(date)? && overridden(bs.stp()?, others, date)? {
+                    feed.calendar_dates.push(CalendarDate {
+                        service_id: trip_id.clone(),
+                        date,
+                        exception_type: SERVICE_REMOVED,
+                    });
+                }
+            }
+        }
+
+        feed.agencies = agencies
+            .into_iter()
+            .map(|agency_id| Agency {
+                agency_name: options
+                    .agency_names
+                    .get(&agency_id)
+                    .cloned()
+                    .unwrap_or_else(|| agency_id.clone()),
+                agency_id,
+                agency_url: options.agency_url.clone(),
+                agency_timezone: options.agency_timezone.clone(),
+            })
+            .collect();
+        feed.stops = stop_names
+            .into_iter()
+            .map(|(stop_id, stop_name)| {
+                let coordinates = options.stop_coordinates.get(&stop_id);
+                Stop {
+                    stop_lat: coordinates.map(|c| c.0),
+                    stop_lon: coordinates.map(|c| c.1),
+                    stop_id,
+                    stop_name,
+                }
+            })
+            .collect();
+        feed.routes = routes.into_values().collect();
+        Ok(feed)
+    }
+
+    pub fn agencies(&self) -> &[Agency] {
+        &self.agencies
+    }
+    pub fn stops(&self) -> &[Stop] {
+        &self.stops
+    }
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+    pub fn trips(&self) -> &[Trip] {
+        &self.trips
+    }
+    pub fn stop_times(&self) -> &[StopTime] {
+        &self.stop_times
+    }
+    pub fn calendar(&self) -> &[Calendar] {
+        &self.calendar
+    }
+    pub fn calendar_dates(&self) -> &[CalendarDate] {
+        &self.calendar_dates
+    }
+
+    /// Writes every table into `dir`, which must already exist.
+    pub fn write_dir(&self, dir: &Path) -> GtfsResult<()> {
+        for table in GtfsTable::ALL.iter() {
+            let file = File::create(dir.join(table.file_name()))?;
+            self.write_table(*table, io::BufWriter::new(file))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_table<W: Write>(&self, table: GtfsTable, dst: W) -> GtfsResult<()> {
+        let mut csv = csv::Writer::from_writer(dst);
+        match table {
+            GtfsTable::Agency => {
+                csv.write_record(["agency_id", "agency_name", "agency_url", "agency_timezone"])?;
+                for a in &self.agencies {
+                    csv.write_record([
+                        &a.agency_id,
+                        &a.agency_name,
+                        &a.agency_url,
+                        &a.agency_timezone,
+                    ])?;
+                }
+            }
+            GtfsTable::Stops => {
+                csv.write_record(["stop_id", "stop_code", "stop_name", "stop_lat", "stop_lon"])?;
+                for s in &self.stops {
+                    csv.write_record([
+                        s.stop_id.clone(),
+                        s.stop_id.clone(),
+                        s.stop_name.clone(),
+                        s.stop_lat.map(|l| l.to_string()).unwrap_or_default(),
+                        s.stop_lon.map(|l| l.to_string()).unwrap_or_default(),
+                    ])?;
+                }
+            }
+            GtfsTable::Routes => {
+                csv.write_record([
+                    "route_id",
+                    "agency_id",
+                    "route_short_name",
+                    "route_long_name",
+                    "route_type",
+                ])?;
+                for r in &self.routes {
+                    csv.write_record([
+                        r.route_id.clone(),
+                        r.agency_id.clone(),
+                        String::new(),
+                        r.route_long_name.clone(),
+                        r.route_type.to_string(),
+                    ])?;
+                }
+            }
+            GtfsTable::Trips => {
+                csv.write_record(["route_id", "service_id", "trip_id", "trip_short_name"])?;
+                for t in &self.trips {
+                    csv.write_record([
+                        &t.route_id,
+                        &t.service_id,
+                        &t.trip_id,
+                        t.trip_short_name.as_deref().unwrap_or(""),
+                    ])?;
+                }
+            }
+            GtfsTable::StopTimes => {
+                csv.write_record([
+                    "trip_id",
+                    "arrival_time",
+                    "departure_time",
+                    "stop_id",
+                    "stop_sequence",
+                    "pickup_type",
+                    "drop_off_type",
+                ])?;
+                for st in &self.stop_times {
+                    csv.write_record([
+                        st.trip_id.clone(),
+                        gtfs_time(st.arrival_time),
+                        gtfs_time(st.departure_time),
+                        st.stop_id.clone(),
+                        st.stop_sequence.to_string(),
+                        st.pickup_type.to_string(),
+                        st.drop_off_type.to_string(),
+                    ])?;
+                }
+            }
+            GtfsTable::Calendar => {
+                csv.write_record([
+                    "service_id",
+                    "monday",
+                    "tuesday",
+                    "wednesday",
+                    "thursday",
+                    "friday",
+                    "saturday",
+                    "sunday",
+                    "start_date",
+                    "end_date",
+                ])?;
+                for c in &self.calendar {
+                    let mut record = vec![c.service_id.clone()];
+                    for day in [
+                        Days::MON,
+                        Days::TUE,
+                        Days::WED,
+                        Days::THU,
+                        Days::FRI,
+                        Days::SAT,
+                        Days::SUN,
+                    ] {
+                        record.push(if c.days.contains(day) { "1" } else { "0" }.to_owned());
+                    }
+                    record.push(gtfs_date(c.start_date));
+                    record.push(gtfs_date(c.end_date));
+                    csv.write_record(record)?;
+                }
+            }
+            GtfsTable::CalendarDates => {
+                csv.write_record(["service_id", "date", "exception_type"])?;
+                for cd in &self.calendar_dates {
+                    csv.write_record([
+                        cd.service_id.clone(),
+                        gtfs_date(cd.date),
+                        cd.exception_type.to_string(),
+                    ])?;
+                }
+            }
+        }
+        csv.flush()?;
+        Ok(())
+    }
+}
+
+fn trip_id(schedule: &Schedule) -> Result<String, CIFParseError> {
+    let bs = schedule.schedule();
+    Ok(format!(
+        "{}_{}_{}",
+        bs.uid()?,
+        gtfs_date(bs.start_date()?),
+        char::from(bs.stp()?.code())
+    ))
+}
+
+// Whether a plan with greater precedence than `stp` applies to the same
+// train on `date`.
+fn overridden(stp: Stp, others: &[&Schedule], date: NaiveDate) -> Result<bool, CIFParseError> {
+    for other in others {
+        let bs = other.schedule();
+        if precedence(bs.stp()?) > precedence(stp) && bs.runs_on(date)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn route_type(category: Option<TrainCategory>) -> u16 {
+    match category {
+        Some(TrainCategory::BusReplacement) | Some(TrainCategory::BusService) => 3,
+        Some(TrainCategory::Ship) => 4,
+        _ => 2,
+    }
+}
+
+// The activities at each location, in the same order as `call_times`.
+fn activities(schedule: &Schedule) -> Result<Vec<Activities>, CIFParseError> {
+    let mut activities = Vec::new();
+    if let Some(origin) = schedule.origin() {
+        activities.push(origin.activity()?);
+    }
+    for intermediate in schedule.intermediates() {
+        activities.push(intermediate.location().activity()?);
+    }
+    if let Some(terminating) = schedule.terminating() {
+        activities.push(terminating.activity()?);
+    }
+    Ok(activities)
+}
+
+fn stop_times(
+    schedule: &Schedule,
+    trip_id: &str,
+    crs_of_tiploc: &BTreeMap<String, String>,
+) -> Result<Vec<StopTime>, CIFParseError> {
+    let start_date = schedule.schedule().start_date()?;
+    let midnight = start_date.and_hms_opt(0, 0, 0).expect("midnight");
+    let since_midnight = |t: NaiveDateTime| t - midnight;
+
+    let calls = schedule.call_times(start_date)?;
+    let mut stop_times = Vec::new();
+    for (call, activities) in calls.iter().zip(activities(schedule)?) {
+        let stop_id = match crs_of_tiploc.get(call.tiploc.as_ref()) {
+            Some(crs) => crs,
+            None => continue,
+        };
+        if !activities.is_public_stop() {
+            continue;
+        }
+        let (arrival, departure) = match (call.public_arrival, call.public_departure) {
+            (None, None) => continue,
+            (arr, dep) => (
+                arr.or(dep).expect("public time"),
+                dep.or(arr).expect("public time"),
+            ),
+        };
+        let policy = |allowed| {
+            if activities.is_request_stop() {
+                COORDINATE_WITH_DRIVER
+            } else if allowed {
+                REGULAR
+            } else {
+                NONE
+            }
+        };
+        stop_times.push(StopTime {
+            trip_id: trip_id.to_owned(),
+            arrival_time: since_midnight(arrival),
+            departure_time: since_midnight(departure),
+            stop_id: stop_id.clone(),
+            stop_sequence: stop_times.len() as u32 + 1,
+            pickup_type: policy(activities.picks_up()),
+            drop_off_type: policy(activities.sets_down()),
+        });
+    }
+    Ok(stop_times)
+}
+
+fn gtfs_time(time: Duration) -> String {
+    let secs = time.num_seconds();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn gtfs_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Reader;
+
+    static EXTRACT: &[u8] = b"\
+HDTPS.UDFROC1.PD2006191906201947DFROC2E       FA190620190621                    \n\
+TILEEDS  00849900TLEEDS                     17132   0LDSLEEDS                   \n\
+TIGARFRTH00849700HGARFORTH                  17111   0GRFGARFORTH                \n\
+TIYORK   00826300EYORK                      16311   0YRKYORK                    \n\
+TICOLTONJ00826400YCOLTON JN                 16301   0                           \n\
+BSNC123452005172006301111100 POO1A01    122974000 EMU    100      S            P\n\
+BX         NTY                                                                  \n\
+LOLEEDS   0900 09008  ML     TB                                                 \n\
+LIGARFRTH 0910 0911      09100911         T                                     \n\
+LICOLTONJ           0920H00000000                                               \n\
+LTYORK    0930 09303     TF                                                     \n\
+BSNC123452005252005291111100 POO1A01    122974000 EMU    100      S            O\n\
+BX         NTY                                                                  \n\
+LOLEEDS   1000 10008  ML     TB                                                 \n\
+LIGARFRTH 1010 1011      10101011         D                                     \n\
+LTYORK    1030 10303     TF                                                     \n\
+BSNC123452006012006011000000            1                                      C\n\
+BSNC543212005172006300000011 PBR1B02    122974000 EMU    100      S            P\n\
+BX         GRY                                                                  \n\
+LOYORK    2345 23453  ML     TB                                                 \n\
+LIGARFRTH 2355 2356      23552356         T R                                   \n\
+LTLEEDS   0010 00108     TF                                                     \n\
+ZZ                                                                              \n";
+
+    fn feed() -> GtfsFeed {
+        let mut timetable = Timetable::new();
+        timetable.apply(Reader::new(EXTRACT)).expect("apply");
+        GtfsFeed::from_timetable(&timetable, &GtfsOptions::default()).expect("feed")
+    }
+
+    fn table(feed: &GtfsFeed, table: GtfsTable) -> String {
+        let mut out = Vec::new();
+        feed.write_table(table, &mut out).expect("write");
+        String::from_utf8(out).expect("utf8")
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn should_list_stations_with_crs_codes() {
+        let feed = feed();
+        assert_eq!(
+            table(&feed, GtfsTable::Stops),
+            "stop_id,stop_code,stop_name,stop_lat,stop_lon\n\
+             GRF,GRF,GARFORTH,,\n\
+             LDS,LDS,LEEDS,,\n\
+             YRK,YRK,YORK,,\n"
+        );
+        assert_eq!(
+            table(&feed, GtfsTable::Agency),
+            "agency_id,agency_name,agency_url,agency_timezone\n\
+             GR,GR,https://www.nationalrail.co.uk/,Europe/London\n\
+             NT,NT,https://www.nationalrail.co.uk/,Europe/London\n"
+        );
+    }
+
+    #[test]
+    fn should_write_public_stop_times() {
+        let feed = feed();
+        assert_eq!(feed.trips().len(), 3);
+        let stop_times = table(&feed, GtfsTable::StopTimes);
+        let permanent = stop_times
+            .lines()
+            .filter(|l| l.starts_with("C12345_20200517_P"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            permanent,
+            vec![
+                "C12345_20200517_P,09:00:00,09:00:00,LDS,1,0,1",
+                "C12345_20200517_P,09:10:00,09:11:00,GRF,2,0,0",
+                "C12345_20200517_P,09:30:00,09:30:00,YRK,3,1,0",
+            ]
+        );
+        let overlay_garforth = &feed.stop_times()[4];
+        assert_eq!(overlay_garforth.stop_id, "GRF");
+        assert_eq!(overlay_garforth.pickup_type, NONE);
+        assert_eq!(overlay_garforth.drop_off_type, REGULAR);
+    }
+
+    #[test]
+    fn should_continue_times_past_midnight() {
+        let feed = feed();
+        let overnight = feed
+            .stop_times()
+            .iter()
+            .filter(|st| st.trip_id == "C54321_20200517_P")
+            .collect::<Vec<_>>();
+        assert_eq!(gtfs_time(overnight[1].departure_time), "23:56:00");
+        assert_eq!(overnight[1].pickup_type, COORDINATE_WITH_DRIVER);
+        assert_eq!(gtfs_time(overnight[2].arrival_time), "24:10:00");
+
+        let route = feed
+            .routes()
+            .iter()
+            .find(|r| r.agency_id == "GR")
+            .expect("GR route");
+        assert_eq!(route.route_type, 3);
+        assert_eq!(route.route_long_name, "YORK to LEEDS");
+    }
+
+    #[test]
+    fn should_remove_dates_covered_by_overlays_and_cancellations() {
+        let feed = feed();
+        let calendar = &feed.calendar()[0];
+        assert_eq!(calendar.service_id, "C12345_20200517_P");
+        assert_eq!(calendar.start_date, date(2020, 5, 17));
+        assert_eq!(calendar.end_date, date(2020, 6, 30));
+
+        let removed = feed
+            .calendar_dates()
+            .iter()
+            .filter(|cd| cd.service_id == "C12345_20200517_P")
+            .map(|cd| cd.date)
+            .collect::<Vec<_>>();
+        let mut expected = date(2020, 5, 25).iter_days().take(5).collect::<Vec<_>>();
+        expected.push(date(2020, 6, 1));
+        assert_eq!(removed, expected);
+        assert!(feed
+            .calendar_dates()
+            .iter()
+            .all(|cd| cd.exception_type == SERVICE_REMOVED));
+
+        assert!(table(&feed, GtfsTable::Calendar)
+            .contains("C12345_20200525_O,1,1,1,1,1,0,0,20200525,20200529\n"));
+    }
+}
@@ -2,16 +2,46 @@ use std::fmt;
 
 use bytes::Bytes;
 use chrono::{NaiveDate, NaiveDateTime};
+use thiserror::Error;
 
-use crate::helpers::{string_of_slice_opt, time_from_slice};
-use crate::{errors::CIFParseError, helpers::ddmmyy_from_slice};
+use crate::helpers::{parse_field, string_of_slice_opt, time_from_slice, RecordBuf};
+use crate::{
+    errors::{BuildError, CIFParseError},
+    helpers::ddmmyy_from_slice,
+};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FullOrUpdate {
     Full,
     Update,
 }
 
+impl FullOrUpdate {
+    pub(crate) fn code(&self) -> u8 {
+        match self {
+            FullOrUpdate::Full => b'F',
+            FullOrUpdate::Update => b'U',
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SequenceError {
+    #[error("Parsing header: {0}")]
+    Parse(#[from] CIFParseError),
+    #[error("File {current} follows {last:?}, but {expected} was last applied")]
+    Gap {
+        expected: String,
+        current: String,
+        last: Option<String>,
+    },
+    #[error("File {current} has already been applied")]
+    Replay { current: String },
+    #[error("File {current} is a full extract")]
+    FullExtract { current: String },
+}
+
 #[derive(Clone, Eq, PartialEq)]
 pub struct Header {
     record: Bytes,
@@ -26,41 +56,112 @@ impl Header {
     }
 
     pub fn file_mainframe_identity(&self) -> Result<&str, CIFParseError> {
-        Ok(std::str::from_utf8(&self.record[2..22])?)
+        parse_field(&self.record, "file_mainframe_identity", 2..22, |s| {
+            Ok(std::str::from_utf8(s)?)
+        })
     }
 
     pub fn extracted_at(&self) -> Result<NaiveDateTime, CIFParseError> {
-        let date = ddmmyy_from_slice(&self.record[22..28])?;
-        let time = time_from_slice(&self.record[28..32])?;
+        let date = parse_field(&self.record, "extracted_at", 22..28, ddmmyy_from_slice)?;
+        let time = parse_field(&self.record, "extracted_at", 28..32, time_from_slice)?;
         Ok(date.and_time(time))
     }
 
     pub fn current_file(&self) -> Result<&str, CIFParseError> {
-        Ok(std::str::from_utf8(&self.record[32..39])?)
+        parse_field(&self.record, "current_file", 32..39, |s| {
+            Ok(std::str::from_utf8(s)?)
+        })
     }
     pub fn last_file(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[39..46])?)
+        parse_field(&self.record, "last_file", 39..46, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn update(&self) -> Result<FullOrUpdate, CIFParseError> {
-        let val = match self.record[46] {
-            b'F' => FullOrUpdate::Full,
-            b'U' => FullOrUpdate::Update,
-            _ => return Err(CIFParseError::InvalidItem),
-        };
-        Ok(val)
+        parse_field(&self.record, "update", 46..47, |s| {
+            let val = match s[0] {
+                b'F' => FullOrUpdate::Full,
+                b'U' => FullOrUpdate::Update,
+                _ => return Err(CIFParseError::InvalidItem),
+            };
+            Ok(val)
+        })
     }
 
     pub fn version(&self) -> Result<&str, CIFParseError> {
-        Ok(std::str::from_utf8(&self.record[47..48])?)
+        parse_field(&self.record, "version", 47..48, |s| {
+            Ok(std::str::from_utf8(s)?)
+        })
     }
     pub fn user_start_date(&self) -> Result<NaiveDate, CIFParseError> {
-        ddmmyy_from_slice(&self.record[48..54])
+        parse_field(&self.record, "user_start_date", 48..54, ddmmyy_from_slice)
     }
     pub fn user_end_date(&self) -> Result<NaiveDate, CIFParseError> {
-        ddmmyy_from_slice(&self.record[54..60])
+        parse_field(&self.record, "user_end_date", 54..60, ddmmyy_from_slice)
+    }
+
+    /// Checks that this file is the update that directly follows the file
+    /// `last_applied`, eg: `DFROC1I` after `DFROC1H`.
+    ///
+    /// `last_applied` itself, or any of the dozen or so files before it, is
+    /// reported as a replay rather than a gap.
+    pub fn check_follows(&self, last_applied: &str) -> Result<(), SequenceError> {
+        let current = self.current_file()?;
+        if generations_behind(current, last_applied).is_some_and(|n| n <= MAX_REPLAY_GENERATIONS) {
+            return Err(SequenceError::Replay {
+                current: current.to_owned(),
+            });
+        }
+        if self.update()? == FullOrUpdate::Full {
+            return Err(SequenceError::FullExtract {
+                current: current.to_owned(),
+            });
+        }
+        let last = self.last_file()?;
+        if last != Some(last_applied)
+            || next_file_reference(last_applied).as_deref() != Some(current)
+        {
+            return Err(SequenceError::Gap {
+                expected: last_applied.to_owned(),
+                current: current.to_owned(),
+                last: last.map(str::to_owned),
+            });
+        }
+        Ok(())
     }
 }
 
+// File references end in a generation letter, which runs from A to Z and
+// then wraps back around to A.
+const GENERATIONS: u8 = 26;
+
+// Since generation letters wrap, how far back we look for replays before
+// treating a file as being ahead instead.
+const MAX_REPLAY_GENERATIONS: u8 = GENERATIONS / 2 - 1;
+
+// How many generations `file` comes before `later` in the same sequence.
+fn generations_behind(file: &str, later: &str) -> Option<u8> {
+    let (prefix, generation) = file.split_at_checked(file.len().checked_sub(1)?)?;
+    let (later_prefix, later_generation) = later.split_at_checked(later.len().checked_sub(1)?)?;
+    let (generation, later_generation) = (generation.as_bytes()[0], later_generation.as_bytes()[0]);
+    if prefix != later_prefix
+        || !generation.is_ascii_uppercase()
+        || !later_generation.is_ascii_uppercase()
+    {
+        return None;
+    }
+    Some((later_generation + GENERATIONS - generation) % GENERATIONS)
+}
+
+fn next_file_reference(file: &str) -> Option<String> {
+    let next = match *file.as_bytes().last()? {
+        b'Z' => 'A',
+        ch @ b'A'..=b'Y' => char::from(ch + 1),
+        _ => return None,
+    };
+    Some(format!("{}{}", &file[..file.len() - 1], next))
+}
+
 impl fmt::Debug for Header {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s = f.debug_struct("Header");
@@ -75,6 +176,46 @@ impl fmt::Debug for Header {
     }
 }
 
+/// Builds a [`Header`] record, eg: for writing out a generated extract.
+pub struct HeaderBuilder {
+    record: RecordBuf,
+}
+
+impl HeaderBuilder {
+    pub fn new(
+        file_mainframe_identity: &str,
+        extracted_at: NaiveDateTime,
+        current_file: &str,
+        update: FullOrUpdate,
+        user_start_date: NaiveDate,
+        user_end_date: NaiveDate,
+    ) -> Self {
+        let mut record = RecordBuf::new(b"HD");
+        record.put("file_mainframe_identity", 2..22, file_mainframe_identity);
+        record.put_ddmmyy("extracted_at", 22..28, extracted_at.date());
+        record.put_time("extracted_at", 28..32, extracted_at.time());
+        record.put("current_file", 32..39, current_file);
+        record.put_byte(46, update.code());
+        record.put("version", 47..48, "A");
+        record.put_ddmmyy("user_start_date", 48..54, user_start_date);
+        record.put_ddmmyy("user_end_date", 54..60, user_end_date);
+        HeaderBuilder { record }
+    }
+
+    pub fn last_file(mut self, last_file: &str) -> Self {
+        self.record.put("last_file", 39..46, last_file);
+        self
+    }
+    pub fn version(mut self, version: &str) -> Self {
+        self.record.put("version", 47..48, version);
+        self
+    }
+
+    pub fn build(self) -> Result<Header, BuildError> {
+        Ok(Header::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use chrono::NaiveDate;
@@ -139,6 +280,76 @@ mod test {
         );
     }
 
+    #[test]
+    fn should_follow_previous_file() {
+        let header = example();
+        header.check_follows("DFROC1H").expect("in sequence");
+    }
+
+    #[test]
+    fn should_detect_gap() {
+        let header = example();
+        let e = header.check_follows("DFROC1G").unwrap_err();
+        assert!(
+            matches!(e, SequenceError::Gap { ref expected, .. } if expected == "DFROC1G"),
+            "{:?} should be a gap",
+            e
+        );
+    }
+
+    #[test]
+    fn should_detect_replay() {
+        let header = example();
+        let e = header.check_follows("DFROC1I").unwrap_err();
+        assert!(
+            matches!(e, SequenceError::Replay { ref current } if current == "DFROC1I"),
+            "{:?} should be a replay",
+            e
+        );
+    }
+
+    #[test]
+    fn should_detect_replay_of_older_file() {
+        let header = Header::from_record(Bytes::from(
+            b"HDTPS.UDFROC1.PD2006272706201934DFROC1GDFROC1FUA270620270621                    "
+                as &[u8],
+        ));
+        let e = header.check_follows("DFROC1H").unwrap_err();
+        assert!(
+            matches!(e, SequenceError::Replay { ref current } if current == "DFROC1G"),
+            "{:?} should be a replay",
+            e
+        );
+    }
+
+    #[test]
+    fn should_detect_replay_across_wrap() {
+        assert_eq!(generations_behind("DFROC1Y", "DFROC1B"), Some(3));
+        assert_eq!(generations_behind("DFROC1B", "DFROC1Y"), Some(23));
+        assert_eq!(generations_behind("DFROC1B", "DFROC2B"), None);
+    }
+
+    #[test]
+    fn should_detect_full_extract() {
+        let header = Header::from_record(Bytes::from(
+            b"HDTPS.UDFROC1.PD2006191906201947DFROC2E       FA190620190621                    "
+                as &[u8],
+        ));
+        let e = header.check_follows("DFROC2D").unwrap_err();
+        assert!(
+            matches!(e, SequenceError::FullExtract { .. }),
+            "{:?} should be a full extract",
+            e
+        );
+    }
+
+    #[test]
+    fn should_wrap_generation_letter() {
+        assert_eq!(next_file_reference("DFROC1Z").as_deref(), Some("DFROC1A"));
+        assert_eq!(next_file_reference("DFROC1A").as_deref(), Some("DFROC1B"));
+        assert_eq!(next_file_reference(""), None);
+    }
+
     fn example() -> Header {
         // From sample-larger.cif
         let record = Bytes::from(
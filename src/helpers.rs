@@ -1,12 +1,20 @@
+use std::ops::Range;
+
 use bitflags::bitflags;
 use bytes::Bytes;
+use chrono::Datelike;
+use chrono::Duration;
 use chrono::NaiveDate;
 use chrono::NaiveTime;
+use chrono::Timelike;
+use chrono::Weekday;
 
-use crate::errors::CIFParseError;
+use crate::errors::{BuildError, CIFParseError, FieldError};
+use crate::Tiploc;
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Days: u8 {
         const MON = 0b00000001;
         const TUE = 0b0000010;
@@ -18,6 +26,70 @@ bitflags! {
     }
 }
 
+impl From<Weekday> for Days {
+    fn from(day: Weekday) -> Self {
+        match day {
+            Weekday::Mon => Days::MON,
+            Weekday::Tue => Days::TUE,
+            Weekday::Wed => Days::WED,
+            Weekday::Thu => Days::THU,
+            Weekday::Fri => Days::FRI,
+            Weekday::Sat => Days::SAT,
+            Weekday::Sun => Days::SUN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Speed(pub u16);
+
+impl Speed {
+    pub fn mph(&self) -> u16 {
+        self.0
+    }
+}
+
+/// A timing allowance, which CIF expresses in minutes with an optional `H`
+/// suffix for an extra half minute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Allowance {
+    half_minutes: u16,
+}
+
+impl Allowance {
+    pub fn from_half_minutes(half_minutes: u16) -> Self {
+        Allowance { half_minutes }
+    }
+    pub fn half_minutes(&self) -> u16 {
+        self.half_minutes
+    }
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(i64::from(self.half_minutes) * 30)
+    }
+}
+
+/// Parses the `columns` of `record` with `parse`, noting the field and
+/// record if it fails.
+pub(crate) fn parse_field<'a, T>(
+    record: &'a Bytes,
+    field: &'static str,
+    columns: Range<usize>,
+    parse: impl FnOnce(&'a [u8]) -> Result<T, CIFParseError>,
+) -> Result<T, CIFParseError> {
+    parse(&record[columns.clone()]).map_err(|source| {
+        CIFParseError::Field(Box::new(FieldError {
+            record: record.clone(),
+            field,
+            columns,
+            position: None,
+            source,
+        }))
+    })
+}
+
 pub(crate) fn string_of_slice(val: &[u8]) -> Result<&str, std::str::Utf8Error> {
     let s = std::str::from_utf8(val)?.trim_end();
     Ok(s)
@@ -87,6 +159,31 @@ pub(crate) fn time_half_from_slice_opt(slice: &[u8]) -> Result<Option<NaiveTime>
     }
 }
 
+pub(crate) fn speed_from_slice_opt(slice: &[u8]) -> Result<Option<Speed>, CIFParseError> {
+    if let Some(s) = string_of_slice_opt(slice)? {
+        Ok(Some(Speed(lexical_core::parse(s.as_bytes())?)))
+    } else {
+        Ok(None)
+    }
+}
+
+pub(crate) fn allowance_from_slice_opt(slice: &[u8]) -> Result<Option<Allowance>, CIFParseError> {
+    let s = std::str::from_utf8(slice)?.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    let (minutes, half) = match s.strip_suffix('H') {
+        Some(minutes) => (minutes, 1),
+        None => (s, 0),
+    };
+    let minutes: u16 = if minutes.is_empty() {
+        0
+    } else {
+        lexical_core::parse(minutes.as_bytes())?
+    };
+    Ok(Some(Allowance::from_half_minutes(minutes * 2 + half)))
+}
+
 pub(crate) fn days_from_slice(slice: &[u8]) -> Result<Days, CIFParseError> {
     const DAYS: &[Days] = &[
         Days::MON,
@@ -111,6 +208,187 @@ pub(crate) fn days_from_slice(slice: &[u8]) -> Result<Days, CIFParseError> {
     Ok(days)
 }
 
+/// Formats days as a `1`/`0` flag for each of Monday to Sunday.
+pub(crate) fn days_to_string(days: Days) -> String {
+    const DAYS: &[Days] = &[
+        Days::MON,
+        Days::TUE,
+        Days::WED,
+        Days::THU,
+        Days::FRI,
+        Days::SAT,
+        Days::SUN,
+    ];
+    DAYS.iter()
+        .map(|day| if days.contains(*day) { '1' } else { '0' })
+        .collect()
+}
+
+pub(crate) fn time_to_string(time: NaiveTime) -> String {
+    format!("{:02}{:02}", time.hour(), time.minute())
+}
+
+/// Formats a time as `HHMM`, with a trailing `H` for the half minute.
+pub(crate) fn time_half_to_string(time: NaiveTime) -> String {
+    let half = if time.second() >= 30 { "H" } else { " " };
+    format!("{:02}{:02}{}", time.hour(), time.minute(), half)
+}
+
+pub(crate) fn allowance_to_string(allowance: Allowance) -> String {
+    let minutes = allowance.half_minutes / 2;
+    let half = allowance.half_minutes % 2 == 1;
+    match (minutes, half) {
+        (0, true) => " H".to_owned(),
+        (minutes, true) => format!("{}H", minutes),
+        (minutes, false) => format!("{}", minutes),
+    }
+}
+
+// 80 characters plus a newline
+pub(crate) const CIF_LINE_LEN: usize = 81;
+
+/// An 80 column record under construction, initially blank. Fields are
+/// checked as they're written, and the first problem is reported when the
+/// record is finished.
+pub(crate) struct RecordBuf {
+    buf: [u8; CIF_LINE_LEN],
+    error: Option<BuildError>,
+}
+
+impl RecordBuf {
+    pub(crate) fn new(kind: &[u8; 2]) -> Self {
+        let mut buf = [b' '; CIF_LINE_LEN];
+        buf[0..2].copy_from_slice(kind);
+        buf[CIF_LINE_LEN - 1] = b'\n';
+        RecordBuf { buf, error: None }
+    }
+
+    fn fail(&mut self, error: BuildError) {
+        self.error.get_or_insert(error);
+    }
+
+    /// Writes `val` left justified into `range`, padding with spaces.
+    pub(crate) fn put(&mut self, field: &'static str, range: Range<usize>, val: &str) {
+        if !val.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+            return self.fail(BuildError::InvalidChars {
+                field,
+                value: val.to_owned(),
+            });
+        }
+        if val.len() > range.len() {
+            return self.fail(BuildError::TooLong {
+                field,
+                value: val.to_owned(),
+                max: range.len(),
+            });
+        }
+        let dst = &mut self.buf[range];
+        dst.fill(b' ');
+        dst[..val.len()].copy_from_slice(val.as_bytes());
+    }
+    pub(crate) fn put_byte(&mut self, at: usize, val: u8) {
+        self.buf[at] = val;
+    }
+
+    pub(crate) fn put_tiploc(&mut self, field: &'static str, range: Range<usize>, tiploc: &Tiploc) {
+        let val = tiploc.as_ref();
+        if val.is_empty()
+            || !val
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        {
+            return self.fail(BuildError::InvalidChars {
+                field,
+                value: val.to_owned(),
+            });
+        }
+        self.put(field, range, val)
+    }
+    // Train UIDs are a letter followed by five digits.
+    pub(crate) fn put_uid(&mut self, field: &'static str, range: Range<usize>, uid: &str) {
+        let bytes = uid.as_bytes();
+        let valid = bytes.len() == 6
+            && bytes[0].is_ascii_uppercase()
+            && bytes[1..].iter().all(u8::is_ascii_digit);
+        if !valid {
+            return self.fail(BuildError::InvalidUid {
+                field,
+                value: uid.to_owned(),
+            });
+        }
+        self.put(field, range, uid)
+    }
+
+    fn check_date(&mut self, field: &'static str, date: NaiveDate) -> bool {
+        // Years are only written as two digits, which we read back as 20xx.
+        let valid = (2000..2100).contains(&date.year());
+        if !valid {
+            self.fail(BuildError::DateOutOfRange { field, date });
+        }
+        valid
+    }
+    pub(crate) fn put_ddmmyy(&mut self, field: &'static str, range: Range<usize>, date: NaiveDate) {
+        if self.check_date(field, date) {
+            let s = format!(
+                "{:02}{:02}{:02}",
+                date.day(),
+                date.month(),
+                date.year() % 100
+            );
+            self.put(field, range, &s)
+        }
+    }
+    pub(crate) fn put_yymmdd(&mut self, field: &'static str, range: Range<usize>, date: NaiveDate) {
+        if self.check_date(field, date) {
+            let s = format!(
+                "{:02}{:02}{:02}",
+                date.year() % 100,
+                date.month(),
+                date.day()
+            );
+            self.put(field, range, &s)
+        }
+    }
+    pub(crate) fn put_time(&mut self, field: &'static str, range: Range<usize>, time: NaiveTime) {
+        if time.second() != 0 || time.nanosecond() != 0 {
+            return self.fail(BuildError::InvalidTime { field, time });
+        }
+        self.put(field, range, &time_to_string(time))
+    }
+    pub(crate) fn put_time_half(
+        &mut self,
+        field: &'static str,
+        range: Range<usize>,
+        time: NaiveTime,
+    ) {
+        match (time.second(), time.nanosecond()) {
+            (0, 0) | (30, 0) => self.put(field, range, &time_half_to_string(time)),
+            _ => self.fail(BuildError::InvalidTime { field, time }),
+        }
+    }
+    pub(crate) fn put_days(&mut self, field: &'static str, range: Range<usize>, days: Days) {
+        self.put(field, range, &days_to_string(days))
+    }
+    pub(crate) fn put_speed(&mut self, field: &'static str, range: Range<usize>, speed: Speed) {
+        self.put(field, range, &format!("{:03}", speed.0))
+    }
+    pub(crate) fn put_allowance(
+        &mut self,
+        field: &'static str,
+        range: Range<usize>,
+        allowance: Allowance,
+    ) {
+        self.put(field, range, &allowance_to_string(allowance))
+    }
+
+    pub(crate) fn finish(self) -> Result<Bytes, BuildError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(Bytes::copy_from_slice(&self.buf)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -137,6 +415,138 @@ mod test {
         assert_eq!(result, None)
     }
 
+    #[test]
+    fn speed_should_parse_mph() {
+        let result = speed_from_slice_opt(b"075").expect("parse");
+        assert_eq!(result, Some(Speed(75)));
+    }
+
+    #[test]
+    fn speed_should_parse_empty() {
+        let result = speed_from_slice_opt(b"   ").expect("parse");
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn allowance_should_parse_whole_minutes() {
+        let result = allowance_from_slice_opt(b"10").expect("parse");
+        assert_eq!(result.map(|a| a.duration()), Some(Duration::minutes(10)));
+    }
+
+    #[test]
+    fn allowance_should_parse_minutes_and_a_half() {
+        let result = allowance_from_slice_opt(b"1H").expect("parse");
+        assert_eq!(result.map(|a| a.duration()), Some(Duration::seconds(90)));
+    }
+
+    #[test]
+    fn allowance_should_parse_lone_half() {
+        let result = allowance_from_slice_opt(b" H").expect("parse");
+        assert_eq!(result, Some(Allowance::from_half_minutes(1)));
+    }
+
+    #[test]
+    fn allowance_should_parse_empty() {
+        let result = allowance_from_slice_opt(b"  ").expect("parse");
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn allowance_should_reject_garbage() {
+        assert!(allowance_from_slice_opt(b"X ").is_err());
+    }
+
+    #[test]
+    fn record_buf_should_round_trip_fields() {
+        let mut buf = RecordBuf::new(b"XX");
+        buf.put("a", 2..5, "AB");
+        let date = NaiveDate::from_ymd_opt(2020, 6, 28).unwrap();
+        buf.put_yymmdd("b", 5..11, date);
+        let time = NaiveTime::from_hms_opt(12, 11, 30).unwrap();
+        buf.put_time_half("c", 11..16, time);
+        buf.put_days("d", 16..23, Days::MON | Days::SUN);
+        buf.put_allowance("e", 23..25, Allowance::from_half_minutes(1));
+        buf.put_speed("f", 25..28, Speed(75));
+        let record = buf.finish().expect("finish");
+        assert_eq!(record.len(), CIF_LINE_LEN);
+        assert_eq!(&record[..28], b"XXAB 2006281211H1000001 H075");
+        assert_eq!(record[80], b'\n');
+
+        assert_eq!(string_of_slice(&record[2..5]).unwrap(), "AB");
+        assert_eq!(
+            time_half_from_slice(&record[11..16]).unwrap(),
+            NaiveTime::from_hms_opt(12, 11, 30).unwrap()
+        );
+        assert_eq!(
+            allowance_from_slice_opt(&record[23..25]).unwrap(),
+            Some(Allowance::from_half_minutes(1))
+        );
+    }
+
+    #[test]
+    fn record_buf_should_report_first_invalid_field() {
+        let mut buf = RecordBuf::new(b"XX");
+        buf.put("a", 2..5, "ABCD");
+        buf.put_tiploc("b", 5..12, &Tiploc::from("leeds"));
+        assert_eq!(
+            buf.finish().unwrap_err(),
+            BuildError::TooLong {
+                field: "a",
+                value: "ABCD".to_owned(),
+                max: 3
+            }
+        );
+    }
+
+    #[test]
+    fn record_buf_should_reject_unrepresentable_values() {
+        let mut buf = RecordBuf::new(b"XX");
+        buf.put_tiploc("tiploc", 2..9, &Tiploc::from("leeds"));
+        assert!(matches!(
+            buf.finish(),
+            Err(BuildError::InvalidChars {
+                field: "tiploc",
+                ..
+            })
+        ));
+
+        let mut buf = RecordBuf::new(b"XX");
+        buf.put_uid("uid", 2..8, "C0004");
+        assert!(matches!(
+            buf.finish(),
+            Err(BuildError::InvalidUid { field: "uid", .. })
+        ));
+
+        let mut buf = RecordBuf::new(b"XX");
+        let date = NaiveDate::from_ymd_opt(1999, 12, 31).unwrap();
+        buf.put_yymmdd("start_date", 2..8, date);
+        assert!(matches!(
+            buf.finish(),
+            Err(BuildError::DateOutOfRange {
+                field: "start_date",
+                ..
+            })
+        ));
+
+        let mut buf = RecordBuf::new(b"XX");
+        let time = NaiveTime::from_hms_opt(12, 11, 15).unwrap();
+        buf.put_time_half("pass", 2..7, time);
+        assert!(matches!(
+            buf.finish(),
+            Err(BuildError::InvalidTime { field: "pass", .. })
+        ));
+
+        let mut buf = RecordBuf::new(b"XX");
+        buf.put("platform", 2..5, "1\n");
+        assert!(matches!(
+            buf.finish(),
+            Err(BuildError::InvalidChars {
+                field: "platform",
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn days_should_parse_bitwise_weekdays() {
         let s = b"1111100";
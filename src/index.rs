@@ -0,0 +1,515 @@
+//! A side-car index of where each schedule lives in a CIF file, so that
+//! single schedules can be read without scanning the whole extract.
+//!
+//! The index file is a short header identifying the CIF file it was built
+//! from, followed by a table of schedule keys and the offsets of their basic
+//! schedule records, sorted by key, and then for each TIPLOC, the offsets of
+//! the schedules that call there. All numbers are little-endian.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use bytes::{Buf, BufMut, BytesMut};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use thiserror::Error;
+
+use crate::{
+    Assembled, AssemblerError, CIFParseError, Header, Reader, ReaderError, ReaderOptions, Record,
+    Schedule, ScheduleAssembler, ScheduleKey, Stp,
+};
+
+const MAGIC: &[u8; 8] = b"CIFIDX\x00\x02";
+
+const LENIENT: u8 = 1;
+const RECOVER: u8 = 2;
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+    #[error("I/O: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Reader: {0}")]
+    Reader(#[from] ReaderError),
+    #[error("Assembling schedules: {0}")]
+    Assembler(#[from] AssemblerError),
+    #[error("Parsing record: {0}")]
+    Parse(#[from] CIFParseError),
+    #[error("Index file is corrupt: {0}")]
+    Corrupt(&'static str),
+    #[error("Index is for {indexed} bytes of CIF, but the file is {actual} bytes long")]
+    Stale { indexed: u64, actual: u64 },
+    #[error("Index is for file {indexed:?}, but the file is {actual:?}")]
+    WrongFile {
+        indexed: Option<String>,
+        actual: Option<String>,
+    },
+    #[error("No schedule at byte {offset}")]
+    NotASchedule { offset: usize },
+}
+
+pub type IndexResult<T> = std::result::Result<T, IndexError>;
+
+/// Where a schedule starts in the file.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct IndexEntry {
+    pub key: ScheduleKey,
+    /// The byte offset of the basic schedule record.
+    pub offset: usize,
+}
+
+// Which extract a file holds, going by its header.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Identity {
+    current_file: String,
+    extracted_at: NaiveDateTime,
+}
+
+impl Identity {
+    fn of_header(header: &Header) -> Result<Self, CIFParseError> {
+        Ok(Identity {
+            current_file: header.current_file()?.to_owned(),
+            extracted_at: header.extracted_at()?,
+        })
+    }
+
+    // Files without a header can only be told apart by their length.
+    fn of_source<R: Read + Seek>(src: &mut R, options: ReaderOptions) -> IndexResult<Option<Self>> {
+        src.seek(SeekFrom::Start(0))?;
+        match Reader::with_options(src, options).read_next()? {
+            Some(Record::Header(header)) => Ok(Some(Self::of_header(&header)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn describe(identity: &Option<Self>) -> Option<String> {
+        identity
+            .as_ref()
+            .map(|i| format!("{} extracted at {}", i.current_file, i.extracted_at))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Index {
+    // So we can tell if the file has changed since we indexed it.
+    source_len: u64,
+    identity: Option<Identity>,
+    options: ReaderOptions,
+    // Sorted by key, then in file order.
+    schedules: Vec<IndexEntry>,
+    tiplocs: BTreeMap<String, Vec<usize>>,
+}
+
+impl Index {
+    /// Reads every schedule from `src`, noting where each one starts and
+    /// which TIPLOCs it calls at.
+    pub fn build<R: Read + Seek>(mut src: R, options: ReaderOptions) -> IndexResult<Self> {
+        let source_len = src.seek(SeekFrom::End(0))?;
+        src.seek(SeekFrom::Start(0))?;
+        let mut assembler = ScheduleAssembler::new(Reader::with_options(src, options));
+        let mut identity = None;
+        let mut schedules = Vec::new();
+        let mut tiplocs = BTreeMap::<String, Vec<usize>>::new();
+
+        let mut first = true;
+        while let Some(item) = assembler.read_next()? {
+            let schedule = match item {
+                Assembled::Schedule(schedule) => schedule,
+                Assembled::Other(Record::Header(header)) if first => {
+                    identity = Some(Identity::of_header(&header)?);
+                    first = false;
+                    continue;
+                }
+                Assembled::Other(_) => {
+                    first = false;
+                    continue;
+                }
+            };
+            first = false;
+            let offset = schedule.offset();
+            schedules.push(IndexEntry {
+                key: ScheduleKey::of_schedule(&schedule)?,
+                offset,
+            });
+
+            for tiploc in calling_points(&schedule)? {
+                let offsets = tiplocs.entry(tiploc).or_default();
+                // Schedules can call at the same place more than once.
+                if offsets.last() != Some(&offset) {
+                    offsets.push(offset);
+                }
+            }
+        }
+        schedules.sort();
+
+        Ok(Index {
+            source_len,
+            identity,
+            options,
+            schedules,
+            tiplocs,
+        })
+    }
+
+    /// All of the schedules in the file, sorted by key.
+    pub fn schedules(&self) -> &[IndexEntry] {
+        &self.schedules
+    }
+
+    /// The schedules with the given train UID, sorted by start date and
+    /// STP indicator.
+    pub fn schedules_for_uid(&self, uid: &str) -> &[IndexEntry] {
+        let start = self.schedules.partition_point(|e| e.key.uid.as_str() < uid);
+        let end = self
+            .schedules
+            .partition_point(|e| e.key.uid.as_str() <= uid);
+        &self.schedules[start..end]
+    }
+
+    /// Where the schedule with `key` starts. An update extract may mention
+    /// the same schedule more than once, in which case this is the last.
+    pub fn schedule_offset(&self, key: &ScheduleKey) -> Option<usize> {
+        let end = self.schedules.partition_point(|e| e.key <= *key);
+        self.schedules[..end]
+            .last()
+            .filter(|e| e.key == *key)
+            .map(|e| e.offset)
+    }
+
+    /// The offsets of the schedules that call at `tiploc`, in file order.
+    pub fn calling_at(&self, tiploc: &str) -> &[usize] {
+        self.tiplocs.get(tiploc).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn tiplocs(&self) -> impl Iterator<Item = &str> {
+        self.tiplocs.keys().map(String::as_str)
+    }
+
+    /// Reads the schedule starting at `offset`, along with its location
+    /// records, from `src`, which must be the file this index was built
+    /// from.
+    pub fn read_schedule<R: Read + Seek>(
+        &self,
+        mut src: R,
+        offset: usize,
+    ) -> IndexResult<Schedule> {
+        let actual = src.seek(SeekFrom::End(0))?;
+        if actual != self.source_len {
+            return Err(IndexError::Stale {
+                indexed: self.source_len,
+                actual,
+            });
+        }
+        let identity = Identity::of_source(&mut src, self.options)?;
+        if identity != self.identity {
+            return Err(IndexError::WrongFile {
+                indexed: Identity::describe(&self.identity),
+                actual: Identity::describe(&identity),
+            });
+        }
+
+        let mut reader = Reader::with_options(src, self.options);
+        reader.resume_at(offset)?;
+        match ScheduleAssembler::new(reader).read_next()? {
+            Some(Assembled::Schedule(schedule)) if schedule.offset() == offset => Ok(schedule),
+            _ => Err(IndexError::NotASchedule { offset }),
+        }
+    }
+
+    /// Reads the schedule with `key` from `src`, if there is one.
+    pub fn find_schedule<R: Read + Seek>(
+        &self,
+        src: R,
+        key: &ScheduleKey,
+    ) -> IndexResult<Option<Schedule>> {
+        self.schedule_offset(key)
+            .map(|offset| self.read_schedule(src, offset))
+            .transpose()
+    }
+
+    pub fn write_to<W: Write>(&self, mut w: W) -> IndexResult<()> {
+        let mut buf = BytesMut::new();
+        buf.put_slice(MAGIC);
+        buf.put_u64_le(self.source_len);
+        let mut flags = 0;
+        if self.options.lenient {
+            flags |= LENIENT;
+        }
+        if self.options.recover {
+            flags |= RECOVER;
+        }
+        buf.put_u8(flags);
+        match &self.identity {
+            Some(identity) => {
+                buf.put_u8(1);
+                put_str(&mut buf, &identity.current_file);
+                buf.put_i64_le(identity.extracted_at.and_utc().timestamp());
+            }
+            None => buf.put_u8(0),
+        }
+
+        buf.put_u32_le(self.schedules.len() as u32);
+        for entry in &self.schedules {
+            put_str(&mut buf, &entry.key.uid);
+            buf.put_i32_le(entry.key.start_date.num_days_from_ce());
+            buf.put_u8(entry.key.stp.code());
+            buf.put_u64_le(entry.offset as u64);
+        }
+
+        buf.put_u32_le(self.tiplocs.len() as u32);
+        for (tiploc, offsets) in &self.tiplocs {
+            put_str(&mut buf, tiploc);
+            buf.put_u32_le(offsets.len() as u32);
+            for offset in offsets {
+                buf.put_u64_le(*offset as u64);
+            }
+        }
+
+        w.write_all(&buf)?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(mut r: R) -> IndexResult<Self> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+        let mut buf = &data[..];
+
+        if take(&mut buf, MAGIC.len())? != MAGIC {
+            return Err(IndexError::Corrupt("not an index file"));
+        }
+        need(buf, 9)?;
+        let source_len = buf.get_u64_le();
+        let flags = buf.get_u8();
+        let options = ReaderOptions::new()
+            .lenient(flags & LENIENT != 0)
+            .recover(flags & RECOVER != 0);
+        let identity = match *take(&mut buf, 1)? {
+            [0] => None,
+            [1] => {
+                let current_file = get_str(&mut buf)?;
+                need(buf, 8)?;
+                let extracted_at = DateTime::from_timestamp(buf.get_i64_le(), 0)
+                    .ok_or(IndexError::Corrupt("invalid extract time"))?
+                    .naive_utc();
+                Some(Identity {
+                    current_file,
+                    extracted_at,
+                })
+            }
+            _ => return Err(IndexError::Corrupt("invalid header flag")),
+        };
+
+        need(buf, 4)?;
+        let count = buf.get_u32_le() as usize;
+        let mut schedules = Vec::with_capacity(count.min(buf.len()));
+        for _ in 0..count {
+            let uid = get_str(&mut buf)?;
+            need(buf, 13)?;
+            let start_date = NaiveDate::from_num_days_from_ce_opt(buf.get_i32_le())
+                .ok_or(IndexError::Corrupt("invalid start date"))?;
+            let stp = Stp::from_code(buf.get_u8()).ok_or(IndexError::Corrupt("invalid STP"))?;
+            let offset = buf.get_u64_le() as usize;
+            schedules.push(IndexEntry {
+                key: ScheduleKey {
+                    uid,
+                    start_date,
+                    stp,
+                },
+                offset,
+            });
+        }
+
+        need(buf, 4)?;
+        let count = buf.get_u32_le();
+        let mut tiplocs = BTreeMap::new();
+        for _ in 0..count {
+            let tiploc = get_str(&mut buf)?;
+            need(buf, 4)?;
+            let len = buf.get_u32_le() as usize;
+            need(buf, len.saturating_mul(8))?;
+            let offsets = (0..len).map(|_| buf.get_u64_le() as usize).collect();
+            tiplocs.insert(tiploc, offsets);
+        }
+
+        if buf.has_remaining() {
+            return Err(IndexError::Corrupt("trailing data"));
+        }
+
+        Ok(Index {
+            source_len,
+            identity,
+            options,
+            schedules,
+            tiplocs,
+        })
+    }
+}
+
+fn calling_points(schedule: &Schedule) -> Result<Vec<String>, CIFParseError> {
+    let mut tiplocs = Vec::new();
+    if let Some(origin) = schedule.origin() {
+        tiplocs.push(origin.tiploc()?.to_string());
+    }
+    for intermediate in schedule.intermediates() {
+        tiplocs.push(intermediate.location().tiploc()?.to_string());
+    }
+    if let Some(terminating) = schedule.terminating() {
+        tiplocs.push(terminating.tiploc()?.to_string());
+    }
+    Ok(tiplocs)
+}
+
+fn need(buf: &[u8], len: usize) -> IndexResult<()> {
+    if buf.len() < len {
+        return Err(IndexError::Corrupt("unexpected end of file"));
+    }
+    Ok(())
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> IndexResult<&'a [u8]> {
+    need(buf, len)?;
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+fn put_str(buf: &mut BytesMut, s: &str) {
+    buf.put_u8(s.len() as u8);
+    buf.put_slice(s.as_bytes());
+}
+
+fn get_str(buf: &mut &[u8]) -> IndexResult<String> {
+    let len = *take(buf, 1)?.first().expect("one byte");
+    let s = take(buf, len.into())?;
+    String::from_utf8(s.to_vec()).map_err(|_| IndexError::Corrupt("invalid string"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use fallible_iterator::FallibleIterator;
+
+    use super::*;
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample-larger.cif");
+
+    fn build() -> Index {
+        Index::build(Cursor::new(SAMPLE_FILE), ReaderOptions::default()).expect("build index")
+    }
+
+    fn all_schedules() -> Vec<Schedule> {
+        ScheduleAssembler::new(Reader::new(SAMPLE_FILE))
+            .filter_map(|item| match item {
+                Assembled::Schedule(s) => Ok(Some(s)),
+                Assembled::Other(_) => Ok(None),
+            })
+            .collect()
+            .expect("assemble")
+    }
+
+    #[test]
+    fn should_find_every_schedule() {
+        let index = build();
+        let mut src = Cursor::new(SAMPLE_FILE);
+        let schedules = all_schedules();
+        assert_eq!(index.schedules().len(), schedules.len());
+
+        for expected in schedules {
+            let key = ScheduleKey::of_schedule(&expected).unwrap();
+            let actual = index
+                .find_schedule(&mut src, &key)
+                .expect("read")
+                .expect("found");
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn should_find_schedules_by_uid_and_tiploc() {
+        let index = build();
+        let schedules = all_schedules();
+        let first = &schedules[0];
+        let uid = first.schedule().uid().unwrap();
+
+        let by_uid = index.schedules_for_uid(uid);
+        assert!(!by_uid.is_empty());
+        assert!(by_uid.iter().all(|e| e.key.uid == uid));
+        assert_eq!(
+            by_uid.len(),
+            schedules
+                .iter()
+                .filter(|s| s.schedule().uid().unwrap() == uid)
+                .count()
+        );
+        assert!(index.schedules_for_uid("ZZZZZZ").is_empty());
+
+        let origin = first.origin().unwrap().tiploc().unwrap().to_string();
+        let calling = index.calling_at(&origin);
+        assert!(calling.contains(&first.offset()));
+        assert!(calling.windows(2).all(|w| w[0] < w[1]));
+        assert!(index.calling_at("NOWHERE").is_empty());
+    }
+
+    #[test]
+    fn should_index_file_with_partial_last_line() {
+        let mut data = SAMPLE_FILE.to_vec();
+        data.extend_from_slice(b"ZZ   ");
+        let index = Index::build(Cursor::new(&data), ReaderOptions::default()).expect("build");
+        let entry = &index.schedules()[0];
+        let schedule = index
+            .read_schedule(Cursor::new(&data), entry.offset)
+            .expect("read");
+        assert_eq!(schedule.offset(), entry.offset);
+    }
+
+    #[test]
+    fn should_round_trip_index_file() {
+        let index = build();
+        let mut buf = Vec::new();
+        index.write_to(&mut buf).expect("write");
+        let read = Index::read_from(&buf[..]).expect("read");
+        assert_eq!(read, index);
+
+        let e = Index::read_from(&buf[..buf.len() - 3]).unwrap_err();
+        assert!(
+            matches!(e, IndexError::Corrupt(_)),
+            "{:?} should be corrupt",
+            e
+        );
+    }
+
+    #[test]
+    fn should_refuse_stale_source() {
+        let index = build();
+        let entry = &index.schedules()[0];
+        let shorter = &SAMPLE_FILE[..SAMPLE_FILE.len() - 81];
+        let e = index
+            .read_schedule(Cursor::new(shorter), entry.offset)
+            .unwrap_err();
+        assert!(
+            matches!(e, IndexError::Stale { .. }),
+            "{:?} should be stale",
+            e
+        );
+
+        let mut other = SAMPLE_FILE.to_vec();
+        // A later extract, which happens to be the same length.
+        other[38] = b'J';
+        let e = index
+            .read_schedule(Cursor::new(other), entry.offset)
+            .unwrap_err();
+        assert!(
+            matches!(e, IndexError::WrongFile { .. }),
+            "{:?} should be the wrong file",
+            e
+        );
+
+        let e = index
+            .read_schedule(Cursor::new(SAMPLE_FILE), 0)
+            .unwrap_err();
+        assert!(
+            matches!(e, IndexError::NotASchedule { offset: 0 }),
+            "{:?} should not be a schedule",
+            e
+        );
+    }
+}
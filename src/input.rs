@@ -0,0 +1,338 @@
+//! Opening CIF files from disk, decompressing them on the fly if need be.
+//!
+//! Extracts are usually distributed gzipped, and the ATOC timetable feed
+//! comes as a zip archive holding the CIF alongside various other files.
+//! Support for each is behind the `gzip` and `zip` features respectively.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use tracing::debug;
+
+use crate::{Reader, ReaderError, ReaderOptions, ReaderResult};
+
+/// The source of records for a [`Reader`] opened with [`Reader::open`].
+pub type Input = Box<dyn Read + Send>;
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Extensions of archive members we'll treat as the CIF itself, in order of
+/// preference.
+#[cfg(feature = "zip")]
+const CIF_EXTENSIONS: &[&str] = &["mca", "cif"];
+
+impl Reader<Input> {
+    /// Opens the file at `path`, which may be plain, gzipped or a zip
+    /// archive containing a CIF member.
+    pub fn open<P: AsRef<Path>>(path: P) -> ReaderResult<Self> {
+        Self::open_with_options(path, ReaderOptions::default())
+    }
+
+    pub fn open_with_options<P: AsRef<Path>>(
+        path: P,
+        options: ReaderOptions,
+    ) -> ReaderResult<Self> {
+        let input = open_input(path.as_ref())?;
+        Ok(Reader::with_options(input, options))
+    }
+}
+
+fn open_input(path: &Path) -> ReaderResult<Input> {
+    let mut file = BufReader::new(File::open(path)?);
+    let magic = file.fill_buf()?;
+
+    if magic.starts_with(GZIP_MAGIC) {
+        debug!(?path, "Reading gzip input");
+        return open_gzip(file);
+    }
+    if magic.starts_with(ZIP_MAGIC) {
+        debug!(?path, "Reading zip input");
+        return open_zip(file.into_inner());
+    }
+
+    Ok(Box::new(file))
+}
+
+#[cfg(feature = "gzip")]
+fn open_gzip(file: BufReader<File>) -> ReaderResult<Input> {
+    // Extracts are sometimes built by concatenating gzip streams.
+    Ok(Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(
+        file,
+    ))))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn open_gzip(_: BufReader<File>) -> ReaderResult<Input> {
+    Err(ReaderError::UnsupportedFormat { format: "gzip" })
+}
+
+// The archive only borrows its reader for as long as we read a member, so
+// rather than hold on to it, we find out where the member's data lives and
+// decompress it straight from the file.
+#[cfg(feature = "zip")]
+fn open_zip(file: File) -> ReaderResult<Input> {
+    use std::io::{Seek, SeekFrom};
+
+    use zip::result::ZipError;
+    use zip::{CompressionMethod, ZipArchive};
+
+    let mut archive = ZipArchive::new(file)?;
+    let index = cif_member(&archive).ok_or(ReaderError::NoCifMember)?;
+    let (start, len, method, crc32) = {
+        let member = archive.by_index_raw(index)?;
+        debug!(
+            name = member.name(),
+            size = member.size(),
+            "Reading zip member"
+        );
+        if member.encrypted() {
+            return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED).into());
+        }
+        (
+            member.data_start(),
+            member.compressed_size(),
+            member.compression(),
+            member.crc32(),
+        )
+    };
+
+    let mut file = archive.into_inner();
+    file.seek(SeekFrom::Start(start))?;
+    let data = BufReader::new(file).take(len);
+    match method {
+        CompressionMethod::Stored => Ok(Box::new(CheckCrc::new(data, crc32))),
+        m if m == CompressionMethod::DEFLATE => Ok(Box::new(CheckCrc::new(
+            BufReader::new(flate2::read::DeflateDecoder::new(data)),
+            crc32,
+        ))),
+        _ => Err(ZipError::UnsupportedArchive("Compression method not supported").into()),
+    }
+}
+
+// Since we're reading around the archive, we have to check the member's
+// CRC ourselves, which we do once we reach the end of it.
+#[cfg(feature = "zip")]
+struct CheckCrc<R> {
+    inner: flate2::CrcReader<R>,
+    expected: u32,
+}
+
+#[cfg(feature = "zip")]
+impl<R: Read> CheckCrc<R> {
+    fn new(inner: R, expected: u32) -> Self {
+        CheckCrc {
+            inner: flate2::CrcReader::new(inner),
+            expected,
+        }
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<R: Read> Read for CheckCrc<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if len == 0 && !buf.is_empty() && self.inner.crc().sum() != self.expected {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Zip member failed its CRC check",
+            ));
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "zip")]
+fn cif_member<R: Read + std::io::Seek>(archive: &zip::ZipArchive<R>) -> Option<usize> {
+    let names = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .collect::<Vec<_>>();
+    let extension = |name: &str| {
+        Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+    };
+
+    let name = CIF_EXTENSIONS
+        .iter()
+        .find_map(|wanted| {
+            names
+                .iter()
+                .find(|name| extension(name).as_deref() == Some(*wanted))
+        })
+        // An archive with only the one file in it can't be anything else.
+        .or(match names.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        })?;
+    archive.index_for_name(name)
+}
+
+#[cfg(not(feature = "zip"))]
+fn open_zip(_: File) -> ReaderResult<Input> {
+    Err(ReaderError::UnsupportedFormat { format: "zip" })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use fallible_iterator::FallibleIterator;
+
+    use super::*;
+    use crate::Record;
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample.cif");
+
+    // A file in the temporary directory that cleans up after itself.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("cif-parser-{}-{}", std::process::id(), name));
+            std::fs::write(&path, contents).expect("write temp file");
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn read_all(path: &Path) -> ReaderResult<Vec<Record>> {
+        Reader::open(path)?.collect()
+    }
+
+    fn expected() -> Vec<Record> {
+        Reader::new(SAMPLE_FILE).collect().expect("read sample")
+    }
+
+    #[test]
+    fn should_open_plain_file() {
+        let file = TempFile::new("plain.cif", SAMPLE_FILE);
+        assert_eq!(read_all(&file.0).expect("read"), expected());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn should_open_gzip_file() {
+        use std::io::Write;
+
+        use flate2::write::GzEncoder;
+
+        // Two concatenated streams, to check we read past the first.
+        let (first, second) = SAMPLE_FILE.split_at(81 * 3);
+        let mut data = Vec::new();
+        for part in [first, second] {
+            let mut enc = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            enc.write_all(part).expect("compress");
+            data.extend(enc.finish().expect("compress"));
+        }
+
+        let file = TempFile::new("sample.cif.gz", &data);
+        assert_eq!(read_all(&file.0).expect("read"), expected());
+    }
+
+    #[cfg(feature = "zip")]
+    fn zip_of(members: &[(&str, &[u8])], method: zip::CompressionMethod) -> Vec<u8> {
+        use std::io::Write;
+
+        use zip::write::SimpleFileOptions;
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(method);
+        for (name, contents) in members {
+            zip.start_file(*name, options).expect("start file");
+            zip.write_all(contents).expect("write member");
+        }
+        zip.finish().expect("finish zip").into_inner()
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn should_pick_cif_member_from_zip() {
+        for method in [
+            zip::CompressionMethod::Stored,
+            zip::CompressionMethod::Deflated,
+        ] {
+            let data = zip_of(
+                &[
+                    ("RJTTF123.ZTR", b"ZTR                   \n"),
+                    ("RJTTF123.MCA", SAMPLE_FILE),
+                    ("RJTTF123.MSN", b"MSN                   \n"),
+                ],
+                method,
+            );
+            let file = TempFile::new("sample.zip", &data);
+            assert_eq!(read_all(&file.0).expect("read"), expected());
+        }
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn should_fail_without_cif_member() {
+        let data = zip_of(
+            &[("README", b"Nothing\n"), ("NOTES", b"Here\n")],
+            zip::CompressionMethod::Stored,
+        );
+        let file = TempFile::new("empty.zip", &data);
+        let e = read_all(&file.0).unwrap_err();
+        assert!(
+            matches!(e, ReaderError::NoCifMember),
+            "{:?} should be missing a CIF member",
+            e
+        );
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn should_check_zip_member_crc() {
+        let mut data = zip_of(
+            &[("RJTTF123.MCA", SAMPLE_FILE)],
+            zip::CompressionMethod::Stored,
+        );
+        let at = data
+            .windows(5)
+            .position(|w| w == b"HDTPS")
+            .expect("stored header");
+        data[at + 4] = b'X';
+
+        let file = TempFile::new("corrupt.zip", &data);
+        let e = read_all(&file.0).unwrap_err();
+        assert!(
+            matches!(e, ReaderError::Io(ref e) if e.kind() == std::io::ErrorKind::InvalidData),
+            "{:?} should fail the CRC check",
+            e
+        );
+    }
+
+    #[cfg(not(feature = "zip"))]
+    #[test]
+    fn should_refuse_zip_without_feature() {
+        let file = TempFile::new("refused.zip", b"PK\x03\x04\x14\x00");
+        let e = read_all(&file.0).unwrap_err();
+        assert!(
+            matches!(e, ReaderError::UnsupportedFormat { format: "zip" }),
+            "{:?} should be unsupported",
+            e
+        );
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn should_refuse_gzip_without_feature() {
+        let file = TempFile::new("refused.cif.gz", b"\x1f\x8b\x08\x00");
+        let e = read_all(&file.0).unwrap_err();
+        assert!(
+            matches!(e, ReaderError::UnsupportedFormat { format: "gzip" }),
+            "{:?} should be unsupported",
+            e
+        );
+    }
+}
@@ -0,0 +1,1151 @@
+//! Conversion between CIF and Network Rail's JSON SCHEDULE feed, which
+//! carries the same data as one JSON object per line.
+//!
+//! The JSON feed has no equivalent of change en route records, location
+//! activities, or several minor TIPLOC fields, so those are dropped on the
+//! way out and left blank on the way back in. It also only knows how to
+//! create and delete, so CIF revisions and TIPLOC amendments are written as
+//! a delete followed by a create.
+//!
+//! Nor does the feed's timetable header identify the file or the period it
+//! covers, so those are written in an extra `CIF_header` object, and a JSON
+//! header without one can't be turned back into CIF. The feed's sequence
+//! number has no CIF equivalent, and is left out.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use chrono::{NaiveDate, NaiveTime};
+use fallible_iterator::FallibleIterator;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::helpers::{
+    allowance_from_slice_opt, allowance_to_string, days_from_slice, days_to_string,
+    speed_from_slice_opt, time_from_slice, time_half_from_slice, time_half_to_string,
+    time_to_string,
+};
+use crate::serialization::{opt, owned};
+use crate::{
+    Activities, Assembled, Association, AssociationBuilder, AssociationCategory,
+    BankHolidayRunning, BasicSchedule, BasicScheduleBuilder, Branding, BuildError, CIFParseError,
+    CateringCode, DateIndicator, FullOrUpdate, Header, HeaderBuilder, LocationIntermediate,
+    LocationIntermediateBuilder, LocationOrigin, LocationOriginBuilder, LocationTerminating,
+    LocationTerminatingBuilder, PowerType, Record, ReservationPolicy, Schedule,
+    ScheduleExtraBuilder, SeatingClass, SleeperClass, Stp, Tiploc, TiplocDeleteBuilder,
+    TiplocInsert, TiplocInsertBuilder, TrailerBuilder, TrainCategory, TrainStatus, TransactionType,
+};
+
+#[derive(Error, Debug)]
+pub enum JsonError {
+    #[error("I/O: {0}")]
+    Io(#[from] io::Error),
+    #[error("JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Parsing record: {0}")]
+    Parse(#[from] CIFParseError),
+    #[error("Building record: {0}")]
+    Build(#[from] BuildError),
+    #[error("Unknown {field}: {value:?}")]
+    UnknownCode { field: &'static str, value: String },
+    #[error("Missing {0}")]
+    Missing(&'static str),
+}
+
+pub type JsonResult<T> = std::result::Result<T, JsonError>;
+
+/// A single line of the JSON feed.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JsonRecord {
+    JsonTimetableV1(JsonTimetableV1),
+    TiplocV1(TiplocV1),
+    JsonAssociationV1(JsonAssociationV1),
+    JsonScheduleV1(JsonScheduleV1),
+    #[serde(rename = "EOF")]
+    Eof(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonTimetableV1 {
+    pub classification: String,
+    pub timestamp: i64,
+    pub owner: String,
+    #[serde(rename = "Sender")]
+    pub sender: JsonSender,
+    #[serde(rename = "Metadata")]
+    pub metadata: JsonMetadata,
+    #[serde(
+        rename = "CIF_header",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cif_header: Option<JsonCifHeader>,
+}
+
+/// The parts of a CIF header that the feed has nowhere for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonCifHeader {
+    pub file_mainframe_identity: String,
+    pub current_file: String,
+    pub last_file: Option<String>,
+    pub version: String,
+    pub user_start_date: NaiveDate,
+    pub user_end_date: NaiveDate,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonSender {
+    pub organisation: String,
+    pub application: String,
+    pub component: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonMetadata {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TiplocV1 {
+    pub transaction_type: String,
+    pub tiploc_code: String,
+    pub nalco: Option<String>,
+    pub stanox: Option<String>,
+    pub crs_code: Option<String>,
+    pub description: Option<String>,
+    pub tps_description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonAssociationV1 {
+    pub transaction_type: String,
+    pub main_train_uid: String,
+    pub assoc_train_uid: String,
+    #[serde(with = "midnight_utc")]
+    pub assoc_start_date: NaiveDate,
+    #[serde(default, with = "midnight_utc_opt")]
+    pub assoc_end_date: Option<NaiveDate>,
+    pub assoc_days: Option<String>,
+    pub category: Option<String>,
+    pub date_indicator: Option<String>,
+    pub location: String,
+    pub base_location_suffix: Option<String>,
+    pub assoc_location_suffix: Option<String>,
+    pub diagram_type: Option<String>,
+    #[serde(rename = "CIF_stp_indicator")]
+    pub stp_indicator: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonScheduleV1 {
+    #[serde(rename = "CIF_bank_holiday_running")]
+    pub bank_holiday_running: Option<String>,
+    #[serde(rename = "CIF_stp_indicator")]
+    pub stp_indicator: String,
+    #[serde(rename = "CIF_train_uid")]
+    pub train_uid: String,
+    pub applicable_timetable: Option<String>,
+    pub atoc_code: Option<String>,
+    pub new_schedule_segment: Option<JsonNewScheduleSegment>,
+    pub schedule_days_runs: Option<String>,
+    pub schedule_end_date: Option<NaiveDate>,
+    pub schedule_segment: Option<JsonScheduleSegment>,
+    pub schedule_start_date: NaiveDate,
+    pub train_status: Option<String>,
+    pub transaction_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonNewScheduleSegment {
+    pub traction_class: Option<String>,
+    pub uic_code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonScheduleSegment {
+    pub signalling_id: Option<String>,
+    #[serde(rename = "CIF_train_category")]
+    pub train_category: Option<String>,
+    #[serde(rename = "CIF_headcode")]
+    pub headcode: Option<String>,
+    #[serde(rename = "CIF_course_indicator")]
+    pub course_indicator: Option<u8>,
+    #[serde(rename = "CIF_train_service_code")]
+    pub train_service_code: Option<String>,
+    #[serde(rename = "CIF_business_sector")]
+    pub business_sector: Option<String>,
+    #[serde(rename = "CIF_power_type")]
+    pub power_type: Option<String>,
+    #[serde(rename = "CIF_timing_load")]
+    pub timing_load: Option<String>,
+    #[serde(rename = "CIF_speed")]
+    pub speed: Option<String>,
+    #[serde(rename = "CIF_operating_characteristics")]
+    pub operating_characteristics: Option<String>,
+    #[serde(rename = "CIF_train_class")]
+    pub train_class: Option<String>,
+    #[serde(rename = "CIF_sleepers")]
+    pub sleepers: Option<String>,
+    #[serde(rename = "CIF_reservations")]
+    pub reservations: Option<String>,
+    #[serde(rename = "CIF_connection_indicator")]
+    pub connection_indicator: Option<String>,
+    #[serde(rename = "CIF_catering_code")]
+    pub catering_code: Option<String>,
+    #[serde(rename = "CIF_service_branding")]
+    pub service_branding: Option<String>,
+    #[serde(default)]
+    pub schedule_location: Vec<JsonLocation>,
+}
+
+/// An entry in `schedule_location`; which times are present depends on the
+/// `location_type`, one of `LO`, `LI` or `LT`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonLocation {
+    pub location_type: String,
+    pub record_identity: String,
+    pub tiploc_code: String,
+    pub tiploc_instance: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrival: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub departure: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pass: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_arrival: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_departure: Option<String>,
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub line: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub engineering_allowance: Option<String>,
+    #[serde(default)]
+    pub pathing_allowance: Option<String>,
+    #[serde(default)]
+    pub performance_allowance: Option<String>,
+}
+
+// Association dates are written as midnight UTC timestamps, unlike the
+// plain dates used for schedules.
+mod midnight_utc {
+    use chrono::NaiveDate;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%dT00:00:00Z";
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format(FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let date = s.get(..10).unwrap_or(&s);
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(de::Error::custom)
+    }
+}
+
+mod midnight_utc_opt {
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        date: &Option<NaiveDate>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match date {
+            Some(date) => super::midnight_utc::serialize(date, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDate>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::midnight_utc")] NaiveDate);
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(date)| date))
+    }
+}
+
+const CREATE: &str = "Create";
+const DELETE: &str = "Delete";
+
+fn stp_code(stp: Stp) -> String {
+    char::from(stp.code()).to_string()
+}
+
+fn stp_of_code(code: &str) -> JsonResult<Stp> {
+    match code.as_bytes() {
+        [code] => Stp::from_code(*code),
+        _ => None,
+    }
+    .ok_or_else(|| unknown("CIF_stp_indicator", code))
+}
+
+fn unknown(field: &'static str, value: &str) -> JsonError {
+    JsonError::UnknownCode {
+        field,
+        value: value.to_owned(),
+    }
+}
+
+// The JSON feed only creates and deletes, so a revision replaces the
+// original outright.
+fn transactions(transaction_type: TransactionType) -> &'static [&'static str] {
+    match transaction_type {
+        TransactionType::New => &[CREATE],
+        TransactionType::Delete => &[DELETE],
+        TransactionType::Revise => &[DELETE, CREATE],
+    }
+}
+
+fn transaction_of_code(code: &str) -> JsonResult<TransactionType> {
+    match code {
+        CREATE => Ok(TransactionType::New),
+        DELETE => Ok(TransactionType::Delete),
+        _ => Err(unknown("transaction_type", code)),
+    }
+}
+
+fn time_half(field: &'static str, s: &str) -> JsonResult<NaiveTime> {
+    match s.len() {
+        4 | 5 => Ok(time_half_from_slice(format!("{:<5}", s).as_bytes())?),
+        _ => Err(unknown(field, s)),
+    }
+}
+
+fn time(field: &'static str, s: &str) -> JsonResult<NaiveTime> {
+    match s.len() {
+        4 => Ok(time_from_slice(s.as_bytes())?),
+        _ => Err(unknown(field, s)),
+    }
+}
+
+fn trimmed(s: String) -> String {
+    s.trim().to_owned()
+}
+
+impl JsonRecord {
+    /// Converts an item from a [`crate::ScheduleAssembler`] into the lines
+    /// of the JSON feed that describe it.
+    pub fn from_assembled(item: &Assembled) -> JsonResult<Vec<JsonRecord>> {
+        let records = match item {
+            Assembled::Schedule(schedule) => {
+                let bs = schedule.schedule();
+                transactions(bs.transaction_type()?)
+                    .iter()
+                    .map(|tx| schedule_of(schedule, tx).map(JsonRecord::JsonScheduleV1))
+                    .collect::<JsonResult<_>>()?
+            }
+            Assembled::Other(Record::Header(header)) => {
+                vec![JsonRecord::JsonTimetableV1(timetable_of(header)?)]
+            }
+            Assembled::Other(Record::TiplocInsert(insert)) => {
+                vec![JsonRecord::TiplocV1(tiploc_of(insert)?)]
+            }
+            Assembled::Other(Record::TiplocAmend(amend)) => {
+                let tiploc = amend.tiploc()?;
+                let new_tiploc = amend.new_tiploc()?.unwrap_or_else(|| tiploc.clone());
+                vec![
+                    JsonRecord::TiplocV1(tiploc_deletion(&tiploc)),
+                    JsonRecord::TiplocV1(TiplocV1 {
+                        transaction_type: CREATE.to_owned(),
+                        tiploc_code: new_tiploc.as_ref().to_owned(),
+                        nalco: Some(amend.nlc()?.to_owned()),
+                        stanox: Some(amend.stanox()?.to_owned()),
+                        crs_code: owned(amend.crs()?),
+                        description: owned(amend.nlc_desc()?),
+                        tps_description: Some(amend.tps_description()?.to_owned()),
+                    }),
+                ]
+            }
+            Assembled::Other(Record::TiplocDelete(delete)) => {
+                vec![JsonRecord::TiplocV1(tiploc_deletion(&delete.tiploc()?))]
+            }
+            Assembled::Other(Record::Association(assoc)) => transactions(assoc.transaction_type()?)
+                .iter()
+                .map(|tx| association_of(assoc, tx).map(JsonRecord::JsonAssociationV1))
+                .collect::<JsonResult<_>>()?,
+            Assembled::Other(Record::Trailer(_)) => vec![JsonRecord::Eof(true)],
+            Assembled::Other(_) => vec![],
+        };
+        Ok(records)
+    }
+
+    /// Converts a line of the JSON feed back into CIF records.
+    pub fn to_records(&self) -> JsonResult<Vec<Record>> {
+        let records = match self {
+            JsonRecord::JsonTimetableV1(timetable) => vec![Record::Header(header_of(timetable)?)],
+            JsonRecord::TiplocV1(tiploc) => vec![tiploc_record(tiploc)?],
+            JsonRecord::JsonAssociationV1(assoc) => {
+                vec![Record::Association(association_record(assoc)?)]
+            }
+            JsonRecord::JsonScheduleV1(schedule) => schedule_records(schedule)?,
+            JsonRecord::Eof(_) => vec![Record::Trailer(TrailerBuilder::new().build()?)],
+        };
+        Ok(records)
+    }
+}
+
+fn timetable_of(header: &Header) -> JsonResult<JsonTimetableV1> {
+    let kind = match header.update()? {
+        FullOrUpdate::Full => "full",
+        FullOrUpdate::Update => "update",
+    };
+    Ok(JsonTimetableV1 {
+        classification: "public".to_owned(),
+        timestamp: header.extracted_at()?.and_utc().timestamp(),
+        owner: "Network Rail".to_owned(),
+        sender: JsonSender {
+            organisation: "Network Rail".to_owned(),
+            application: "CIF".to_owned(),
+            component: "SCHEDULE".to_owned(),
+        },
+        // CIF files are identified by name rather than sequence number.
+        metadata: JsonMetadata {
+            kind: kind.to_owned(),
+            sequence: None,
+        },
+        cif_header: Some(JsonCifHeader {
+            file_mainframe_identity: header.file_mainframe_identity()?.to_owned(),
+            current_file: header.current_file()?.to_owned(),
+            last_file: owned(header.last_file()?),
+            version: header.version()?.to_owned(),
+            user_start_date: header.user_start_date()?,
+            user_end_date: header.user_end_date()?,
+        }),
+    })
+}
+
+fn header_of(timetable: &JsonTimetableV1) -> JsonResult<Header> {
+    let cif = timetable
+        .cif_header
+        .as_ref()
+        .ok_or(JsonError::Missing("CIF_header"))?;
+    let update = match timetable.metadata.kind.as_str() {
+        "full" => FullOrUpdate::Full,
+        "update" => FullOrUpdate::Update,
+        other => return Err(unknown("Metadata.type", other)),
+    };
+    let extracted_at = chrono::DateTime::from_timestamp(timetable.timestamp, 0)
+        .ok_or_else(|| unknown("timestamp", &timetable.timestamp.to_string()))?
+        .naive_utc();
+    let b = HeaderBuilder::new(
+        &cif.file_mainframe_identity,
+        extracted_at,
+        &cif.current_file,
+        update,
+        cif.user_start_date,
+        cif.user_end_date,
+    )
+    .version(&cif.version);
+    Ok(opt(b, cif.last_file.as_deref(), HeaderBuilder::last_file).build()?)
+}
+
+fn tiploc_of(insert: &TiplocInsert) -> JsonResult<TiplocV1> {
+    Ok(TiplocV1 {
+        transaction_type: CREATE.to_owned(),
+        tiploc_code: insert.tiploc()?.as_ref().to_owned(),
+        nalco: Some(insert.nlc()?.to_owned()),
+        stanox: Some(insert.stanox()?.to_owned()),
+        crs_code: owned(insert.crs()?),
+        description: owned(insert.nlc_desc()?),
+        tps_description: Some(insert.tps_description()?.to_owned()),
+    })
+}
+
+fn tiploc_deletion(tiploc: &Tiploc<'_>) -> TiplocV1 {
+    TiplocV1 {
+        transaction_type: DELETE.to_owned(),
+        tiploc_code: tiploc.as_ref().to_owned(),
+        nalco: None,
+        stanox: None,
+        crs_code: None,
+        description: None,
+        tps_description: None,
+    }
+}
+
+fn tiploc_record(json: &TiplocV1) -> JsonResult<Record> {
+    let tiploc = Tiploc::from(json.tiploc_code.as_str());
+    let record = match transaction_of_code(&json.transaction_type)? {
+        TransactionType::Delete => Record::TiplocDelete(TiplocDeleteBuilder::new(&tiploc).build()?),
+        _ => {
+            let b = TiplocInsertBuilder::new(
+                &tiploc,
+                json.nalco.as_deref().unwrap_or(""),
+                "",
+                json.tps_description.as_deref().unwrap_or(""),
+                json.stanox.as_deref().unwrap_or(""),
+            );
+            let b = opt(b, json.crs_code.as_deref(), TiplocInsertBuilder::crs);
+            let b = opt(
+                b,
+                json.description.as_deref(),
+                TiplocInsertBuilder::nlc_desc,
+            );
+            Record::TiplocInsert(b.build()?)
+        }
+    };
+    Ok(record)
+}
+
+fn association_of(assoc: &Association, tx: &str) -> JsonResult<JsonAssociationV1> {
+    let create = tx == CREATE;
+    let when = |val| if create { val } else { None };
+    Ok(JsonAssociationV1 {
+        transaction_type: tx.to_owned(),
+        main_train_uid: assoc.main_uid()?.to_owned(),
+        assoc_train_uid: assoc.associated_uid()?.to_owned(),
+        assoc_start_date: assoc.start_date()?,
+        assoc_end_date: if create { assoc.end_date()? } else { None },
+        assoc_days: when(Some(days_to_string(assoc.days()?))),
+        category: when(assoc.category()?.map(|c| c.code().to_owned())),
+        date_indicator: when(
+            assoc
+                .date_indicator()?
+                .map(|d| char::from(d.code()).to_string()),
+        ),
+        location: assoc.location()?.as_ref().to_owned(),
+        base_location_suffix: owned(assoc.base_location_suffix()?),
+        assoc_location_suffix: when(owned(assoc.assoc_location_suffix()?)),
+        diagram_type: owned(assoc.diagram_type()?),
+        stp_indicator: stp_code(assoc.stp()?),
+    })
+}
+
+fn association_record(json: &JsonAssociationV1) -> JsonResult<Association> {
+    let b = AssociationBuilder::new(
+        transaction_of_code(&json.transaction_type)?,
+        &json.main_train_uid,
+        &json.assoc_train_uid,
+        json.assoc_start_date,
+        &Tiploc::from(json.location.as_str()),
+        stp_of_code(&json.stp_indicator)?,
+    )
+    .diagram_type(json.diagram_type.as_deref().unwrap_or(""));
+    let b = opt(b, json.assoc_end_date, AssociationBuilder::end_date);
+    let b = match &json.assoc_days {
+        Some(days) => b.days(days_from_slice(days.as_bytes())?),
+        None => b,
+    };
+    let b = match json.category.as_deref() {
+        None => b,
+        Some("JJ") => b.category(AssociationCategory::Join),
+        Some("VV") => b.category(AssociationCategory::Divide),
+        Some("NP") => b.category(AssociationCategory::Next),
+        Some(other) => return Err(unknown("category", other)),
+    };
+    let b = match json.date_indicator.as_deref() {
+        None => b,
+        Some("S") => b.date_indicator(DateIndicator::SameDay),
+        Some("N") => b.date_indicator(DateIndicator::NextDay),
+        Some("P") => b.date_indicator(DateIndicator::PreviousDay),
+        Some(other) => return Err(unknown("date_indicator", other)),
+    };
+    let b = opt(
+        b,
+        json.base_location_suffix.as_deref(),
+        AssociationBuilder::base_location_suffix,
+    );
+    let b = opt(
+        b,
+        json.assoc_location_suffix.as_deref(),
+        AssociationBuilder::assoc_location_suffix,
+    );
+    Ok(b.build()?)
+}
+
+fn schedule_of(schedule: &Schedule, tx: &str) -> JsonResult<JsonScheduleV1> {
+    let bs = schedule.schedule();
+    let mut json = JsonScheduleV1 {
+        bank_holiday_running: None,
+        stp_indicator: stp_code(bs.stp()?),
+        train_uid: bs.uid()?.to_owned(),
+        applicable_timetable: None,
+        atoc_code: None,
+        new_schedule_segment: None,
+        schedule_days_runs: None,
+        schedule_end_date: None,
+        schedule_segment: None,
+        schedule_start_date: bs.start_date()?,
+        train_status: None,
+        transaction_type: tx.to_owned(),
+    };
+    if tx == DELETE {
+        return Ok(json);
+    }
+
+    json.bank_holiday_running = bs.bank_holiday()?.map(|c| c.code().to_owned());
+    json.schedule_days_runs = Some(days_to_string(bs.days()?));
+    json.schedule_end_date = bs.end_date()?;
+    json.train_status = bs.status()?.map(|c| c.code().to_owned());
+    if let Some(extra) = schedule.extra() {
+        json.atoc_code = Some(extra.atoc_code()?.to_owned());
+        json.applicable_timetable = Some(extra.applicable_timetable_code()?.to_owned());
+        json.new_schedule_segment = Some(JsonNewScheduleSegment {
+            traction_class: owned(extra.traction_class()?),
+            uic_code: owned(extra.uic_code()?),
+        });
+    }
+
+    let mut locations = Vec::new();
+    if let Some(origin) = schedule.origin() {
+        locations.push(origin_of(origin)?);
+    }
+    for intermediate in schedule.intermediates() {
+        locations.push(intermediate_of(intermediate.location())?);
+    }
+    if let Some(terminating) = schedule.terminating() {
+        locations.push(terminating_of(terminating)?);
+    }
+
+    let operating_chars = crate::codes::operating_chars_to_string(bs.operating_chars()?);
+    let catering = crate::codes::catering_to_string(bs.catering()?);
+    json.schedule_segment = Some(JsonScheduleSegment {
+        signalling_id: owned(bs.identity()?),
+        train_category: bs.category()?.map(|c| c.code().to_owned()),
+        headcode: owned(bs.headcode()?),
+        course_indicator: bs.course_indicator()?.and_then(|c| c.parse().ok()),
+        train_service_code: owned(bs.service_code()?),
+        business_sector: owned(bs.portion_id()?),
+        power_type: bs.power_type()?.map(|c| c.code().to_owned()),
+        timing_load: owned(bs.timing_load()?),
+        speed: bs.speed()?.map(|s| format!("{:03}", s.mph())),
+        operating_characteristics: Some(operating_chars).filter(|s| !s.is_empty()),
+        train_class: bs.seating_class()?.map(|c| c.code().to_owned()),
+        sleepers: bs.sleepers()?.map(|c| c.code().to_owned()),
+        reservations: bs.reservations()?.map(|c| c.code().to_owned()),
+        connection_indicator: owned(bs.connection_indicator()?),
+        catering_code: Some(catering).filter(|s| !s.is_empty()),
+        service_branding: bs.branding()?.map(|c| c.code().to_owned()),
+        schedule_location: locations,
+    });
+    Ok(json)
+}
+
+fn location(location_type: &str, tiploc: Tiploc<'_>, suffix: Option<&str>) -> JsonLocation {
+    JsonLocation {
+        location_type: location_type.to_owned(),
+        record_identity: location_type.to_owned(),
+        tiploc_code: tiploc.as_ref().to_owned(),
+        tiploc_instance: owned(suffix),
+        arrival: None,
+        departure: None,
+        pass: None,
+        public_arrival: None,
+        public_departure: None,
+        platform: None,
+        line: None,
+        path: None,
+        engineering_allowance: None,
+        pathing_allowance: None,
+        performance_allowance: None,
+    }
+}
+
+fn half(time: NaiveTime) -> String {
+    trimmed(time_half_to_string(time))
+}
+
+fn origin_of(origin: &LocationOrigin) -> JsonResult<JsonLocation> {
+    let allowance = |a: Option<_>| a.map(|a| trimmed(allowance_to_string(a)));
+    Ok(JsonLocation {
+        departure: Some(half(origin.scheduled_departure_time()?)),
+        public_departure: Some(time_to_string(origin.public_departure()?)),
+        platform: owned(origin.platform()?),
+        line: owned(origin.line()?),
+        engineering_allowance: allowance(origin.eng_allowance()?),
+        pathing_allowance: allowance(origin.path_allowance()?),
+        performance_allowance: allowance(origin.perf_allowance()?),
+        ..location("LO", origin.tiploc()?, origin.tiploc_suffix()?)
+    })
+}
+
+fn intermediate_of(li: &LocationIntermediate) -> JsonResult<JsonLocation> {
+    let allowance = |a: Option<_>| a.map(|a| trimmed(allowance_to_string(a)));
+    Ok(JsonLocation {
+        arrival: li.scheduled_arrival_time()?.map(half),
+        departure: li.scheduled_departure_time()?.map(half),
+        pass: li.scheduled_pass()?.map(half),
+        public_arrival: li.public_arrival()?.map(time_to_string),
+        public_departure: li.public_departure()?.map(time_to_string),
+        platform: owned(li.platform()?),
+        line: owned(li.line()?),
+        path: owned(li.path()?),
+        engineering_allowance: allowance(li.eng_allowance()?),
+        pathing_allowance: allowance(li.path_allowance()?),
+        performance_allowance: allowance(li.perf_allowance()?),
+        ..location("LI", li.tiploc()?, li.tiploc_suffix()?)
+    })
+}
+
+fn terminating_of(lt: &LocationTerminating) -> JsonResult<JsonLocation> {
+    Ok(JsonLocation {
+        arrival: Some(half(lt.scheduled_arrival_time()?)),
+        public_arrival: Some(time_to_string(lt.public_arrival()?)),
+        platform: owned(lt.platform()?),
+        path: owned(lt.path()?),
+        ..location("LT", lt.tiploc()?, lt.tiploc_suffix()?)
+    })
+}
+
+fn schedule_records(json: &JsonScheduleV1) -> JsonResult<Vec<Record>> {
+    let mut records = vec![Record::Schedule(basic_schedule_record(json)?)];
+    if let Some(atoc_code) = &json.atoc_code {
+        let b = ScheduleExtraBuilder::new(
+            atoc_code,
+            json.applicable_timetable.as_deref().unwrap_or(""),
+        );
+        let segment = json.new_schedule_segment.as_ref();
+        let b = opt(
+            b,
+            segment.and_then(|s| s.traction_class.as_deref()),
+            ScheduleExtraBuilder::traction_class,
+        );
+        let b = opt(
+            b,
+            segment.and_then(|s| s.uic_code.as_deref()),
+            ScheduleExtraBuilder::uic_code,
+        );
+        records.push(Record::ScheduleExtra(b.build()?));
+    }
+    if let Some(segment) = &json.schedule_segment {
+        for location in &segment.schedule_location {
+            records.push(location_record(location)?);
+        }
+    }
+    Ok(records)
+}
+
+fn basic_schedule_record(json: &JsonScheduleV1) -> JsonResult<BasicSchedule> {
+    let b = BasicScheduleBuilder::new(
+        transaction_of_code(&json.transaction_type)?,
+        &json.train_uid,
+        json.schedule_start_date,
+        stp_of_code(&json.stp_indicator)?,
+    );
+    let b = opt(b, json.schedule_end_date, BasicScheduleBuilder::end_date);
+    let b = match &json.schedule_days_runs {
+        Some(days) => b.days(days_from_slice(days.as_bytes())?),
+        None => b,
+    };
+    let b = opt(b, json.bank_holiday_running.as_deref(), |b, code| {
+        b.bank_holiday(&BankHolidayRunning::from_code(code))
+    });
+    let b = opt(b, json.train_status.as_deref(), |b, code| {
+        b.status(&TrainStatus::from_code(code))
+    });
+    let segment = match &json.schedule_segment {
+        Some(segment) => segment,
+        None => return Ok(b.build()?),
+    };
+    let b = opt(
+        b,
+        segment.signalling_id.as_deref(),
+        BasicScheduleBuilder::identity,
+    );
+    let b = opt(b, segment.train_category.as_deref(), |b, code| {
+        b.category(&TrainCategory::from_code(code))
+    });
+    let b = opt(
+        b,
+        segment.headcode.as_deref(),
+        BasicScheduleBuilder::headcode,
+    );
+    let b = opt(b, segment.course_indicator, |b, c| {
+        b.course_indicator(&c.to_string())
+    });
+    let b = opt(
+        b,
+        segment.train_service_code.as_deref(),
+        BasicScheduleBuilder::service_code,
+    );
+    let b = opt(
+        b,
+        segment.business_sector.as_deref(),
+        BasicScheduleBuilder::portion_id,
+    );
+    let b = opt(b, segment.power_type.as_deref(), |b, code| {
+        b.power_type(&PowerType::from_code(code))
+    });
+    let b = opt(
+        b,
+        segment.timing_load.as_deref(),
+        BasicScheduleBuilder::timing_load,
+    );
+    let b = match segment.speed.as_deref() {
+        Some(speed) => opt(b, speed_from_slice_opt(speed.as_bytes())?, |b, s| {
+            b.speed(s)
+        }),
+        None => b,
+    };
+    let b = match segment.operating_characteristics.as_deref() {
+        Some(chars) => {
+            b.operating_chars(crate::codes::operating_chars_from_slice(chars.as_bytes())?)
+        }
+        None => b,
+    };
+    let b = opt(b, segment.train_class.as_deref(), |b, code| {
+        b.seating_class(&SeatingClass::from_code(code))
+    });
+    let b = opt(b, segment.sleepers.as_deref(), |b, code| {
+        b.sleepers(&SleeperClass::from_code(code))
+    });
+    let b = opt(b, segment.reservations.as_deref(), |b, code| {
+        b.reservations(&ReservationPolicy::from_code(code))
+    });
+    let b = opt(
+        b,
+        segment.connection_indicator.as_deref(),
+        BasicScheduleBuilder::connection_indicator,
+    );
+    let b = match segment.catering_code.as_deref() {
+        Some(catering) => b.catering(crate::codes::catering_from_slice(catering.as_bytes())?),
+        None => b.catering(CateringCode::empty()),
+    };
+    let b = opt(b, segment.service_branding.as_deref(), |b, code| {
+        b.branding(&Branding::from_code(code))
+    });
+    Ok(b.build()?)
+}
+
+fn location_record(json: &JsonLocation) -> JsonResult<Record> {
+    let tiploc = Tiploc::from(json.tiploc_code.as_str());
+    let allowance = |a: &Option<String>| -> JsonResult<_> {
+        match a {
+            Some(a) => Ok(allowance_from_slice_opt(a.as_bytes())?),
+            None => Ok(None),
+        }
+    };
+    let record = match json.location_type.as_str() {
+        "LO" => {
+            let dep = json
+                .departure
+                .as_deref()
+                .ok_or(JsonError::Missing("departure"))?;
+            let public = json.public_departure.as_deref().unwrap_or("0000");
+            type B = LocationOriginBuilder;
+            let b = B::new(
+                &tiploc,
+                time_half("departure", dep)?,
+                time("public_departure", public)?,
+            )
+            .activity(&Activities::default());
+            let b = opt(b, json.tiploc_instance.as_deref(), B::tiploc_suffix);
+            let b = opt(b, json.platform.as_deref(), B::platform);
+            let b = opt(b, json.line.as_deref(), B::line);
+            let b = opt(b, allowance(&json.engineering_allowance)?, B::eng_allowance);
+            let b = opt(b, allowance(&json.pathing_allowance)?, B::path_allowance);
+            let b = opt(
+                b,
+                allowance(&json.performance_allowance)?,
+                B::perf_allowance,
+            );
+            Record::LocationOrigin(b.build()?)
+        }
+        "LI" => {
+            type B = LocationIntermediateBuilder;
+            let b = B::new(&tiploc);
+            let b = opt(b, json.tiploc_instance.as_deref(), B::tiploc_suffix);
+            let b = match &json.arrival {
+                Some(t) => b.scheduled_arrival_time(time_half("arrival", t)?),
+                None => b,
+            };
+            let b = match &json.departure {
+                Some(t) => b.scheduled_departure_time(time_half("departure", t)?),
+                None => b,
+            };
+            let b = match &json.pass {
+                Some(t) => b.scheduled_pass(time_half("pass", t)?),
+                None => b,
+            };
+            let b = match &json.public_arrival {
+                Some(t) => b.public_arrival(time("public_arrival", t)?),
+                None => b,
+            };
+            let b = match &json.public_departure {
+                Some(t) => b.public_departure(time("public_departure", t)?),
+                None => b,
+            };
+            let b = opt(b, json.platform.as_deref(), B::platform);
+            let b = opt(b, json.line.as_deref(), B::line);
+            let b = opt(b, json.path.as_deref(), B::path);
+            let b = opt(b, allowance(&json.engineering_allowance)?, B::eng_allowance);
+            let b = opt(b, allowance(&json.pathing_allowance)?, B::path_allowance);
+            let b = opt(
+                b,
+                allowance(&json.performance_allowance)?,
+                B::perf_allowance,
+            );
+            Record::LocationIntermediate(b.build()?)
+        }
+        "LT" => {
+            let arr = json
+                .arrival
+                .as_deref()
+                .ok_or(JsonError::Missing("arrival"))?;
+            let public = json.public_arrival.as_deref().unwrap_or("0000");
+            type B = LocationTerminatingBuilder;
+            let b = B::new(
+                &tiploc,
+                time_half("arrival", arr)?,
+                time("public_arrival", public)?,
+            );
+            let b = opt(b, json.tiploc_instance.as_deref(), B::tiploc_suffix);
+            let b = opt(b, json.platform.as_deref(), B::platform);
+            let b = opt(b, json.path.as_deref(), B::path);
+            Record::LocationTerminating(b.build()?)
+        }
+        other => return Err(unknown("location_type", other)),
+    };
+    Ok(record)
+}
+
+/// Writes the JSON feed, one object per line.
+pub struct JsonWriter<W> {
+    dst: W,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(dst: W) -> Self {
+        JsonWriter { dst }
+    }
+
+    pub fn write_assembled(&mut self, item: &Assembled) -> JsonResult<()> {
+        for record in JsonRecord::from_assembled(item)? {
+            serde_json::to_writer(&mut self.dst, &record)?;
+            self.dst.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.dst
+    }
+}
+
+/// Reads the JSON feed, yielding the equivalent CIF records.
+pub struct JsonReader<R> {
+    src: R,
+    line: String,
+    queued: VecDeque<Record>,
+}
+
+impl<R: BufRead> JsonReader<R> {
+    pub fn new(src: R) -> Self {
+        JsonReader {
+            src,
+            line: String::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    pub fn read_next(&mut self) -> JsonResult<Option<Record>> {
+        while self.queued.is_empty() {
+            self.line.clear();
+            if self.src.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            if self.line.trim().is_empty() {
+                continue;
+            }
+            let record: JsonRecord = serde_json::from_str(&self.line)?;
+            self.queued.extend(record.to_records()?);
+        }
+        Ok(self.queued.pop_front())
+    }
+}
+
+impl<R: BufRead> FallibleIterator for JsonReader<R> {
+    type Item = Record;
+
+    type Error = JsonError;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        self.read_next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Reader, ScheduleAssembler, Timetable};
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample.cif");
+
+    fn to_json(data: &[u8]) -> Vec<u8> {
+        let mut assembler = ScheduleAssembler::new(Reader::new(data));
+        let mut writer = JsonWriter::new(Vec::new());
+        while let Some(item) = assembler.next().expect("assemble") {
+            writer.write_assembled(&item).expect("write");
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn should_write_schedule() {
+        let json = to_json(SAMPLE_FILE);
+        let lines = json
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).expect("parse"))
+            .collect::<Vec<serde_json::Value>>();
+        assert_eq!(lines.len(), 4 + 2 + 3 + 2);
+        assert_eq!(lines[0]["JsonTimetableV1"]["Metadata"]["type"], "full");
+        assert_eq!(
+            lines[0]["JsonTimetableV1"]["CIF_header"]["current_file"],
+            "DFROC2E"
+        );
+        assert_eq!(
+            lines[1],
+            json!({"TiplocV1": {
+                "transaction_type": "Create",
+                "tiploc_code": "AACHEN",
+                "nalco": "081601",
+                "stanox": "00005",
+                "crs_code": null,
+                "description": null,
+                "tps_description": "AACHEN",
+            }})
+        );
+        assert_eq!(
+            lines[5]["JsonAssociationV1"]["assoc_start_date"],
+            "2020-05-17T00:00:00Z"
+        );
+        assert_eq!(lines[5]["JsonAssociationV1"]["assoc_days"], "0000001");
+
+        let schedule = &lines[7]["JsonScheduleV1"];
+        assert_eq!(schedule["CIF_train_uid"], "C00046");
+        assert_eq!(schedule["schedule_start_date"], "2020-05-17");
+        assert_eq!(schedule["atoc_code"], "NT");
+        let segment = &schedule["schedule_segment"];
+        assert_eq!(segment["signalling_id"], "5J11");
+        assert_eq!(segment["CIF_course_indicator"], 1);
+        assert_eq!(segment["CIF_speed"], "090");
+        let locations = segment["schedule_location"].as_array().unwrap();
+        assert_eq!(locations.len(), 4);
+        assert_eq!(locations[0]["departure"], "0944");
+        assert_eq!(locations[2]["pass"], "0952H");
+        assert_eq!(locations[3]["platform"], "12A");
+        assert_eq!(lines[10], json!({"EOF": true}));
+    }
+
+    #[test]
+    fn should_read_back_equivalent_timetable() {
+        let json = to_json(SAMPLE_FILE);
+        let records = JsonReader::new(&json[..])
+            .collect::<Vec<_>>()
+            .expect("read json");
+        let mut cif = crate::Writer::new(Vec::new());
+        for record in &records {
+            cif.write_record(record).expect("write");
+        }
+        let cif = cif.into_inner();
+        assert_eq!(cif[..81], SAMPLE_FILE[..81], "Header differs");
+
+        let mut expected = Timetable::new();
+        expected.apply(Reader::new(SAMPLE_FILE)).expect("apply");
+        let mut actual = Timetable::new();
+        actual.apply(Reader::new(&cif[..])).expect("apply");
+
+        assert_eq!(
+            actual
+                .tiplocs()
+                .map(|t| t.tiploc().unwrap())
+                .collect::<Vec<_>>(),
+            expected
+                .tiplocs()
+                .map(|t| t.tiploc().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            actual.associations().map(|(k, _)| k).collect::<Vec<_>>(),
+            expected.associations().map(|(k, _)| k).collect::<Vec<_>>()
+        );
+        let expected = expected.schedules().collect::<Vec<_>>();
+        let actual = actual.schedules().collect::<Vec<_>>();
+        assert_eq!(actual.len(), expected.len());
+        for ((ak, a), (ek, e)) in actual.iter().zip(&expected) {
+            assert_eq!(ak, ek);
+            assert_eq!(a.schedule(), e.schedule());
+            assert_eq!(a.intermediates().len(), e.intermediates().len());
+            let times = |s: &Schedule| {
+                s.intermediates()
+                    .iter()
+                    .map(|i| i.location().scheduled_pass().unwrap())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(times(a), times(e));
+        }
+    }
+
+    #[test]
+    fn should_split_revision_into_delete_and_create() {
+        let data: &[u8] = b"\
+BSRC000462005172012060000001 PEE5J99    111841820 DMUE   090                   P\n";
+        let json = String::from_utf8(to_json(data)).unwrap();
+        let lines = json.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""transaction_type":"Delete""#));
+        assert!(lines[1].contains(r#""transaction_type":"Create""#));
+    }
+
+    #[test]
+    fn should_refuse_header_without_cif_fields() {
+        let line = r#"{"JsonTimetableV1":{"classification":"public","timestamp":1592784000,"owner":"Network Rail","Sender":{"organisation":"Rockshore","application":"NTROD","component":"SCHEDULE"},"Metadata":{"type":"full","sequence":3211}}}"#;
+        let e = JsonReader::new(line.as_bytes()).next().unwrap_err();
+        assert!(
+            matches!(e, JsonError::Missing("CIF_header")),
+            "{:?} should be missing CIF_header",
+            e
+        );
+    }
+
+    #[test]
+    fn should_report_missing_origin_departure() {
+        let json = to_json(SAMPLE_FILE);
+        let mut schedule = json
+            .split(|b| *b == b'\n')
+            .map(|l| serde_json::from_slice::<serde_json::Value>(l).expect("parse"))
+            .find(|l| l.get("JsonScheduleV1").is_some())
+            .expect("schedule");
+        schedule["JsonScheduleV1"]["schedule_segment"]["schedule_location"][0]["departure"] =
+            serde_json::Value::Null;
+        let line = serde_json::to_vec(&schedule).unwrap();
+
+        let e = JsonReader::new(&line[..]).next().unwrap_err();
+        assert!(
+            matches!(e, JsonError::Missing("departure")),
+            "{:?} should be missing departure",
+            e
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_transaction_type() {
+        let line = r#"{"TiplocV1":{"transaction_type":"Frobnicate","tiploc_code":"LEEDS","nalco":null,"stanox":null,"crs_code":null,"description":null,"tps_description":null}}"#;
+        let e = JsonReader::new(line.as_bytes()).next().unwrap_err();
+        assert!(
+            matches!(
+                e,
+                JsonError::UnknownCode {
+                    field: "transaction_type",
+                    ..
+                }
+            ),
+            "{:?}",
+            e
+        );
+    }
+}
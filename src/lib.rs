@@ -1,42 +1,103 @@
 use bytes::Bytes;
 
+mod assembler;
 mod association;
+#[cfg(feature = "tokio")]
+mod async_reader;
 mod basic_schedule;
 mod change_en_route;
+mod codes;
 mod errors;
+#[cfg(feature = "gtfs")]
+mod gtfs;
 mod header;
 mod helpers;
+mod index;
+mod input;
+#[cfg(feature = "json")]
+mod json;
 mod location_intermediate;
 mod location_origin;
 mod location_terminating;
+#[cfg(feature = "tz")]
+mod london;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "rayon")]
+mod parallel;
 mod reader;
+mod resolver;
 mod schedule_extra;
+#[cfg(feature = "serde")]
+mod serialization;
+mod timetable;
+mod timings;
 mod tiploc;
 mod tiploc_amend;
+mod tiploc_delete;
 mod tiploc_insert;
 mod trailer;
+mod writer;
 
-pub use association::Association;
-pub use basic_schedule::BasicSchedule;
-pub use change_en_route::ChangeEnRoute;
-pub use errors::CIFParseError;
-pub use header::Header;
-pub use location_intermediate::LocationIntermediate;
-pub use location_origin::LocationOrigin;
-pub use location_terminating::LocationTerminating;
-pub use reader::{Reader, ReaderError, ReaderResult};
-pub use schedule_extra::ScheduleExtra;
+pub use assembler::{
+    Assembled, AssemblerError, AssemblerResult, Intermediate, Schedule, ScheduleAssembler,
+};
+pub use association::{
+    Association, AssociationBuilder, AssociationCategory, AssociationType, DateIndicator,
+};
+#[cfg(feature = "tokio")]
+pub use async_reader::{AsyncReader, CifCodec};
+pub use basic_schedule::{BasicSchedule, BasicScheduleBuilder};
+pub use change_en_route::{ChangeEnRoute, ChangeEnRouteBuilder};
+pub use codes::{
+    Activities, Activity, BankHolidayRunning, Branding, CateringCode, OperatingCharacteristics,
+    PowerType, ReservationPolicy, SeatingClass, SleeperClass, TrainCategory, TrainStatus,
+};
+pub use errors::{snippet, BuildError, CIFParseError, FieldError, Position};
+#[cfg(feature = "gtfs")]
+pub use gtfs::{
+    Agency, Calendar, CalendarDate, GtfsError, GtfsFeed, GtfsOptions, GtfsResult, GtfsTable, Route,
+    Stop, StopTime, Trip,
+};
+pub use header::{FullOrUpdate, Header, HeaderBuilder, SequenceError};
+pub use helpers::{Allowance, Days, Speed};
+pub use index::{Index, IndexEntry, IndexError, IndexResult};
+pub use input::Input;
+#[cfg(feature = "json")]
+pub use json::{
+    JsonAssociationV1, JsonError, JsonLocation, JsonMetadata, JsonNewScheduleSegment, JsonReader,
+    JsonRecord, JsonResult, JsonScheduleSegment, JsonScheduleV1, JsonSender, JsonTimetableV1,
+    JsonWriter, TiplocV1,
+};
+pub use location_intermediate::{LocationIntermediate, LocationIntermediateBuilder};
+pub use location_origin::{LocationOrigin, LocationOriginBuilder};
+pub use location_terminating::{LocationTerminating, LocationTerminatingBuilder};
+#[cfg(feature = "tz")]
+pub use london::{london_to_utc, LondonTime, UtcCallTimes};
+#[cfg(feature = "mmap")]
+pub use mmap::MmapReader;
+#[cfg(feature = "rayon")]
+pub use parallel::{ParallelAssembler, ParallelReader};
+pub use reader::{Reader, ReaderError, ReaderOptions, ReaderResult, Repair, RepairKind};
+pub use resolver::{resolve_on_date, Effective};
+pub use schedule_extra::{ScheduleExtra, ScheduleExtraBuilder};
+pub use timetable::{AssociationKey, ScheduleKey, Timetable, TimetableError, TimetableResult};
+pub use timings::CallTimes;
 pub use tiploc::Tiploc;
-pub use tiploc_amend::TiplocAmend;
-pub use tiploc_insert::TiplocInsert;
-pub use trailer::Trailer;
+pub use tiploc_amend::{TiplocAmend, TiplocAmendBuilder};
+pub use tiploc_delete::{TiplocDelete, TiplocDeleteBuilder};
+pub use tiploc_insert::{TiplocInsert, TiplocInsertBuilder};
+pub use trailer::{Trailer, TrailerBuilder};
+pub use writer::Writer;
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Record {
     Header(Header),
     TiplocInsert(TiplocInsert),
     TiplocAmend(TiplocAmend),
+    TiplocDelete(TiplocDelete),
     Association(Association),
     Schedule(BasicSchedule),
     ScheduleExtra(ScheduleExtra),
@@ -46,16 +107,26 @@ pub enum Record {
     ChangeEnRoute(ChangeEnRoute),
     Trailer(Trailer),
     Unrecognised(Bytes),
+    /// A line that couldn't be read as a record, from a [`Reader`] in
+    /// recovery mode. `bytes` excludes the newline.
+    Malformed {
+        offset: usize,
+        line_no: usize,
+        bytes: Bytes,
+        reason: String,
+    },
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TransactionType {
     New,
     Delete,
     Revise,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stp {
     Cancellation,
     New,
@@ -63,12 +134,44 @@ pub enum Stp {
     Permanent,
 }
 
+impl TransactionType {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            TransactionType::New => b'N',
+            TransactionType::Delete => b'D',
+            TransactionType::Revise => b'R',
+        }
+    }
+}
+
+impl Stp {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            Stp::Cancellation => b'C',
+            Stp::New => b'N',
+            Stp::Overlay => b'O',
+            Stp::Permanent => b'P',
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<Self> {
+        match code {
+            b'C' => Some(Stp::Cancellation),
+            b'N' => Some(Stp::New),
+            b'O' => Some(Stp::Overlay),
+            b'P' => Some(Stp::Permanent),
+            _ => None,
+        }
+    }
+}
+
 impl Record {
     pub fn buf(&self) -> &Bytes {
         match self {
             Record::Header(record) => record.buf(),
             Record::TiplocInsert(record) => record.buf(),
             Record::TiplocAmend(record) => record.buf(),
+            Record::TiplocDelete(record) => record.buf(),
             Record::Association(record) => record.buf(),
             Record::Schedule(record) => record.buf(),
             Record::ScheduleExtra(record) => record.buf(),
@@ -78,6 +181,7 @@ impl Record {
             Record::ChangeEnRoute(record) => record.buf(),
             Record::Trailer(record) => record.buf(),
             Record::Unrecognised(record) => record,
+            Record::Malformed { bytes, .. } => bytes,
         }
     }
 }
@@ -3,7 +3,8 @@ use std::fmt;
 use bytes::Bytes;
 use chrono::NaiveTime;
 
-use crate::errors::CIFParseError;
+use crate::codes::{activities_from_slice, activities_to_string, Activities};
+use crate::errors::{BuildError, CIFParseError};
 use crate::helpers::*;
 use crate::tiploc::*;
 
@@ -20,48 +21,93 @@ impl LocationIntermediate {
         &self.record
     }
 
-    pub fn tiploc(&self) -> Result<Tiploc, CIFParseError> {
-        let s = string_of_slice(&self.record[2..9])?;
-        Ok(Tiploc::of_string(s.to_owned()))
+    pub fn tiploc(&self) -> Result<Tiploc<'_>, CIFParseError> {
+        parse_field(&self.record, "tiploc", 2..9, |s| {
+            let s = string_of_slice(s)?;
+            Ok(Tiploc::of_string(s.to_owned()))
+        })
     }
     pub fn tiploc_suffix(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[9..10])?)
+        parse_field(&self.record, "tiploc_suffix", 9..10, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn scheduled_arrival_time(&self) -> Result<Option<NaiveTime>, CIFParseError> {
-        time_half_from_slice_opt(&self.record[10..15])
+        parse_field(
+            &self.record,
+            "scheduled_arrival_time",
+            10..15,
+            time_half_from_slice_opt,
+        )
     }
     pub fn scheduled_departure_time(&self) -> Result<Option<NaiveTime>, CIFParseError> {
-        time_half_from_slice_opt(&self.record[15..20])
+        parse_field(
+            &self.record,
+            "scheduled_departure_time",
+            15..20,
+            time_half_from_slice_opt,
+        )
     }
     pub fn scheduled_pass(&self) -> Result<Option<NaiveTime>, CIFParseError> {
-        time_half_from_slice_opt(&self.record[20..25])
+        parse_field(
+            &self.record,
+            "scheduled_pass",
+            20..25,
+            time_half_from_slice_opt,
+        )
     }
     pub fn public_arrival(&self) -> Result<Option<NaiveTime>, CIFParseError> {
-        time_from_slice_opt(&self.record[25..29])
+        parse_field(&self.record, "public_arrival", 25..29, time_from_slice_opt)
     }
     pub fn public_departure(&self) -> Result<Option<NaiveTime>, CIFParseError> {
-        time_from_slice_opt(&self.record[29..33])
+        parse_field(
+            &self.record,
+            "public_departure",
+            29..33,
+            time_from_slice_opt,
+        )
     }
     pub fn platform(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[33..36])?)
+        parse_field(&self.record, "platform", 33..36, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn line(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[36..39])?)
+        parse_field(&self.record, "line", 36..39, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn path(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[39..42])?)
+        parse_field(&self.record, "path", 39..42, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
-    pub fn activity(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[42..54])?)
+    pub fn activity(&self) -> Result<Activities, CIFParseError> {
+        parse_field(&self.record, "activity", 42..54, activities_from_slice)
     }
-    pub fn eng_allowance(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[54..56])?)
+    pub fn eng_allowance(&self) -> Result<Option<Allowance>, CIFParseError> {
+        parse_field(
+            &self.record,
+            "eng_allowance",
+            54..56,
+            allowance_from_slice_opt,
+        )
     }
-    pub fn path_allowance(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[56..58])?)
+    pub fn path_allowance(&self) -> Result<Option<Allowance>, CIFParseError> {
+        parse_field(
+            &self.record,
+            "path_allowance",
+            56..58,
+            allowance_from_slice_opt,
+        )
     }
-    pub fn perf_allowance(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[58..60])?)
+    pub fn perf_allowance(&self) -> Result<Option<Allowance>, CIFParseError> {
+        parse_field(
+            &self.record,
+            "perf_allowance",
+            58..60,
+            allowance_from_slice_opt,
+        )
     }
 }
 
@@ -85,9 +131,104 @@ impl fmt::Debug for LocationIntermediate {
     }
 }
 
+/// Builds a [`LocationIntermediate`]; a timing point needs either a pass
+/// time, or both arrival and departure times.
+pub struct LocationIntermediateBuilder {
+    record: RecordBuf,
+    arrival: bool,
+    departure: bool,
+    pass: bool,
+}
+
+impl LocationIntermediateBuilder {
+    pub fn new(tiploc: &Tiploc<'_>) -> Self {
+        let mut record = RecordBuf::new(b"LI");
+        record.put_tiploc("tiploc", 2..9, tiploc);
+        LocationIntermediateBuilder {
+            record,
+            arrival: false,
+            departure: false,
+            pass: false,
+        }
+    }
+
+    pub fn tiploc_suffix(mut self, suffix: &str) -> Self {
+        self.record.put("tiploc_suffix", 9..10, suffix);
+        self
+    }
+    pub fn scheduled_arrival_time(mut self, time: NaiveTime) -> Self {
+        self.record
+            .put_time_half("scheduled_arrival_time", 10..15, time);
+        self.arrival = true;
+        self
+    }
+    pub fn scheduled_departure_time(mut self, time: NaiveTime) -> Self {
+        self.record
+            .put_time_half("scheduled_departure_time", 15..20, time);
+        self.departure = true;
+        self
+    }
+    pub fn scheduled_pass(mut self, time: NaiveTime) -> Self {
+        self.record.put_time_half("scheduled_pass", 20..25, time);
+        self.pass = true;
+        self
+    }
+    pub fn public_arrival(mut self, time: NaiveTime) -> Self {
+        self.record.put_time("public_arrival", 25..29, time);
+        self
+    }
+    pub fn public_departure(mut self, time: NaiveTime) -> Self {
+        self.record.put_time("public_departure", 29..33, time);
+        self
+    }
+    pub fn platform(mut self, platform: &str) -> Self {
+        self.record.put("platform", 33..36, platform);
+        self
+    }
+    pub fn line(mut self, line: &str) -> Self {
+        self.record.put("line", 36..39, line);
+        self
+    }
+    pub fn path(mut self, path: &str) -> Self {
+        self.record.put("path", 39..42, path);
+        self
+    }
+    pub fn activity(mut self, activity: &Activities) -> Self {
+        self.record
+            .put("activity", 42..54, &activities_to_string(activity));
+        self
+    }
+    pub fn eng_allowance(mut self, allowance: Allowance) -> Self {
+        self.record
+            .put_allowance("eng_allowance", 54..56, allowance);
+        self
+    }
+    pub fn path_allowance(mut self, allowance: Allowance) -> Self {
+        self.record
+            .put_allowance("path_allowance", 56..58, allowance);
+        self
+    }
+    pub fn perf_allowance(mut self, allowance: Allowance) -> Self {
+        self.record
+            .put_allowance("perf_allowance", 58..60, allowance);
+        self
+    }
+
+    pub fn build(self) -> Result<LocationIntermediate, BuildError> {
+        if self.pass && (self.arrival || self.departure) {
+            return Err(BuildError::ConflictingTimes);
+        }
+        if !(self.pass || (self.arrival && self.departure)) {
+            return Err(BuildError::MissingTimes);
+        }
+        Ok(LocationIntermediate::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::codes::Activity;
 
     #[test]
     fn should_parse_location_intermediate() {
@@ -118,7 +259,11 @@ mod test {
         assert_eq!(example.path().unwrap(), None);
         assert_eq!(example.eng_allowance().unwrap(), None);
         assert_eq!(example.path_allowance().unwrap(), None);
-        assert_eq!(example.activity().unwrap(), Some("T"));
+        assert_eq!(
+            example.activity().unwrap().iter().collect::<Vec<_>>(),
+            vec![&Activity::TakesUpAndSetsDown]
+        );
+        assert!(example.activity().unwrap().is_public_stop());
         assert_eq!(example.perf_allowance().unwrap(), None);
     }
     #[test]
@@ -147,7 +292,59 @@ mod test {
         assert_eq!(example.path().unwrap(), None);
         assert_eq!(example.eng_allowance().unwrap(), None);
         assert_eq!(example.path_allowance().unwrap(), None);
-        assert_eq!(example.activity().unwrap(), None);
+        assert!(example.activity().unwrap().is_empty());
         assert_eq!(example.perf_allowance().unwrap(), None);
     }
+    #[test]
+    fn should_parse_location_intermediate_allowances() {
+        // From sample-larger.cif
+        let i = b"LILBRDFJN           1123 00000000                     1 2H                      ";
+        assert_eq!(80, i.len());
+        let example = LocationIntermediate::from_record(Bytes::from(i.as_ref()));
+        assert_eq!(
+            example.eng_allowance().unwrap().map(|a| a.duration()),
+            Some(chrono::Duration::minutes(1))
+        );
+        assert_eq!(
+            example.path_allowance().unwrap().map(|a| a.duration()),
+            Some(chrono::Duration::seconds(150))
+        );
+        assert_eq!(example.perf_allowance().unwrap(), None);
+    }
+
+    #[test]
+    fn should_require_pass_or_arrival_and_departure() {
+        let tiploc = Tiploc::from("WLOE");
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let e = LocationIntermediateBuilder::new(&tiploc)
+            .build()
+            .unwrap_err();
+        assert!(matches!(e, BuildError::MissingTimes), "{:?}", e);
+
+        let e = LocationIntermediateBuilder::new(&tiploc)
+            .scheduled_arrival_time(time(23, 27))
+            .build()
+            .unwrap_err();
+        assert!(matches!(e, BuildError::MissingTimes), "{:?}", e);
+
+        let e = LocationIntermediateBuilder::new(&tiploc)
+            .scheduled_pass(time(23, 27))
+            .scheduled_departure_time(time(23, 28))
+            .build()
+            .unwrap_err();
+        assert!(matches!(e, BuildError::ConflictingTimes), "{:?}", e);
+
+        let stop = LocationIntermediateBuilder::new(&tiploc)
+            .scheduled_arrival_time(time(23, 27))
+            .scheduled_departure_time(time(23, 28))
+            .build()
+            .expect("stop");
+        assert_eq!(stop.scheduled_pass().unwrap(), None);
+        let pass = LocationIntermediateBuilder::new(&tiploc)
+            .scheduled_pass(time(23, 27))
+            .build()
+            .expect("pass");
+        assert_eq!(pass.scheduled_arrival_time().unwrap(), None);
+    }
 }
@@ -4,8 +4,12 @@ use bytes::Bytes;
 use chrono::NaiveTime;
 
 use crate::{
-    errors::CIFParseError,
-    helpers::{string_of_slice, string_of_slice_opt, time_from_slice, time_half_from_slice},
+    codes::{activities_from_slice, activities_to_string, Activities},
+    errors::{BuildError, CIFParseError},
+    helpers::{
+        allowance_from_slice_opt, parse_field, string_of_slice, string_of_slice_opt,
+        time_from_slice, time_half_from_slice, Allowance, RecordBuf,
+    },
     Tiploc,
 };
 
@@ -22,36 +26,64 @@ impl LocationOrigin {
         &self.record
     }
 
-    pub fn tiploc(&self) -> Result<Tiploc, CIFParseError> {
-        let s = string_of_slice(&self.record[2..9])?;
-        Ok(Tiploc::of_string(s.to_owned()))
+    pub fn tiploc(&self) -> Result<Tiploc<'_>, CIFParseError> {
+        parse_field(&self.record, "tiploc", 2..9, |s| {
+            let s = string_of_slice(s)?;
+            Ok(Tiploc::of_string(s.to_owned()))
+        })
     }
     pub fn tiploc_suffix(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[9..10])?)
+        parse_field(&self.record, "tiploc_suffix", 9..10, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn scheduled_departure_time(&self) -> Result<NaiveTime, CIFParseError> {
-        time_half_from_slice(&self.record[10..15])
+        parse_field(
+            &self.record,
+            "scheduled_departure_time",
+            10..15,
+            time_half_from_slice,
+        )
     }
     pub fn public_departure(&self) -> Result<NaiveTime, CIFParseError> {
-        time_from_slice(&self.record[15..19])
+        parse_field(&self.record, "public_departure", 15..19, time_from_slice)
     }
     pub fn platform(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[19..22])?)
+        parse_field(&self.record, "platform", 19..22, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn line(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[22..25])?)
+        parse_field(&self.record, "line", 22..25, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
-    pub fn eng_allowance(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[25..27])?)
+    pub fn eng_allowance(&self) -> Result<Option<Allowance>, CIFParseError> {
+        parse_field(
+            &self.record,
+            "eng_allowance",
+            25..27,
+            allowance_from_slice_opt,
+        )
     }
-    pub fn path_allowance(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[27..29])?)
+    pub fn path_allowance(&self) -> Result<Option<Allowance>, CIFParseError> {
+        parse_field(
+            &self.record,
+            "path_allowance",
+            27..29,
+            allowance_from_slice_opt,
+        )
     }
-    pub fn activity(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[29..41])?)
+    pub fn activity(&self) -> Result<Activities, CIFParseError> {
+        parse_field(&self.record, "activity", 29..41, activities_from_slice)
     }
-    pub fn perf_allowance(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[41..43])?)
+    pub fn perf_allowance(&self) -> Result<Option<Allowance>, CIFParseError> {
+        parse_field(
+            &self.record,
+            "perf_allowance",
+            41..43,
+            allowance_from_slice_opt,
+        )
     }
 }
 
@@ -71,9 +103,61 @@ impl fmt::Debug for LocationOrigin {
     }
 }
 
+pub struct LocationOriginBuilder {
+    record: RecordBuf,
+}
+
+impl LocationOriginBuilder {
+    pub fn new(tiploc: &Tiploc<'_>, departure: NaiveTime, public_departure: NaiveTime) -> Self {
+        let mut record = RecordBuf::new(b"LO");
+        record.put_tiploc("tiploc", 2..9, tiploc);
+        record.put_time_half("departure", 10..15, departure);
+        record.put_time("public_departure", 15..19, public_departure);
+        LocationOriginBuilder { record }
+    }
+
+    pub fn tiploc_suffix(mut self, suffix: &str) -> Self {
+        self.record.put("tiploc_suffix", 9..10, suffix);
+        self
+    }
+    pub fn platform(mut self, platform: &str) -> Self {
+        self.record.put("platform", 19..22, platform);
+        self
+    }
+    pub fn line(mut self, line: &str) -> Self {
+        self.record.put("line", 22..25, line);
+        self
+    }
+    pub fn eng_allowance(mut self, allowance: Allowance) -> Self {
+        self.record
+            .put_allowance("eng_allowance", 25..27, allowance);
+        self
+    }
+    pub fn path_allowance(mut self, allowance: Allowance) -> Self {
+        self.record
+            .put_allowance("path_allowance", 27..29, allowance);
+        self
+    }
+    pub fn activity(mut self, activity: &Activities) -> Self {
+        self.record
+            .put("activity", 29..41, &activities_to_string(activity));
+        self
+    }
+    pub fn perf_allowance(mut self, allowance: Allowance) -> Self {
+        self.record
+            .put_allowance("perf_allowance", 41..43, allowance);
+        self
+    }
+
+    pub fn build(self) -> Result<LocationOrigin, BuildError> {
+        Ok(LocationOrigin::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::codes::Activity;
 
     #[test]
     fn should_parse_location_origin() {
@@ -95,7 +179,10 @@ mod test {
         assert_eq!(example.line().unwrap(), Some("FL"));
         assert_eq!(example.eng_allowance().unwrap(), None);
         assert_eq!(example.path_allowance().unwrap(), None);
-        assert_eq!(example.activity().unwrap(), Some("TB"));
+        assert_eq!(
+            example.activity().unwrap().iter().collect::<Vec<_>>(),
+            vec![&Activity::TrainBegins]
+        );
         assert_eq!(example.perf_allowance().unwrap(), None);
     }
 }
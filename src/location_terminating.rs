@@ -3,6 +3,7 @@ use std::fmt;
 use bytes::Bytes;
 use chrono::NaiveTime;
 
+use crate::codes::{activities_from_slice, activities_to_string, Activities};
 use crate::errors::*;
 use crate::helpers::*;
 use crate::tiploc::*;
@@ -20,24 +21,40 @@ impl LocationTerminating {
         &self.record
     }
 
-    pub fn tiploc(&self) -> Result<Tiploc, CIFParseError> {
-        let s = string_of_slice(&self.record[2..9])?;
-        Ok(Tiploc::of_string(s.to_owned()))
+    pub fn tiploc(&self) -> Result<Tiploc<'_>, CIFParseError> {
+        parse_field(&self.record, "tiploc", 2..9, |s| {
+            let s = string_of_slice(s)?;
+            Ok(Tiploc::of_string(s.to_owned()))
+        })
+    }
+    pub fn tiploc_suffix(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "tiploc_suffix", 9..10, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn scheduled_arrival_time(&self) -> Result<NaiveTime, CIFParseError> {
-        time_half_from_slice(&self.record[10..15])
+        parse_field(
+            &self.record,
+            "scheduled_arrival_time",
+            10..15,
+            time_half_from_slice,
+        )
     }
     pub fn public_arrival(&self) -> Result<NaiveTime, CIFParseError> {
-        time_from_slice(&self.record[15..19])
+        parse_field(&self.record, "public_arrival", 15..19, time_from_slice)
     }
     pub fn platform(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[19..22])?)
+        parse_field(&self.record, "platform", 19..22, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn path(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[22..25])?)
+        parse_field(&self.record, "path", 22..25, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
-    pub fn activity(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[25..37])?)
+    pub fn activity(&self) -> Result<Activities, CIFParseError> {
+        parse_field(&self.record, "activity", 25..37, activities_from_slice)
     }
 }
 
@@ -45,6 +62,7 @@ impl fmt::Debug for LocationTerminating {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s = f.debug_struct("LocationOrigin");
         s.field("tiploc", &self.tiploc());
+        s.field("tiploc_suffix", &self.tiploc_suffix());
         s.field("scheduled_arrival_time", &self.scheduled_arrival_time());
         s.field("public_arrival", &self.public_arrival());
         s.field("platform", &self.platform());
@@ -55,9 +73,46 @@ impl fmt::Debug for LocationTerminating {
     }
 }
 
+pub struct LocationTerminatingBuilder {
+    record: RecordBuf,
+}
+
+impl LocationTerminatingBuilder {
+    pub fn new(tiploc: &Tiploc<'_>, arrival: NaiveTime, public_arrival: NaiveTime) -> Self {
+        let mut record = RecordBuf::new(b"LT");
+        record.put_tiploc("tiploc", 2..9, tiploc);
+        record.put_time_half("arrival", 10..15, arrival);
+        record.put_time("public_arrival", 15..19, public_arrival);
+        LocationTerminatingBuilder { record }
+    }
+
+    pub fn tiploc_suffix(mut self, suffix: &str) -> Self {
+        self.record.put("tiploc_suffix", 9..10, suffix);
+        self
+    }
+    pub fn platform(mut self, platform: &str) -> Self {
+        self.record.put("platform", 19..22, platform);
+        self
+    }
+    pub fn path(mut self, path: &str) -> Self {
+        self.record.put("path", 22..25, path);
+        self
+    }
+    pub fn activity(mut self, activity: &Activities) -> Self {
+        self.record
+            .put("activity", 25..37, &activities_to_string(activity));
+        self
+    }
+
+    pub fn build(self) -> Result<LocationTerminating, BuildError> {
+        Ok(LocationTerminating::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::codes::Activity;
 
     #[test]
     fn should_parse_location_terminating() {
@@ -76,6 +131,9 @@ mod test {
         );
         assert_eq!(example.platform().unwrap(), Some("1"));
         assert_eq!(example.path().unwrap(), None);
-        assert_eq!(example.activity().unwrap(), Some("TF"));
+        assert_eq!(
+            example.activity().unwrap().iter().collect::<Vec<_>>(),
+            vec![&Activity::TrainFinishes]
+        );
     }
 }
@@ -0,0 +1,228 @@
+use chrono::{DateTime, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Europe::London;
+
+use crate::{CIFParseError, CallTimes, Schedule, Tiploc};
+
+/// How a UK wall-clock time maps onto UTC.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LondonTime {
+    Single(DateTime<Utc>),
+    /// The time falls in the hour that is repeated when the clocks go back
+    /// in October.
+    Ambiguous {
+        earlier: DateTime<Utc>,
+        later: DateTime<Utc>,
+    },
+    /// The time falls in the hour that is skipped when the clocks go forward
+    /// in March; we interpret it with the offset in force before the change.
+    Skipped(DateTime<Utc>),
+}
+
+/// A [`CallTimes`] with every time converted to a UTC instant.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UtcCallTimes {
+    pub tiploc: Tiploc<'static>,
+    pub day_offset: u32,
+    pub arrival: Option<DateTime<Utc>>,
+    pub departure: Option<DateTime<Utc>>,
+    pub pass: Option<DateTime<Utc>>,
+    pub public_arrival: Option<DateTime<Utc>>,
+    pub public_departure: Option<DateTime<Utc>>,
+}
+
+pub fn london_to_utc(local: NaiveDateTime) -> LondonTime {
+    match London.from_local_datetime(&local) {
+        LocalResult::Single(t) => LondonTime::Single(t.with_timezone(&Utc)),
+        LocalResult::Ambiguous(earlier, later) => LondonTime::Ambiguous {
+            earlier: earlier.with_timezone(&Utc),
+            later: later.with_timezone(&Utc),
+        },
+        LocalResult::None => {
+            // The hour before the gap is always valid, and carries the old
+            // offset.
+            let before = London
+                .from_local_datetime(&(local - Duration::hours(1)))
+                .earliest()
+                .expect("time before clock change");
+            LondonTime::Skipped(before.with_timezone(&Utc) + Duration::hours(1))
+        }
+    }
+}
+
+impl LondonTime {
+    pub fn earliest(&self) -> DateTime<Utc> {
+        match *self {
+            LondonTime::Single(t) | LondonTime::Skipped(t) => t,
+            LondonTime::Ambiguous { earlier, .. } => earlier,
+        }
+    }
+    pub fn latest(&self) -> DateTime<Utc> {
+        match *self {
+            LondonTime::Single(t) | LondonTime::Skipped(t) => t,
+            LondonTime::Ambiguous { later, .. } => later,
+        }
+    }
+    /// Picks the earliest interpretation that isn't before `min`, so that a
+    /// train's times stay in order through the repeated hour.
+    pub fn not_before(&self, min: DateTime<Utc>) -> DateTime<Utc> {
+        match *self {
+            LondonTime::Ambiguous { earlier, later } if earlier < min => later,
+            other => other.earliest(),
+        }
+    }
+    /// Picks the interpretation closest to `near`.
+    pub fn nearest(&self, near: DateTime<Utc>) -> DateTime<Utc> {
+        let (earliest, latest) = (self.earliest(), self.latest());
+        if (latest - near).abs() < (earliest - near).abs() {
+            latest
+        } else {
+            earliest
+        }
+    }
+}
+
+impl Schedule {
+    /// As [`Schedule::call_times`], but converting each time to UTC. Working
+    /// times that fall in the repeated hour are resolved so that they stay in
+    /// order, and public times take the interpretation nearest to the
+    /// corresponding working time.
+    pub fn utc_call_times(&self, run_date: NaiveDate) -> Result<Vec<UtcCallTimes>, CIFParseError> {
+        let mut last: Option<DateTime<Utc>> = None;
+        let mut working = |t: Option<NaiveDateTime>| {
+            t.map(|t| {
+                let local = london_to_utc(t);
+                let utc = match last {
+                    Some(min) => local.not_before(min),
+                    None => local.earliest(),
+                };
+                last = Some(utc);
+                utc
+            })
+        };
+
+        let mut calls = Vec::new();
+        for call in self.call_times(run_date)? {
+            let CallTimes {
+                tiploc,
+                day_offset,
+                arrival,
+                departure,
+                pass,
+                public_arrival,
+                public_departure,
+            } = call;
+            let arrival = working(arrival);
+            let pass = working(pass);
+            let departure = working(departure);
+            calls.push(UtcCallTimes {
+                tiploc,
+                day_offset,
+                arrival,
+                departure,
+                pass,
+                public_arrival: public(public_arrival, arrival),
+                public_departure: public(public_departure, departure),
+            });
+        }
+        Ok(calls)
+    }
+}
+
+fn public(time: Option<NaiveDateTime>, working: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    let local = london_to_utc(time?);
+    Some(match working {
+        Some(working) => local.nearest(working),
+        None => local.earliest(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use fallible_iterator::FallibleIterator;
+
+    use super::*;
+    use crate::{Assembled, Reader, ScheduleAssembler};
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, 0)
+            .unwrap()
+    }
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&local(y, mo, d, h, mi))
+    }
+
+    #[test]
+    fn should_convert_summer_time() {
+        assert_eq!(
+            london_to_utc(local(2020, 6, 28, 19, 34)),
+            LondonTime::Single(utc(2020, 6, 28, 18, 34))
+        );
+    }
+
+    #[test]
+    fn should_convert_winter_time() {
+        assert_eq!(
+            london_to_utc(local(2020, 12, 1, 9, 0)),
+            LondonTime::Single(utc(2020, 12, 1, 9, 0))
+        );
+    }
+
+    #[test]
+    fn should_report_repeated_hour() {
+        assert_eq!(
+            london_to_utc(local(2020, 10, 25, 1, 30)),
+            LondonTime::Ambiguous {
+                earlier: utc(2020, 10, 25, 0, 30),
+                later: utc(2020, 10, 25, 1, 30),
+            }
+        );
+    }
+
+    #[test]
+    fn should_report_skipped_hour() {
+        assert_eq!(
+            london_to_utc(local(2020, 3, 29, 1, 30)),
+            LondonTime::Skipped(utc(2020, 3, 29, 1, 30))
+        );
+    }
+
+    fn schedule() -> Schedule {
+        let data: &[u8] = b"\
+BSNC000462005172012060000001 PEE5J11    111841820 DMUE   090                   P\n\
+LOWLOE    0050 00506  FL     TB                                                 \n\
+LIWLOE    0130 0131      01300131C        T                                     \n\
+LTTUNWELL 0240 02401     TF                                                     \n";
+        match ScheduleAssembler::new(Reader::new(data)).next().unwrap() {
+            Some(Assembled::Schedule(schedule)) => schedule,
+            other => panic!("Expected schedule, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_convert_schedule_through_repeated_hour() {
+        let run_date = NaiveDate::from_ymd_opt(2020, 10, 25).unwrap();
+        let calls = schedule().utc_call_times(run_date).expect("call times");
+        // 00:50 BST
+        assert_eq!(calls[0].departure, Some(utc(2020, 10, 24, 23, 50)));
+        // 01:30 BST, the first time around.
+        assert_eq!(calls[1].arrival, Some(utc(2020, 10, 25, 0, 30)));
+        assert_eq!(calls[1].public_departure, Some(utc(2020, 10, 25, 0, 31)));
+        // 02:40 GMT
+        assert_eq!(calls[2].arrival, Some(utc(2020, 10, 25, 2, 40)));
+    }
+
+    #[test]
+    fn should_convert_schedule_through_skipped_hour() {
+        let run_date = NaiveDate::from_ymd_opt(2020, 3, 29).unwrap();
+        let calls = schedule().utc_call_times(run_date).expect("call times");
+        // 00:50 GMT
+        assert_eq!(calls[0].departure, Some(utc(2020, 3, 29, 0, 50)));
+        // 01:30 doesn't exist, so is read as GMT
+        assert_eq!(calls[1].arrival, Some(utc(2020, 3, 29, 1, 30)));
+        // 02:40 BST
+        assert_eq!(calls[2].arrival, Some(utc(2020, 3, 29, 1, 40)));
+    }
+}
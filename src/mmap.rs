@@ -0,0 +1,144 @@
+//! Reading records straight out of memory, without copying them.
+
+use std::fs::File;
+use std::path::Path;
+
+use bytes::Bytes;
+use fallible_iterator::FallibleIterator;
+use memmap2::Mmap;
+
+use crate::reader::Framer;
+use crate::{ReaderError, ReaderOptions, ReaderResult, Record, Repair};
+
+/// Reads records from data that's already in memory, usually a memory
+/// mapped file. Each record refers directly to the underlying data, so
+/// they're cheap to hold on to, although the data will stay alive for as
+/// long as any of them does.
+///
+/// Lines repaired in lenient mode are the exception, and are copied.
+pub struct MmapReader {
+    data: Bytes,
+    rest: Bytes,
+    framer: Framer,
+}
+
+impl MmapReader {
+    pub fn new(data: Bytes) -> Self {
+        Self::with_options(data, ReaderOptions::default())
+    }
+
+    pub fn with_options(data: Bytes, options: ReaderOptions) -> Self {
+        MmapReader {
+            rest: data.clone(),
+            data,
+            framer: Framer::new(options),
+        }
+    }
+
+    /// Maps the file at `path` into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other
+    /// process, while the reader or any record read from it is alive.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> ReaderResult<Self> {
+        Self::open_with_options(path, ReaderOptions::default())
+    }
+
+    /// Maps the file at `path` into memory.
+    ///
+    /// # Safety
+    ///
+    /// As for [`MmapReader::open`].
+    pub unsafe fn open_with_options<P: AsRef<Path>>(
+        path: P,
+        options: ReaderOptions,
+    ) -> ReaderResult<Self> {
+        let file = File::open(path)?;
+        let map = Mmap::map(&file)?;
+        Ok(Self::with_options(Bytes::from_owner(map), options))
+    }
+
+    pub fn read_next(&mut self) -> ReaderResult<Option<Record>> {
+        self.framer.decode(&mut self.rest, true)
+    }
+
+    /// The number of malformed lines skipped so far in recovery mode.
+    pub fn malformed_count(&self) -> usize {
+        self.framer.malformed_count()
+    }
+
+    /// Removes and returns the repairs made since the last call.
+    pub fn take_repairs(&mut self) -> Vec<Repair> {
+        self.framer.take_repairs()
+    }
+
+    /// All of the data being read, including what's been read already.
+    pub fn get_ref(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+impl FallibleIterator for MmapReader {
+    type Item = Record;
+
+    type Error = ReaderError;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        self.read_next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Reader;
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample-larger.cif");
+
+    #[test]
+    fn should_read_same_records_as_reader() {
+        let expected: Vec<Record> = Reader::new(SAMPLE_FILE).collect().expect("read");
+        let actual: Vec<Record> = MmapReader::new(Bytes::from_static(SAMPLE_FILE))
+            .collect()
+            .expect("read");
+        assert!(actual == expected, "Records differ from Reader");
+    }
+
+    #[test]
+    fn should_not_copy_records() {
+        let range = SAMPLE_FILE.as_ptr_range();
+        let mut rdr = MmapReader::new(Bytes::from_static(SAMPLE_FILE));
+        while let Some(record) = rdr.read_next().expect("read") {
+            let buf = record.buf().as_ptr();
+            assert!(
+                range.contains(&buf),
+                "{:?} should point into the input",
+                record
+            );
+        }
+    }
+
+    #[test]
+    fn should_map_file() {
+        let mut rdr = unsafe { MmapReader::open("tests/sample.cif") }.expect("open");
+        let mut count = 0;
+        while rdr.read_next().expect("read").is_some() {
+            count += 1;
+        }
+        assert_eq!(count * 81, rdr.get_ref().len());
+    }
+
+    #[test]
+    fn should_report_invalid_record() {
+        let data = Bytes::from_static(
+            b"ZZ                                                                             \n ",
+        );
+        let e = MmapReader::new(data).read_next().unwrap_err();
+        assert!(
+            matches!(e, ReaderError::InvalidRecord { offset: 0, .. }),
+            "{:?} should be invalid record at 0",
+            e
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! Reading large in-memory or mapped extracts on several threads at once.
+//!
+//! The input is cut into chunks at line boundaries, moving each cut forward
+//! past any records that belong to the schedule before it, so that every
+//! schedule is read whole by one thread. Chunks are read a batch at a time
+//! and their contents handed out in file order, so the results are just as
+//! if the input had been read by a single [`Reader`] or
+//! [`ScheduleAssembler`].
+
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use fallible_iterator::FallibleIterator;
+use rayon::prelude::*;
+use tracing::{debug, warn};
+
+use crate::errors::Position;
+use crate::reader::Framer;
+use crate::{
+    Assembled, AssemblerError, Reader, ReaderError, ReaderOptions, ReaderResult, Record, Repair,
+    ScheduleAssembler,
+};
+
+const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+// Records that can only appear within a schedule, and so can't start a
+// chunk.
+const SCHEDULE_CONTINUATIONS: &[&[u8]] = &[b"BX", b"LO", b"LI", b"CR", b"LT"];
+
+/// Reads records from data in memory using the rayon thread pool.
+pub struct ParallelReader {
+    data: Bytes,
+    options: ReaderOptions,
+    chunk_size: usize,
+    // Where the next batch of chunks starts.
+    next: Position,
+    pending: VecDeque<Record>,
+    error: Option<ReaderError>,
+    repairs: Vec<Repair>,
+    malformed: usize,
+}
+
+/// Assembles schedules from data in memory using the rayon thread pool,
+/// each thread assembling the schedules in its own chunk of the input.
+pub struct ParallelAssembler {
+    reader: ParallelReader,
+    pending: VecDeque<Assembled>,
+    error: Option<AssemblerError>,
+}
+
+// The outcome of reading one chunk.
+struct Chunk<T, E> {
+    items: Vec<T>,
+    error: Option<E>,
+    // Where reading stopped, which should be where the next chunk starts.
+    end: Position,
+    repairs: Vec<Repair>,
+    malformed: usize,
+}
+
+impl ParallelReader {
+    pub fn new(data: Bytes) -> Self {
+        Self::with_options(data, ReaderOptions::default())
+    }
+
+    pub fn with_options(data: Bytes, options: ReaderOptions) -> Self {
+        ParallelReader {
+            data,
+            options,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            next: Position { offset: 0, line: 1 },
+            pending: VecDeque::new(),
+            error: None,
+            repairs: Vec::new(),
+            malformed: 0,
+        }
+    }
+
+    /// Maps the file at `path` into memory.
+    ///
+    /// # Safety
+    ///
+    /// As for [`MmapReader::open`](crate::MmapReader::open).
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open<P: AsRef<std::path::Path>>(
+        path: P,
+        options: ReaderOptions,
+    ) -> ReaderResult<Self> {
+        let file = std::fs::File::open(path)?;
+        let map = memmap2::Mmap::map(&file)?;
+        Ok(Self::with_options(Bytes::from_owner(map), options))
+    }
+
+    /// Sets roughly how many bytes each thread reads at a time. Chunks are
+    /// extended to finish at the end of a schedule, so may be longer.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Reads whole schedules rather than records, from the start of the
+    /// input.
+    pub fn par_schedules(self) -> ParallelAssembler {
+        ParallelAssembler {
+            reader: ParallelReader::with_options(self.data, self.options)
+                .chunk_size(self.chunk_size),
+            pending: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    pub fn read_next(&mut self) -> ReaderResult<Option<Record>> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Ok(Some(record));
+            }
+            if let Some(e) = self.error.take() {
+                return Err(e);
+            }
+            if self.is_finished() {
+                return Ok(None);
+            }
+            let (records, error) = self.read_batch(read_chunk);
+            self.pending.extend(records);
+            self.error = error;
+        }
+    }
+
+    /// The number of malformed lines skipped so far in recovery mode.
+    pub fn malformed_count(&self) -> usize {
+        self.malformed
+    }
+
+    /// Removes and returns the repairs made since the last call.
+    pub fn take_repairs(&mut self) -> Vec<Repair> {
+        std::mem::take(&mut self.repairs)
+    }
+
+    pub fn get_ref(&self) -> &Bytes {
+        &self.data
+    }
+
+    fn is_finished(&self) -> bool {
+        self.next.offset >= self.data.len()
+    }
+
+    // Reads enough chunks with `read` to keep every thread busy, returning
+    // what they found in order, up to the first error.
+    fn read_batch<T, E, F>(&mut self, read: F) -> (Vec<T>, Option<E>)
+    where
+        T: Send,
+        E: Send,
+        F: Fn(&Bytes, ReaderOptions, Position, usize) -> Chunk<T, E> + Sync,
+    {
+        let starts = self.split(rayon::current_num_threads() * 2);
+        debug!(start = ?self.next, chunks = starts.len(), "Reading batch");
+
+        let chunks = starts
+            .par_iter()
+            .map(|(start, end)| read(&self.data, self.options, *start, *end))
+            .collect::<Vec<_>>();
+
+        let mut items = Vec::new();
+        for ((start, end), mut chunk) in starts.into_iter().zip(chunks) {
+            // Normally each chunk ends exactly where the next begins, but a
+            // line with a newline in the middle of it could throw that out,
+            // so we go back and read it again from where we really are.
+            if start != self.next {
+                debug!(expected = ?start, actual = ?self.next, "Re-reading chunk");
+                chunk = read(&self.data, self.options, self.next, end);
+            }
+            items.extend(chunk.items);
+            self.repairs.extend(chunk.repairs);
+            self.malformed += chunk.malformed;
+            self.next = chunk.end;
+
+            if chunk.error.is_some() {
+                self.next.offset = self.data.len();
+                return (items, chunk.error);
+            }
+        }
+
+        if self.is_finished() && self.malformed > 0 {
+            warn!("Skipped {} malformed lines", self.malformed);
+        }
+        (items, None)
+    }
+
+    // Works out where up to `count` chunks start and end, from where the
+    // last batch left off.
+    fn split(&self, count: usize) -> Vec<(Position, usize)> {
+        let mut chunks = Vec::with_capacity(count);
+        let mut start = self.next;
+        while chunks.len() < count && start.offset < self.data.len() {
+            let end = self.boundary_after(start.offset + self.chunk_size);
+            chunks.push((start, end));
+
+            let lines = self.data[start.offset..end]
+                .iter()
+                .filter(|b| **b == b'\n')
+                .count();
+            start = Position {
+                offset: end,
+                line: start.line + lines,
+            };
+        }
+        chunks
+    }
+
+    // Finds the start of the first line at or after `offset` that isn't part
+    // of a schedule.
+    fn boundary_after(&self, offset: usize) -> usize {
+        let data = &self.data[..];
+        if offset >= data.len() {
+            return data.len();
+        }
+        // Back up one, in case `offset` is already the start of a line.
+        let mut pos = offset - 1;
+        loop {
+            pos = match data[pos..].iter().position(|b| *b == b'\n') {
+                Some(nl) => pos + nl + 1,
+                None => return data.len(),
+            };
+            let line = &data[pos..];
+            if !SCHEDULE_CONTINUATIONS.iter().any(|t| line.starts_with(t)) {
+                return pos;
+            }
+        }
+    }
+}
+
+impl ParallelAssembler {
+    pub fn read_next(&mut self) -> Result<Option<Assembled>, AssemblerError> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Ok(Some(item));
+            }
+            if let Some(e) = self.error.take() {
+                return Err(e);
+            }
+            if self.reader.is_finished() {
+                return Ok(None);
+            }
+            let (items, error) = self.reader.read_batch(assemble_chunk);
+            self.pending.extend(items);
+            self.error = error;
+        }
+    }
+
+    /// The number of malformed lines skipped so far in recovery mode.
+    pub fn malformed_count(&self) -> usize {
+        self.reader.malformed_count()
+    }
+
+    /// Removes and returns the repairs made since the last call.
+    pub fn take_repairs(&mut self) -> Vec<Repair> {
+        self.reader.take_repairs()
+    }
+
+    pub fn get_ref(&self) -> &Bytes {
+        self.reader.get_ref()
+    }
+}
+
+// Reads records from `start` until we reach `end`, carrying on into the
+// rest of the input if need be to finish off the last line.
+fn read_chunk(
+    data: &Bytes,
+    options: ReaderOptions,
+    start: Position,
+    end: usize,
+) -> Chunk<Record, ReaderError> {
+    let mut buf = data.slice(start.offset..);
+    let mut framer = Framer::starting_at(options, start).without_summary();
+    let mut items = Vec::new();
+    let mut error = None;
+
+    while framer.position().offset < end {
+        match framer.decode(&mut buf, true) {
+            Ok(Some(record)) => items.push(record),
+            Ok(None) => break,
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
+
+    let end = match framer.position() {
+        // A partial line at the end of the input gets dropped, so make sure
+        // we know we've finished.
+        position if buf.is_empty() => Position {
+            offset: data.len(),
+            ..position
+        },
+        position => position,
+    };
+
+    Chunk {
+        items,
+        error,
+        end,
+        repairs: framer.take_repairs(),
+        malformed: framer.malformed_count(),
+    }
+}
+
+// Assembles schedules from `start` until we reach `end`. The assembler has
+// to look one record past the end of a schedule with no locations to know
+// that it's finished, so anything it finds from `end` on is left for the
+// next chunk.
+fn assemble_chunk(
+    data: &Bytes,
+    options: ReaderOptions,
+    start: Position,
+    end: usize,
+) -> Chunk<Assembled, AssemblerError> {
+    let reader = Reader::starting_at(&data[start.offset..], options, start);
+    let mut assembler = ScheduleAssembler::new(reader);
+    let mut items = Vec::new();
+    let mut error = None;
+
+    let stop = loop {
+        let position = assembler.get_ref().position();
+        if assembler.is_idle() && position.offset >= end {
+            break position;
+        }
+        match assembler.read_next() {
+            Ok(Some(item)) => match assembler.position() {
+                Some(position) if position.offset >= end => break position,
+                _ => items.push(item),
+            },
+            Ok(None) => {
+                break Position {
+                    offset: data.len(),
+                    ..assembler.get_ref().position()
+                }
+            }
+            Err(e) => {
+                error = Some(e);
+                break assembler.get_ref().position();
+            }
+        }
+    };
+
+    let malformed = items
+        .iter()
+        .filter(|item| matches!(item, Assembled::Other(Record::Malformed { .. })))
+        .count();
+    let mut repairs = assembler.get_mut().take_repairs();
+    repairs.retain(|repair| repair.offset < stop.offset);
+
+    Chunk {
+        items,
+        error,
+        end: stop,
+        repairs,
+        malformed,
+    }
+}
+
+impl FallibleIterator for ParallelReader {
+    type Item = Record;
+
+    type Error = ReaderError;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        self.read_next()
+    }
+}
+
+impl FallibleIterator for ParallelAssembler {
+    type Item = Assembled;
+
+    type Error = AssemblerError;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        self.read_next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Reader;
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample-larger.cif");
+
+    fn read_both(data: &'static [u8], options: ReaderOptions) -> (Vec<Record>, Vec<Record>) {
+        let expected = Reader::with_options(data, options).collect().expect("read");
+        let actual = ParallelReader::with_options(Bytes::from_static(data), options)
+            .chunk_size(1000)
+            .collect()
+            .expect("read");
+        (expected, actual)
+    }
+
+    #[test]
+    fn should_read_same_records_as_reader() {
+        let (expected, actual) = read_both(SAMPLE_FILE, ReaderOptions::default());
+        assert_eq!(actual.len(), expected.len());
+        assert!(actual == expected, "Records differ from Reader");
+    }
+
+    #[test]
+    fn should_not_split_schedules() {
+        let rdr = ParallelReader::new(Bytes::from_static(SAMPLE_FILE)).chunk_size(1000);
+        let chunks = rdr.split(1000);
+        assert!(chunks.len() > 1, "{:?} should be several chunks", chunks);
+        for (start, _) in chunks {
+            let line = &SAMPLE_FILE[start.offset..];
+            assert!(
+                !SCHEDULE_CONTINUATIONS.iter().any(|t| line.starts_with(t)),
+                "Chunk at {:?} starts within a schedule",
+                start
+            );
+            assert_eq!(start.line, start.offset / 81 + 1);
+        }
+    }
+
+    #[test]
+    fn should_read_leniently_across_chunks() {
+        let data = SAMPLE_FILE
+            .chunks(81)
+            .flat_map(|line| {
+                let line = std::str::from_utf8(line).expect("utf8");
+                format!("{}\r\n", line.trim_end()).into_bytes()
+            })
+            .collect::<Vec<u8>>();
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+
+        let (expected, actual) = read_both(data, ReaderOptions::new().lenient(true));
+        assert!(actual == expected, "Records differ from Reader");
+    }
+
+    #[test]
+    fn should_report_first_error_in_order() {
+        let mut data = SAMPLE_FILE.to_vec();
+        // Lose a byte at line 101, and then again further on.
+        data.remove(100 * 81 + 5);
+        data.remove(300 * 81 + 5);
+
+        let mut rdr = ParallelReader::new(Bytes::from(data)).chunk_size(1000);
+        let mut count = 0;
+        let e = loop {
+            match rdr.read_next() {
+                Ok(Some(_)) => count += 1,
+                Ok(None) => panic!("Expected an error"),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(count, 100);
+        assert!(
+            matches!(
+                e,
+                ReaderError::InvalidRecord {
+                    offset: 8100,
+                    line: 101,
+                    ..
+                }
+            ),
+            "{:?} should be invalid record at line 101",
+            e
+        );
+    }
+
+    #[test]
+    fn should_recover_from_malformed_lines_across_chunks() {
+        let mut data = SAMPLE_FILE.to_vec();
+        data.remove(100 * 81 + 5);
+        data.remove(300 * 81 + 5);
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+
+        let options = ReaderOptions::new().recover(true);
+        let (expected, actual) = read_both(data, options);
+        assert!(actual == expected, "Records differ from Reader");
+
+        let mut rdr = ParallelReader::with_options(Bytes::from_static(data), options);
+        while rdr.read_next().expect("read").is_some() {}
+        assert_eq!(rdr.malformed_count(), 2);
+    }
+
+    fn assemble_both(
+        data: &'static [u8],
+        options: ReaderOptions,
+    ) -> (Vec<Assembled>, Vec<Assembled>) {
+        let expected = ScheduleAssembler::new(Reader::with_options(data, options))
+            .collect()
+            .expect("assemble");
+        let actual = ParallelReader::with_options(Bytes::from_static(data), options)
+            .chunk_size(1000)
+            .par_schedules()
+            .collect()
+            .expect("assemble");
+        (expected, actual)
+    }
+
+    #[test]
+    fn should_assemble_same_schedules_as_assembler() {
+        let (expected, actual) = assemble_both(SAMPLE_FILE, ReaderOptions::default());
+        assert!(
+            actual
+                .iter()
+                .filter(|item| matches!(item, Assembled::Schedule(_)))
+                .count()
+                > 1
+        );
+        assert_eq!(actual.len(), expected.len());
+        assert!(actual == expected, "Items differ from ScheduleAssembler");
+    }
+
+    #[test]
+    fn should_assemble_malformed_lines_across_chunks() {
+        let mut data = SAMPLE_FILE.to_vec();
+        data.remove(100 * 81 + 5);
+        data.remove(300 * 81 + 5);
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+
+        let options = ReaderOptions::new().recover(true);
+        let (expected, actual) = assemble_both(data, options);
+        assert!(actual == expected, "Items differ from ScheduleAssembler");
+
+        let mut rdr = ParallelReader::with_options(Bytes::from_static(data), options)
+            .chunk_size(1000)
+            .par_schedules();
+        while rdr.read_next().expect("assemble").is_some() {}
+        assert_eq!(rdr.malformed_count(), 2);
+    }
+
+    #[test]
+    fn should_report_first_assembler_error() {
+        // Lose the terminating location of a schedule part way through.
+        let lines = SAMPLE_FILE.chunks(81).collect::<Vec<_>>();
+        let lt = lines
+            .iter()
+            .enumerate()
+            .skip(200)
+            .find(|(_, line)| line.starts_with(b"LT"))
+            .map(|(i, _)| i)
+            .expect("terminating location");
+        let data = [&lines[..lt], &lines[lt + 1..]].concat().concat();
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+
+        let mut expected = ScheduleAssembler::new(Reader::new(data));
+        let mut actual = ParallelReader::new(Bytes::from_static(data))
+            .chunk_size(1000)
+            .par_schedules();
+        let e = loop {
+            match (expected.read_next(), actual.read_next()) {
+                (Ok(Some(e)), Ok(Some(a))) => assert_eq!(a, e),
+                (Err(e), Err(a)) => break (e, a),
+                other => panic!("Expected both to fail together, got {:?}", other),
+            }
+        };
+        assert_eq!(format!("{:?}", e.1), format!("{:?}", e.0));
+    }
+}
@@ -1,24 +1,55 @@
-use std::io::Read;
+use std::io::{self, Read, Seek, SeekFrom};
 
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use fallible_iterator::FallibleIterator;
 use thiserror::Error;
-use tracing::{trace, Level};
+use tracing::{debug, trace, warn, Level};
 
+use crate::errors::{snippet, Position};
+use crate::helpers::CIF_LINE_LEN;
 use crate::{
     Association, BasicSchedule, ChangeEnRoute, Header, LocationIntermediate, LocationOrigin,
-    LocationTerminating, Record, ScheduleExtra, TiplocAmend, TiplocInsert, Trailer,
+    LocationTerminating, Record, ScheduleExtra, TiplocAmend, TiplocDelete, TiplocInsert, Trailer,
 };
 
-// 80 characters plus a newline
-const CIF_LINE_LEN: usize = 81;
-
 #[derive(Error, Debug)]
 pub enum ReaderError {
-    #[error("I/O:")]
+    #[error("I/O: {0}")]
     Io(#[from] std::io::Error),
-    #[error("Invalid record at byte: {}", 0)]
-    InvalidRecord(usize),
+    #[error("Invalid record at line {line} (byte {offset}): expected a newline after 80 columns")]
+    InvalidRecord {
+        offset: usize,
+        line: usize,
+        /// The line as read, up to where the newline should have been.
+        bytes: Bytes,
+    },
+    #[error("Input is {format} compressed, but support for {format} is not enabled")]
+    UnsupportedFormat { format: &'static str },
+    #[cfg(feature = "zip")]
+    #[error("Zip: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("No CIF file found in zip archive")]
+    NoCifMember,
+    #[error("Byte {offset} is not the start of a record")]
+    NotRecordBoundary { offset: usize },
+}
+
+impl ReaderError {
+    /// Renders the offending line with a caret where the newline should
+    /// have been, or under the early newline.
+    pub fn snippet(&self) -> Option<String> {
+        match self {
+            ReaderError::InvalidRecord { bytes, .. } => {
+                Some(match bytes.iter().position(|b| *b == b'\n') {
+                    // The line ended early.
+                    Some(end) => snippet(&bytes[..end], end..end + 1),
+                    // The line runs on past where the newline should be.
+                    None => snippet(bytes, CIF_LINE_LEN - 1..CIF_LINE_LEN),
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 pub type ReaderResult<T> = std::result::Result<T, ReaderError>;
@@ -27,10 +58,104 @@ struct Filler<R> {
     inner: R,
 }
 
+/// Controls how strictly a [`Reader`] checks the layout of each line.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ReaderOptions {
+    pub(crate) lenient: bool,
+    pub(crate) recover: bool,
+}
+
+impl ReaderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts lines that aren't exactly 80 columns followed by `\n`,
+    /// repairing them rather than failing. Each repair is recorded, and can
+    /// be collected with [`Reader::take_repairs`].
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Yields lines of the wrong length as [`Record::Malformed`] and carries
+    /// on from the next newline, rather than failing. How many lines were
+    /// skipped is given by [`Reader::malformed_count`]. This has no effect
+    /// in lenient mode, which repairs such lines instead.
+    pub fn recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+}
+
+/// A change made to the input by a lenient [`Reader`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Repair {
+    /// The byte offset of the start of the line in the input.
+    pub offset: usize,
+    /// The one-based line number.
+    pub line: usize,
+    pub kind: RepairKind,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RepairKind {
+    /// A `\r` before the newline was removed.
+    CarriageReturn,
+    /// A line of `len` bytes was padded to 80 columns with spaces.
+    Padded { len: usize },
+    /// A line of `len` bytes was cut down to 80 columns.
+    Truncated { len: usize },
+    /// The last line had no newline.
+    MissingFinalNewline,
+    /// An empty line, or one of just whitespace and control characters, was
+    /// skipped.
+    Skipped { len: usize },
+}
+
+// Splits records off the front of a buffer, keeping track of where we are
+// in the input. This is shared between the blocking and async readers.
+#[derive(Debug, Clone)]
+pub(crate) struct Framer {
+    offset: usize,
+    line: usize,
+    options: ReaderOptions,
+    repairs: Vec<Repair>,
+    malformed: usize,
+    // Whether we've logged how many malformed lines were skipped.
+    summarised: bool,
+}
+
+// Something the framer can split records off the front of. Reading from a
+// `Bytes` hands out slices of it, rather than copying.
+pub(crate) trait Buffer: std::ops::Deref<Target = [u8]> {
+    fn take(&mut self, len: usize) -> Bytes;
+    fn clear(&mut self);
+}
+
+impl Buffer for BytesMut {
+    fn take(&mut self, len: usize) -> Bytes {
+        self.split_to(len).freeze()
+    }
+    fn clear(&mut self) {
+        BytesMut::clear(self)
+    }
+}
+
+impl Buffer for Bytes {
+    fn take(&mut self, len: usize) -> Bytes {
+        self.split_to(len)
+    }
+    fn clear(&mut self) {
+        Bytes::clear(self)
+    }
+}
+
 pub struct Reader<R> {
     src: Filler<R>,
     buf: BytesMut,
-    offset: usize,
+    eof: bool,
+    framer: Framer,
 }
 
 impl<R: Read> Filler<R> {
@@ -50,12 +175,229 @@ impl<R: Read> Filler<R> {
     }
 }
 
+impl Framer {
+    pub(crate) fn new(options: ReaderOptions) -> Self {
+        Self::starting_at(options, Position { offset: 0, line: 1 })
+    }
+
+    // For when we're picking up partway through the input.
+    pub(crate) fn starting_at(options: ReaderOptions, position: Position) -> Self {
+        Framer {
+            offset: position.offset,
+            line: position.line,
+            options,
+            repairs: Vec::new(),
+            malformed: 0,
+            summarised: false,
+        }
+    }
+
+    // For reading part of the input, where whoever's reading the whole of
+    // it will log the summary.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn without_summary(mut self) -> Self {
+        self.summarised = true;
+        self
+    }
+
+    /// Takes the next record from the front of `buf`, or returns `None` if
+    /// it needs more input. Once `eof` is set, whatever is left in `buf` is
+    /// treated as the last line.
+    pub(crate) fn decode<B: Buffer>(
+        &mut self,
+        buf: &mut B,
+        eof: bool,
+    ) -> ReaderResult<Option<Record>> {
+        if self.options.lenient {
+            return self.decode_lenient(buf, eof);
+        }
+
+        if buf.len() < CIF_LINE_LEN {
+            if !eof {
+                trace!("Need more");
+                return Ok(None);
+            }
+            if self.options.recover && !buf.is_empty() {
+                return self.decode_malformed(buf, eof);
+            }
+            if !buf.is_empty() {
+                debug!(len = buf.len(), "Ignoring partial line at end of input");
+                buf.clear();
+            }
+            if self.malformed > 0 && !self.summarised {
+                warn!("Skipped {} malformed lines", self.malformed);
+                self.summarised = true;
+            }
+            return Ok(None);
+        }
+
+        if buf[80] != b'\n' {
+            if self.options.recover {
+                return self.decode_malformed(buf, eof);
+            }
+            return Err(ReaderError::InvalidRecord {
+                offset: self.offset,
+                line: self.line,
+                bytes: Bytes::copy_from_slice(&buf[..CIF_LINE_LEN]),
+            });
+        }
+
+        let record = buf.take(CIF_LINE_LEN);
+        self.offset += CIF_LINE_LEN;
+        self.line += 1;
+
+        Ok(Some(record_of(record)))
+    }
+
+    // Skips over the rest of a line that isn't a valid record, so that we can
+    // pick up again at the start of the next one.
+    fn decode_malformed<B: Buffer>(
+        &mut self,
+        buf: &mut B,
+        eof: bool,
+    ) -> ReaderResult<Option<Record>> {
+        let (len, consumed) = match buf.iter().position(|b| *b == b'\n') {
+            Some(pos) => (pos, pos + 1),
+            None if eof => (buf.len(), buf.len()),
+            None => return Ok(None),
+        };
+
+        let reason = if len == consumed {
+            "missing newline at end of input".to_owned()
+        } else {
+            format!("line is {} bytes long, expected {}", len, CIF_LINE_LEN - 1)
+        };
+        warn!(
+            offset = self.offset,
+            line = self.line,
+            %reason,
+            "Skipping malformed line"
+        );
+        let bytes = buf.take(consumed).slice(..len);
+        let record = Record::Malformed {
+            offset: self.offset,
+            line_no: self.line,
+            bytes,
+            reason,
+        };
+        self.offset += consumed;
+        self.line += 1;
+        self.malformed += 1;
+        Ok(Some(record))
+    }
+
+    // Reads up to the next newline, whatever the length of the line, and
+    // then makes it look like a well-formed 80 column record.
+    fn decode_lenient<B: Buffer>(
+        &mut self,
+        buf: &mut B,
+        eof: bool,
+    ) -> ReaderResult<Option<Record>> {
+        loop {
+            let (len, consumed, newline) = match buf.iter().position(|b| *b == b'\n') {
+                Some(pos) => (pos, pos + 1, true),
+                None if eof && !buf.is_empty() => (buf.len(), buf.len(), false),
+                None => return Ok(None),
+            };
+
+            let line = buf.take(consumed);
+            let mut content = &line[..len];
+            let mut repairs = Vec::new();
+            if !newline {
+                repairs.push(RepairKind::MissingFinalNewline);
+            }
+            if content.last() == Some(&b'\r') {
+                content = &content[..content.len() - 1];
+                repairs.push(RepairKind::CarriageReturn);
+            }
+
+            let blank = content
+                .iter()
+                .all(|b| b.is_ascii_whitespace() || b.is_ascii_control());
+            let record = if blank {
+                repairs.push(RepairKind::Skipped { len });
+                None
+            } else {
+                let mut record = BytesMut::with_capacity(CIF_LINE_LEN);
+                let width = CIF_LINE_LEN - 1;
+                if content.len() < width {
+                    repairs.push(RepairKind::Padded { len: content.len() });
+                } else if content.len() > width {
+                    repairs.push(RepairKind::Truncated { len: content.len() });
+                }
+                record.extend_from_slice(&content[..content.len().min(width)]);
+                record.resize(width, b' ');
+                record.extend_from_slice(b"\n");
+                Some(record.freeze())
+            };
+
+            for kind in repairs {
+                debug!(
+                    offset = self.offset,
+                    line = self.line,
+                    ?kind,
+                    "Repaired line"
+                );
+                self.repairs.push(Repair {
+                    offset: self.offset,
+                    line: self.line,
+                    kind,
+                });
+            }
+            self.offset += consumed;
+            self.line += 1;
+
+            if let Some(record) = record {
+                return Ok(Some(record_of(record)));
+            }
+        }
+    }
+
+    pub(crate) fn move_to(&mut self, position: Position) {
+        self.offset = position.offset;
+        self.line = position.line;
+    }
+
+    pub(crate) fn position(&self) -> Position {
+        Position {
+            offset: self.offset,
+            line: self.line,
+        }
+    }
+
+    pub(crate) fn malformed_count(&self) -> usize {
+        self.malformed
+    }
+
+    pub(crate) fn take_repairs(&mut self) -> Vec<Repair> {
+        std::mem::take(&mut self.repairs)
+    }
+}
+
 impl<R: Read> Reader<R> {
     pub fn new(rdr: R) -> Self {
-        let src = Filler::new(rdr);
-        let buf = BytesMut::new();
-        let offset = 0;
-        Self { src, buf, offset }
+        Self::with_options(rdr, ReaderOptions::default())
+    }
+
+    pub fn with_options(rdr: R, options: ReaderOptions) -> Self {
+        Self {
+            src: Filler::new(rdr),
+            buf: BytesMut::new(),
+            eof: false,
+            framer: Framer::new(options),
+        }
+    }
+
+    // For reading from partway through the input, where `rdr` starts at
+    // `position`, and the summary is left to the caller.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn starting_at(rdr: R, options: ReaderOptions, position: Position) -> Self {
+        Self {
+            src: Filler::new(rdr),
+            buf: BytesMut::new(),
+            eof: false,
+            framer: Framer::starting_at(options, position).without_summary(),
+        }
     }
 
     pub fn read_next(&mut self) -> ReaderResult<Option<Record>> {
@@ -74,38 +416,36 @@ impl<R: Read> Reader<R> {
                 }
             }
 
-            if self.buf.len() < CIF_LINE_LEN {
-                trace!("Need more");
-                if !self.src.refill_until_eof(&mut self.buf)? {
-                    return Ok(None);
-                } else {
-                    continue;
-                }
+            if let Some(record) = self.framer.decode(&mut self.buf, self.eof)? {
+                return Ok(Some(record));
             }
-
-            if self.buf[80] != b'\n' {
-                return Err(ReaderError::InvalidRecord(self.offset));
+            if self.eof {
+                return Ok(None);
             }
+            self.eof = !self.src.refill_until_eof(&mut self.buf)?;
+        }
+    }
 
-            let record = self.buf.split_to(CIF_LINE_LEN).freeze();
-            let val = match &record[0..2] {
-                b"HD" => Record::Header(Header::from_record(record)),
-                b"TI" => Record::TiplocInsert(TiplocInsert::from_record(record)),
-                b"TA" => Record::TiplocAmend(TiplocAmend::from_record(record)),
-                b"AA" => Record::Association(Association::from_record(record)),
-                b"BS" => Record::Schedule(BasicSchedule::from_record(record)),
-                b"BX" => Record::ScheduleExtra(ScheduleExtra::from_record(record)),
-                b"LO" => Record::LocationOrigin(LocationOrigin::from_record(record)),
-                b"LI" => Record::LocationIntermediate(LocationIntermediate::from_record(record)),
-                b"LT" => Record::LocationTerminating(LocationTerminating::from_record(record)),
-                b"CR" => Record::ChangeEnRoute(ChangeEnRoute::from_record(record)),
-                b"ZZ" => Record::Trailer(Trailer::from_record(record)),
-                _ => Record::Unrecognised(record),
-            };
-            self.offset += CIF_LINE_LEN;
+    /// Where the next record will start in the input.
+    pub fn position(&self) -> Position {
+        self.framer.position()
+    }
 
-            return Ok(Some(val));
-        }
+    /// The byte offset of the next record in the input. Passing this to
+    /// [`Reader::resume_at`] later carries on from the same place.
+    pub fn offset(&self) -> usize {
+        self.framer.position().offset
+    }
+
+    /// The number of malformed lines skipped so far in recovery mode.
+    pub fn malformed_count(&self) -> usize {
+        self.framer.malformed_count()
+    }
+
+    /// Removes and returns the repairs made since the last call. Callers
+    /// reading large files leniently should drain these as they go.
+    pub fn take_repairs(&mut self) -> Vec<Repair> {
+        self.framer.take_repairs()
     }
 
     pub fn get_ref(&self) -> &R {
@@ -113,6 +453,65 @@ impl<R: Read> Reader<R> {
     }
 }
 
+impl<R: Read + Seek> Reader<R> {
+    /// Moves to `offset` in the input, such as one from [`Reader::offset`]
+    /// or [`Schedule::offset`](crate::Schedule::offset), so that the next
+    /// record read starts there. Fails if `offset` isn't at the start of a
+    /// line, or in strict mode, isn't a whole number of records in.
+    ///
+    /// Line numbers after this assume every earlier line was a full record,
+    /// which will only be true when reading strictly.
+    pub fn resume_at(&mut self, offset: usize) -> ReaderResult<()> {
+        let not_boundary = ReaderError::NotRecordBoundary { offset };
+        let strict = !self.framer.options.lenient && !self.framer.options.recover;
+        if strict && !offset.is_multiple_of(CIF_LINE_LEN) {
+            return Err(not_boundary);
+        }
+
+        let src = &mut self.src.inner;
+        if offset > 0 {
+            // The line before should have ended just before `offset`.
+            src.seek(SeekFrom::Start(offset as u64 - 1))?;
+            let mut prev = [0u8];
+            match src.read_exact(&mut prev) {
+                Ok(()) if prev[0] == b'\n' => {}
+                Ok(()) => return Err(not_boundary),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(not_boundary),
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            src.seek(SeekFrom::Start(0))?;
+        }
+
+        debug!(offset, "Resuming");
+        self.buf.clear();
+        self.eof = false;
+        self.framer.move_to(Position {
+            offset,
+            line: offset / CIF_LINE_LEN + 1,
+        });
+        Ok(())
+    }
+}
+
+fn record_of(record: Bytes) -> Record {
+    match &record[0..2] {
+        b"HD" => Record::Header(Header::from_record(record)),
+        b"TI" => Record::TiplocInsert(TiplocInsert::from_record(record)),
+        b"TA" => Record::TiplocAmend(TiplocAmend::from_record(record)),
+        b"TD" => Record::TiplocDelete(TiplocDelete::from_record(record)),
+        b"AA" => Record::Association(Association::from_record(record)),
+        b"BS" => Record::Schedule(BasicSchedule::from_record(record)),
+        b"BX" => Record::ScheduleExtra(ScheduleExtra::from_record(record)),
+        b"LO" => Record::LocationOrigin(LocationOrigin::from_record(record)),
+        b"LI" => Record::LocationIntermediate(LocationIntermediate::from_record(record)),
+        b"LT" => Record::LocationTerminating(LocationTerminating::from_record(record)),
+        b"CR" => Record::ChangeEnRoute(ChangeEnRoute::from_record(record)),
+        b"ZZ" => Record::Trailer(Trailer::from_record(record)),
+        _ => Record::Unrecognised(record),
+    }
+}
+
 impl<R: Read> FallibleIterator for Reader<R> {
     type Item = Record;
 
@@ -140,7 +539,7 @@ mod test {
         let res = r.read_next();
         let e = res.unwrap_err();
         assert!(
-            matches!(e, ReaderError::InvalidRecord(0)),
+            matches!(e, ReaderError::InvalidRecord { offset: 0, .. }),
             "{:?} should be invalid record at 0",
             e
         );
@@ -157,7 +556,7 @@ mod test {
         let res = r.read_next();
         let e = res.unwrap_err();
         assert!(
-            matches!(e, ReaderError::InvalidRecord(0)),
+            matches!(e, ReaderError::InvalidRecord { offset: 0, .. }),
             "{:?} should be invalid record at 0",
             e
         );
@@ -177,7 +576,7 @@ mod test {
         let res = r.read_next();
         let e = res.unwrap_err();
         assert!(
-            matches!(e, ReaderError::InvalidRecord(81)),
+            matches!(e, ReaderError::InvalidRecord { offset: 81, .. }),
             "{:?} should be invalid record at 81",
             e
         );
@@ -203,9 +602,222 @@ mod test {
         let res = r.read_next();
         let e = res.unwrap_err();
         assert!(
-            matches!(e, ReaderError::InvalidRecord(243)),
+            matches!(e, ReaderError::InvalidRecord { offset: 243, .. }),
             "{:?} should be invalid record at 243",
             e
         );
     }
+
+    fn lenient(data: &[u8]) -> Reader<&[u8]> {
+        Reader::with_options(data, ReaderOptions::new().lenient(true))
+    }
+
+    #[test]
+    fn lenient_reader_should_accept_crlf() {
+        let it: &[u8] = b"\
+ZZ                                                                              \r\n\
+ZZ                                                                              \r\n";
+        let mut r = lenient(it);
+        let records = r.by_ref().collect::<Vec<_>>().expect("read");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].buf().len(), CIF_LINE_LEN);
+        assert_eq!(records[1].buf()[80], b'\n');
+        assert_eq!(
+            r.take_repairs(),
+            vec![
+                Repair {
+                    offset: 0,
+                    line: 1,
+                    kind: RepairKind::CarriageReturn
+                },
+                Repair {
+                    offset: 82,
+                    line: 2,
+                    kind: RepairKind::CarriageReturn
+                },
+            ]
+        );
+        assert!(r.take_repairs().is_empty());
+    }
+
+    #[test]
+    fn lenient_reader_should_pad_and_truncate_lines() {
+        let it: &[u8] = b"\
+TDAACHEN\n\
+ZZ                                                                              garbage\n\
+ZZ";
+        let mut r = lenient(it);
+        let records = r.by_ref().collect::<Vec<_>>().expect("read");
+        assert_eq!(records.len(), 3);
+        match &records[0] {
+            Record::TiplocDelete(td) => assert_eq!(td.tiploc().unwrap().as_ref(), "AACHEN"),
+            other => panic!("Expected TIPLOC delete, got {:?}", other),
+        }
+        for record in &records {
+            assert_eq!(record.buf().len(), CIF_LINE_LEN);
+        }
+        let kinds = r
+            .take_repairs()
+            .into_iter()
+            .map(|r| (r.line, r.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (1, RepairKind::Padded { len: 8 }),
+                (2, RepairKind::Truncated { len: 87 }),
+                (3, RepairKind::MissingFinalNewline),
+                (3, RepairKind::Padded { len: 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn lenient_reader_should_skip_blank_lines() {
+        let it: &[u8] = b"\
+ZZ                                                                              \n\
+\r\n\
+\x1a";
+        let mut r = lenient(it);
+        let records = r.by_ref().collect::<Vec<_>>().expect("read");
+        assert_eq!(records.len(), 1);
+        let repairs = r.take_repairs();
+        assert_eq!(repairs.len(), 4);
+        assert_eq!(repairs[1].offset, 81);
+        assert_eq!(repairs[1].kind, RepairKind::Skipped { len: 1 });
+        assert_eq!(repairs[3].offset, 83);
+        assert_eq!(repairs[3].kind, RepairKind::Skipped { len: 1 });
+    }
+
+    #[test]
+    fn strict_reader_should_still_reject_crlf() {
+        let it: &[u8] =
+            b"ZZ                                                                              \r\n";
+        let e = Reader::new(it).read_next().unwrap_err();
+        assert!(
+            matches!(e, ReaderError::InvalidRecord { offset: 0, .. }),
+            "{:?}",
+            e
+        );
+    }
+
+    fn recovering(data: &[u8]) -> Reader<&[u8]> {
+        Reader::with_options(data, ReaderOptions::new().recover(true))
+    }
+
+    #[test]
+    fn recovering_reader_should_skip_malformed_lines() {
+        let it: &[u8] = b"\
+ZZ                                                                              \n\
+ZZ                                                                             \n\
+ZZ                                                                              \n";
+        let mut r = recovering(it);
+        let records = r.by_ref().collect::<Vec<_>>().expect("read");
+        assert_eq!(records.len(), 3);
+        assert!(matches!(records[0], Record::Trailer(_)));
+        match &records[1] {
+            Record::Malformed {
+                offset,
+                line_no,
+                bytes,
+                reason,
+            } => {
+                assert_eq!(*offset, 81);
+                assert_eq!(*line_no, 2);
+                assert_eq!(bytes.len(), 79);
+                assert_eq!(reason, "line is 79 bytes long, expected 80");
+            }
+            other => panic!("Expected malformed line, got {:?}", other),
+        }
+        assert!(matches!(records[2], Record::Trailer(_)));
+        assert_eq!(r.malformed_count(), 1);
+    }
+
+    #[test]
+    fn recovering_reader_should_report_truncated_final_line() {
+        let it: &[u8] = b"\
+ZZ                                                                              \n\
+ZZ        ";
+        let mut r = recovering(it);
+        let records = r.by_ref().collect::<Vec<_>>().expect("read");
+        assert_eq!(records.len(), 2);
+        assert!(
+            matches!(
+                &records[1],
+                Record::Malformed { offset: 81, line_no: 2, reason, .. }
+                    if reason == "missing newline at end of input"
+            ),
+            "{:?}",
+            records[1]
+        );
+        assert_eq!(r.malformed_count(), 1);
+    }
+
+    #[test]
+    fn invalid_record_should_describe_position() {
+        let it: &[u8] = b"\
+ZZ                                                                              \n\
+ZZ  \n\
+ZZ                                                                              \n";
+        let mut r = Reader::new(it);
+        let _ = r.read_next().unwrap();
+        let e = r.read_next().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Invalid record at line 2 (byte 81): expected a newline after 80 columns"
+        );
+        assert_eq!(e.snippet().unwrap(), "ZZ  \n    ^");
+    }
+
+    #[test]
+    fn invalid_record_snippet_should_point_past_long_line() {
+        let it: &[u8] =
+            b"ZZ                                                                              X\n";
+        let e = Reader::new(it).read_next().unwrap_err();
+        let snippet = e.snippet().unwrap();
+        let (line, carets) = snippet.split_once('\n').unwrap();
+        assert_eq!(line.len(), 81);
+        assert_eq!(carets, format!("{}^", " ".repeat(80)));
+    }
+
+    #[test]
+    fn should_resume_at_record_boundary() {
+        let it: &[u8] = include_bytes!("../tests/sample.cif");
+        let all: Vec<Record> = Reader::new(it).collect().unwrap();
+
+        let mut r = Reader::new(io::Cursor::new(it));
+        for _ in 0..3 {
+            r.read_next().unwrap();
+        }
+        let offset = r.offset();
+        assert_eq!(offset, 3 * 81);
+
+        let mut r = Reader::new(io::Cursor::new(it));
+        r.resume_at(offset).unwrap();
+        assert_eq!(r.position(), Position { offset, line: 4 });
+        let rest: Vec<Record> = r.collect().unwrap();
+        assert_eq!(rest, all[3..]);
+    }
+
+    #[test]
+    fn should_refuse_to_resume_mid_record() {
+        let it: &[u8] = include_bytes!("../tests/sample.cif");
+        let mut r = Reader::new(io::Cursor::new(it));
+        let e = r.resume_at(100).unwrap_err();
+        assert!(
+            matches!(e, ReaderError::NotRecordBoundary { offset: 100 }),
+            "{:?} should not be a record boundary",
+            e
+        );
+
+        // Lenient reading can't rely on the line length, so has to look.
+        let mut r = Reader::with_options(io::Cursor::new(it), ReaderOptions::new().lenient(true));
+        assert!(r.resume_at(100).is_err());
+        assert!(r.resume_at(it.len() + 81).is_err());
+        r.resume_at(81).unwrap();
+        assert!(matches!(
+            r.read_next().unwrap(),
+            Some(Record::TiplocInsert(_))
+        ));
+    }
 }
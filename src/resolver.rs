@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::{CIFParseError, Schedule, Stp};
+
+/// The plan that applies to a train on a given date.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Effective<'a> {
+    Runs(&'a Schedule),
+    Cancelled(&'a Schedule),
+}
+
+impl<'a> Effective<'a> {
+    pub fn schedule(&self) -> &'a Schedule {
+        match self {
+            Effective::Runs(schedule) => schedule,
+            Effective::Cancelled(schedule) => schedule,
+        }
+    }
+}
+
+// Short term plans take precedence over permanent ones, and a cancellation
+// overrides everything else.
+pub(crate) fn precedence(stp: Stp) -> u8 {
+    match stp {
+        Stp::Permanent => 0,
+        Stp::New => 1,
+        Stp::Overlay => 2,
+        Stp::Cancellation => 3,
+    }
+}
+
+/// Resolves, for each train UID, which of `schedules` applies on `date`.
+/// Trains that don't run at all on that date are omitted.
+pub fn resolve_on_date<'a, I>(
+    schedules: I,
+    date: NaiveDate,
+) -> Result<BTreeMap<&'a str, Effective<'a>>, CIFParseError>
+where
+    I: IntoIterator<Item = &'a Schedule>,
+{
+    let mut best = BTreeMap::<&'a str, (Stp, &'a Schedule)>::new();
+    for schedule in schedules {
+        let bs = schedule.schedule();
+        if !bs.runs_on(date)? {
+            continue;
+        }
+        let stp = bs.stp()?;
+        let entry = best.entry(bs.uid()?).or_insert((stp, schedule));
+        if precedence(stp) > precedence(entry.0) {
+            *entry = (stp, schedule);
+        }
+    }
+
+    Ok(best
+        .into_iter()
+        .map(|(uid, (stp, schedule))| {
+            let effective = if stp == Stp::Cancellation {
+                Effective::Cancelled(schedule)
+            } else {
+                Effective::Runs(schedule)
+            };
+            (uid, effective)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::BasicSchedule;
+
+    fn schedule(record: &'static [u8]) -> Schedule {
+        assert_eq!(80, record.len());
+        Schedule::new(0, BasicSchedule::from_record(Bytes::from(record)))
+    }
+
+    fn examples() -> Vec<Schedule> {
+        vec![
+            schedule(
+                b"BSNC670061905191912080000001 POO2N75    113575825 DMUE   090      S            P",
+            ),
+            schedule(
+                b"BSNC670061905191907280000001            1                                      C",
+            ),
+            schedule(
+                b"BSNG828851505181512111111100 POO2N75    113575825 DMUE   090      S            P",
+            ),
+            schedule(
+                b"BSRG828851510191510231100100 POO2N75    113575825 DMUE   090      S            O",
+            ),
+        ]
+    }
+
+    #[test]
+    fn cancellation_should_override_permanent() {
+        let schedules = examples();
+        let date = NaiveDate::from_ymd_opt(2019, 6, 2).unwrap();
+        let resolved = resolve_on_date(&schedules, date).unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved["C67006"], Effective::Cancelled(&schedules[1]));
+    }
+
+    #[test]
+    fn permanent_should_apply_after_cancellation_ends() {
+        let schedules = examples();
+        let date = NaiveDate::from_ymd_opt(2019, 8, 4).unwrap();
+        let resolved = resolve_on_date(&schedules, date).unwrap();
+        assert_eq!(resolved["C67006"], Effective::Runs(&schedules[0]));
+    }
+
+    #[test]
+    fn overlay_should_override_permanent() {
+        let schedules = examples();
+        let date = NaiveDate::from_ymd_opt(2015, 10, 19).unwrap();
+        let resolved = resolve_on_date(&schedules, date).unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved["G82885"], Effective::Runs(&schedules[3]));
+    }
+
+    #[test]
+    fn permanent_should_apply_on_days_overlay_does_not_run() {
+        let schedules = examples();
+        // A Wednesday, which the overlay doesn't cover.
+        let date = NaiveDate::from_ymd_opt(2015, 10, 21).unwrap();
+        let resolved = resolve_on_date(&schedules, date).unwrap();
+        assert_eq!(resolved["G82885"], Effective::Runs(&schedules[2]));
+    }
+
+    #[test]
+    fn should_omit_trains_not_running() {
+        let schedules = examples();
+        // A Saturday
+        let date = NaiveDate::from_ymd_opt(2015, 10, 24).unwrap();
+        let resolved = resolve_on_date(&schedules, date).unwrap();
+        assert!(resolved.is_empty());
+    }
+}
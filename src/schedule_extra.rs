@@ -3,8 +3,8 @@ use std::fmt;
 use bytes::Bytes;
 
 use crate::{
-    errors::CIFParseError,
-    helpers::{string_of_slice, string_of_slice_opt},
+    errors::{BuildError, CIFParseError},
+    helpers::{parse_field, string_of_slice, string_of_slice_opt, RecordBuf},
 };
 
 #[derive(Clone, Eq, PartialEq)]
@@ -20,31 +20,93 @@ impl ScheduleExtra {
         &self.record
     }
 
+    pub fn traction_class(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "traction_class", 2..6, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
     pub fn uic_code(&self) -> Result<Option<&str>, CIFParseError> {
-        Ok(string_of_slice_opt(&self.record[6..11])?)
+        parse_field(&self.record, "uic_code", 6..11, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn atoc_code(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[11..13])?)
+        parse_field(&self.record, "atoc_code", 11..13, |s| {
+            Ok(string_of_slice(s)?)
+        })
     }
     pub fn applicable_timetable_code(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[13..14])?)
+        parse_field(&self.record, "applicable_timetable_code", 13..14, |s| {
+            Ok(string_of_slice(s)?)
+        })
+    }
+    pub fn retail_service_id(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "retail_service_id", 14..22, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
+    }
+    pub fn data_source(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "data_source", 22..23, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
 }
 
 impl fmt::Debug for ScheduleExtra {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s = f.debug_struct("ScheduleExtra");
+        s.field("traction_class", &self.traction_class());
         s.field("uic_code", &self.uic_code());
         s.field("atoc_code", &self.atoc_code());
         s.field(
             "applicable_timetable_code",
             &self.applicable_timetable_code(),
         );
-
+        s.field("retail_service_id", &self.retail_service_id());
+        s.field("data_source", &self.data_source());
         s.finish()
     }
 }
 
+pub struct ScheduleExtraBuilder {
+    record: RecordBuf,
+}
+
+impl ScheduleExtraBuilder {
+    pub fn new(atoc_code: &str, applicable_timetable_code: &str) -> Self {
+        let mut record = RecordBuf::new(b"BX");
+        record.put("atoc_code", 11..13, atoc_code);
+        record.put(
+            "applicable_timetable_code",
+            13..14,
+            applicable_timetable_code,
+        );
+        ScheduleExtraBuilder { record }
+    }
+
+    pub fn traction_class(mut self, traction_class: &str) -> Self {
+        self.record.put("traction_class", 2..6, traction_class);
+        self
+    }
+    pub fn uic_code(mut self, uic_code: &str) -> Self {
+        self.record.put("uic_code", 6..11, uic_code);
+        self
+    }
+    pub fn retail_service_id(mut self, retail_service_id: &str) -> Self {
+        self.record
+            .put("retail_service_id", 14..22, retail_service_id);
+        self
+    }
+    pub fn data_source(mut self, data_source: &str) -> Self {
+        self.record.put("data_source", 22..23, data_source);
+        self
+    }
+
+    pub fn build(self) -> Result<ScheduleExtra, BuildError> {
+        Ok(ScheduleExtra::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
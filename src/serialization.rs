@@ -0,0 +1,759 @@
+//! Serde support for the record types. Each record is (de)serialized as an
+//! object of its decoded fields, and rebuilt into a CIF record with the
+//! matching builder on the way back in.
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    Activities, Allowance, Association, AssociationBuilder, AssociationCategory, AssociationType,
+    BankHolidayRunning, BasicSchedule, BasicScheduleBuilder, Branding, BuildError, CIFParseError,
+    CateringCode, ChangeEnRoute, ChangeEnRouteBuilder, DateIndicator, Days, FullOrUpdate, Header,
+    HeaderBuilder, LocationIntermediate, LocationIntermediateBuilder, LocationOrigin,
+    LocationOriginBuilder, LocationTerminating, LocationTerminatingBuilder,
+    OperatingCharacteristics, PowerType, ReservationPolicy, ScheduleExtra, ScheduleExtraBuilder,
+    SeatingClass, SleeperClass, Speed, Stp, Tiploc, TiplocAmend, TiplocAmendBuilder, TiplocDelete,
+    TiplocDeleteBuilder, TiplocInsert, TiplocInsertBuilder, Trailer, TrailerBuilder, TrainCategory,
+    TrainStatus, TransactionType,
+};
+
+// Implements serde for `$record` by way of the `$fields` struct, which
+// provides `of` to decode a record and `build` to encode one.
+macro_rules! serde_via {
+    ($record:ident, $fields:ident) => {
+        impl Serialize for $record {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                $fields::of(self)
+                    .map_err(ser::Error::custom)?
+                    .serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $record {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                $fields::deserialize(deserializer)?
+                    .build()
+                    .map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+// Applies `set` to the builder only when the field is present.
+pub(crate) fn opt<B, T>(builder: B, val: Option<T>, set: impl FnOnce(B, T) -> B) -> B {
+    match val {
+        Some(val) => set(builder, val),
+        None => builder,
+    }
+}
+
+pub(crate) fn owned(s: Option<&str>) -> Option<String> {
+    s.map(str::to_owned)
+}
+
+fn non_empty(days: Days) -> Option<Days> {
+    Some(days).filter(|days| !days.is_empty())
+}
+
+#[derive(Serialize, Deserialize)]
+struct HeaderFields {
+    file_mainframe_identity: String,
+    extracted_at: NaiveDateTime,
+    current_file: String,
+    last_file: Option<String>,
+    update: FullOrUpdate,
+    version: String,
+    user_start_date: NaiveDate,
+    user_end_date: NaiveDate,
+}
+
+impl HeaderFields {
+    fn of(r: &Header) -> Result<Self, CIFParseError> {
+        Ok(HeaderFields {
+            file_mainframe_identity: r.file_mainframe_identity()?.to_owned(),
+            extracted_at: r.extracted_at()?,
+            current_file: r.current_file()?.to_owned(),
+            last_file: owned(r.last_file()?),
+            update: r.update()?,
+            version: r.version()?.to_owned(),
+            user_start_date: r.user_start_date()?,
+            user_end_date: r.user_end_date()?,
+        })
+    }
+
+    fn build(self) -> Result<Header, BuildError> {
+        let b = HeaderBuilder::new(
+            &self.file_mainframe_identity,
+            self.extracted_at,
+            &self.current_file,
+            self.update,
+            self.user_start_date,
+            self.user_end_date,
+        )
+        .version(&self.version);
+        opt(b, self.last_file.as_deref(), HeaderBuilder::last_file).build()
+    }
+}
+
+serde_via!(Header, HeaderFields);
+
+#[derive(Serialize, Deserialize)]
+struct TiplocFields {
+    tiploc: Tiploc<'static>,
+    capitals: Option<String>,
+    nlc: String,
+    nlc_check: String,
+    tps_description: String,
+    stanox: String,
+    po_mcp_code: Option<String>,
+    crs: Option<String>,
+    nlc_desc: Option<String>,
+}
+
+// TIPLOC inserts and amendments share all of these fields, so this
+// implements `$of` to decode them from `$record`, and `$start` to begin a
+// `$builder` with them.
+macro_rules! tiploc_fields {
+    ($record:ident, $builder:ident, $of:ident, $start:ident) => {
+        impl TiplocFields {
+            fn $of(r: &$record) -> Result<Self, CIFParseError> {
+                Ok(TiplocFields {
+                    tiploc: r.tiploc()?.into_owned(),
+                    capitals: owned(r.capitals()?),
+                    nlc: r.nlc()?.to_owned(),
+                    nlc_check: r.nlc_check()?.to_owned(),
+                    tps_description: r.tps_description()?.to_owned(),
+                    stanox: r.stanox()?.to_owned(),
+                    po_mcp_code: owned(r.po_mcp_code()?),
+                    crs: owned(r.crs()?),
+                    nlc_desc: owned(r.nlc_desc()?),
+                })
+            }
+
+            fn $start(self) -> $builder {
+                let b = $builder::new(
+                    &self.tiploc,
+                    &self.nlc,
+                    &self.nlc_check,
+                    &self.tps_description,
+                    &self.stanox,
+                );
+                let b = opt(b, self.capitals.as_deref(), $builder::capitals);
+                let b = opt(b, self.po_mcp_code.as_deref(), $builder::po_mcp_code);
+                let b = opt(b, self.crs.as_deref(), $builder::crs);
+                opt(b, self.nlc_desc.as_deref(), $builder::nlc_desc)
+            }
+        }
+    };
+}
+
+tiploc_fields!(TiplocInsert, TiplocInsertBuilder, of, insert_builder);
+tiploc_fields!(TiplocAmend, TiplocAmendBuilder, of_amend, amend_builder);
+
+impl TiplocFields {
+    fn build(self) -> Result<TiplocInsert, BuildError> {
+        self.insert_builder().build()
+    }
+}
+
+serde_via!(TiplocInsert, TiplocFields);
+
+#[derive(Serialize, Deserialize)]
+struct TiplocAmendFields {
+    #[serde(flatten)]
+    tiploc: TiplocFields,
+    new_tiploc: Option<Tiploc<'static>>,
+}
+
+impl TiplocAmendFields {
+    fn of(r: &TiplocAmend) -> Result<Self, CIFParseError> {
+        Ok(TiplocAmendFields {
+            tiploc: TiplocFields::of_amend(r)?,
+            new_tiploc: r.new_tiploc()?.map(Tiploc::into_owned),
+        })
+    }
+
+    fn build(self) -> Result<TiplocAmend, BuildError> {
+        let b = self.tiploc.amend_builder();
+        let b = opt(b, self.new_tiploc.as_ref(), TiplocAmendBuilder::new_tiploc);
+        b.build()
+    }
+}
+
+serde_via!(TiplocAmend, TiplocAmendFields);
+
+#[derive(Serialize, Deserialize)]
+struct TiplocDeleteFields {
+    tiploc: Tiploc<'static>,
+}
+
+impl TiplocDeleteFields {
+    fn of(r: &TiplocDelete) -> Result<Self, CIFParseError> {
+        Ok(TiplocDeleteFields {
+            tiploc: r.tiploc()?.into_owned(),
+        })
+    }
+
+    fn build(self) -> Result<TiplocDelete, BuildError> {
+        TiplocDeleteBuilder::new(&self.tiploc).build()
+    }
+}
+
+serde_via!(TiplocDelete, TiplocDeleteFields);
+
+#[derive(Serialize, Deserialize)]
+struct AssociationFields {
+    transaction_type: TransactionType,
+    main_uid: String,
+    associated_uid: String,
+    start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+    days: Days,
+    category: Option<AssociationCategory>,
+    date_indicator: Option<DateIndicator>,
+    location: Tiploc<'static>,
+    base_location_suffix: Option<String>,
+    assoc_location_suffix: Option<String>,
+    diagram_type: Option<String>,
+    association_type: Option<AssociationType>,
+    stp: Stp,
+}
+
+impl AssociationFields {
+    fn of(r: &Association) -> Result<Self, CIFParseError> {
+        Ok(AssociationFields {
+            transaction_type: r.transaction_type()?,
+            main_uid: r.main_uid()?.to_owned(),
+            associated_uid: r.associated_uid()?.to_owned(),
+            start_date: r.start_date()?,
+            end_date: r.end_date()?,
+            days: r.days()?,
+            category: r.category()?,
+            date_indicator: r.date_indicator()?,
+            location: r.location()?.into_owned(),
+            base_location_suffix: owned(r.base_location_suffix()?),
+            assoc_location_suffix: owned(r.assoc_location_suffix()?),
+            diagram_type: owned(r.diagram_type()?),
+            association_type: r.association_type()?,
+            stp: r.stp()?,
+        })
+    }
+
+    fn build(self) -> Result<Association, BuildError> {
+        let b = AssociationBuilder::new(
+            self.transaction_type,
+            &self.main_uid,
+            &self.associated_uid,
+            self.start_date,
+            &self.location,
+            self.stp,
+        )
+        .diagram_type(self.diagram_type.as_deref().unwrap_or(""));
+        let b = opt(b, self.end_date, AssociationBuilder::end_date);
+        let b = opt(b, non_empty(self.days), AssociationBuilder::days);
+        let b = opt(b, self.category, AssociationBuilder::category);
+        let b = opt(b, self.date_indicator, AssociationBuilder::date_indicator);
+        let b = opt(
+            b,
+            self.base_location_suffix.as_deref(),
+            AssociationBuilder::base_location_suffix,
+        );
+        let b = opt(
+            b,
+            self.assoc_location_suffix.as_deref(),
+            AssociationBuilder::assoc_location_suffix,
+        );
+        let b = opt(
+            b,
+            self.association_type,
+            AssociationBuilder::association_type,
+        );
+        b.build()
+    }
+}
+
+serde_via!(Association, AssociationFields);
+
+#[derive(Serialize, Deserialize)]
+struct BasicScheduleFields {
+    transaction_type: TransactionType,
+    uid: String,
+    start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+    days: Days,
+    bank_holiday: Option<BankHolidayRunning>,
+    status: Option<TrainStatus>,
+    category: Option<TrainCategory>,
+    identity: Option<String>,
+    headcode: Option<String>,
+    course_indicator: Option<String>,
+    service_code: Option<String>,
+    portion_id: Option<String>,
+    power_type: Option<PowerType>,
+    timing_load: Option<String>,
+    speed: Option<Speed>,
+    operating_chars: OperatingCharacteristics,
+    seating_class: Option<SeatingClass>,
+    sleepers: Option<SleeperClass>,
+    reservations: Option<ReservationPolicy>,
+    connection_indicator: Option<String>,
+    catering: CateringCode,
+    branding: Option<Branding>,
+    stp: Stp,
+}
+
+impl BasicScheduleFields {
+    fn of(r: &BasicSchedule) -> Result<Self, CIFParseError> {
+        Ok(BasicScheduleFields {
+            transaction_type: r.transaction_type()?,
+            uid: r.uid()?.to_owned(),
+            start_date: r.start_date()?,
+            end_date: r.end_date()?,
+            days: r.days()?,
+            bank_holiday: r.bank_holiday()?,
+            status: r.status()?,
+            category: r.category()?,
+            identity: owned(r.identity()?),
+            headcode: owned(r.headcode()?),
+            course_indicator: owned(r.course_indicator()?),
+            service_code: owned(r.service_code()?),
+            portion_id: owned(r.portion_id()?),
+            power_type: r.power_type()?,
+            timing_load: owned(r.timing_load()?),
+            speed: r.speed()?,
+            operating_chars: r.operating_chars()?,
+            seating_class: r.seating_class()?,
+            sleepers: r.sleepers()?,
+            reservations: r.reservations()?,
+            connection_indicator: owned(r.connection_indicator()?),
+            catering: r.catering()?,
+            branding: r.branding()?,
+            stp: r.stp()?,
+        })
+    }
+
+    fn build(self) -> Result<BasicSchedule, BuildError> {
+        let b =
+            BasicScheduleBuilder::new(self.transaction_type, &self.uid, self.start_date, self.stp)
+                .operating_chars(self.operating_chars)
+                .catering(self.catering);
+        let b = opt(b, self.end_date, BasicScheduleBuilder::end_date);
+        let b = opt(b, non_empty(self.days), BasicScheduleBuilder::days);
+        let b = opt(
+            b,
+            self.bank_holiday.as_ref(),
+            BasicScheduleBuilder::bank_holiday,
+        );
+        let b = opt(b, self.status.as_ref(), BasicScheduleBuilder::status);
+        let b = opt(b, self.category.as_ref(), BasicScheduleBuilder::category);
+        let b = opt(b, self.identity.as_deref(), BasicScheduleBuilder::identity);
+        let b = opt(b, self.headcode.as_deref(), BasicScheduleBuilder::headcode);
+        let b = opt(
+            b,
+            self.course_indicator.as_deref(),
+            BasicScheduleBuilder::course_indicator,
+        );
+        let b = opt(
+            b,
+            self.service_code.as_deref(),
+            BasicScheduleBuilder::service_code,
+        );
+        let b = opt(
+            b,
+            self.portion_id.as_deref(),
+            BasicScheduleBuilder::portion_id,
+        );
+        let b = opt(
+            b,
+            self.power_type.as_ref(),
+            BasicScheduleBuilder::power_type,
+        );
+        let b = opt(
+            b,
+            self.timing_load.as_deref(),
+            BasicScheduleBuilder::timing_load,
+        );
+        let b = opt(b, self.speed, BasicScheduleBuilder::speed);
+        let b = opt(
+            b,
+            self.seating_class.as_ref(),
+            BasicScheduleBuilder::seating_class,
+        );
+        let b = opt(b, self.sleepers.as_ref(), BasicScheduleBuilder::sleepers);
+        let b = opt(
+            b,
+            self.reservations.as_ref(),
+            BasicScheduleBuilder::reservations,
+        );
+        let b = opt(
+            b,
+            self.connection_indicator.as_deref(),
+            BasicScheduleBuilder::connection_indicator,
+        );
+        let b = opt(b, self.branding.as_ref(), BasicScheduleBuilder::branding);
+        b.build()
+    }
+}
+
+serde_via!(BasicSchedule, BasicScheduleFields);
+
+#[derive(Serialize, Deserialize)]
+struct ScheduleExtraFields {
+    traction_class: Option<String>,
+    uic_code: Option<String>,
+    atoc_code: String,
+    applicable_timetable_code: String,
+    retail_service_id: Option<String>,
+    data_source: Option<String>,
+}
+
+impl ScheduleExtraFields {
+    fn of(r: &ScheduleExtra) -> Result<Self, CIFParseError> {
+        Ok(ScheduleExtraFields {
+            traction_class: owned(r.traction_class()?),
+            uic_code: owned(r.uic_code()?),
+            atoc_code: r.atoc_code()?.to_owned(),
+            applicable_timetable_code: r.applicable_timetable_code()?.to_owned(),
+            retail_service_id: owned(r.retail_service_id()?),
+            data_source: owned(r.data_source()?),
+        })
+    }
+
+    fn build(self) -> Result<ScheduleExtra, BuildError> {
+        let b = ScheduleExtraBuilder::new(&self.atoc_code, &self.applicable_timetable_code);
+        let b = opt(
+            b,
+            self.traction_class.as_deref(),
+            ScheduleExtraBuilder::traction_class,
+        );
+        let b = opt(b, self.uic_code.as_deref(), ScheduleExtraBuilder::uic_code);
+        let b = opt(
+            b,
+            self.retail_service_id.as_deref(),
+            ScheduleExtraBuilder::retail_service_id,
+        );
+        let b = opt(
+            b,
+            self.data_source.as_deref(),
+            ScheduleExtraBuilder::data_source,
+        );
+        b.build()
+    }
+}
+
+serde_via!(ScheduleExtra, ScheduleExtraFields);
+
+#[derive(Serialize, Deserialize)]
+struct LocationOriginFields {
+    tiploc: Tiploc<'static>,
+    tiploc_suffix: Option<String>,
+    scheduled_departure_time: NaiveTime,
+    public_departure: NaiveTime,
+    platform: Option<String>,
+    line: Option<String>,
+    eng_allowance: Option<Allowance>,
+    path_allowance: Option<Allowance>,
+    activity: Activities,
+    perf_allowance: Option<Allowance>,
+}
+
+impl LocationOriginFields {
+    fn of(r: &LocationOrigin) -> Result<Self, CIFParseError> {
+        Ok(LocationOriginFields {
+            tiploc: r.tiploc()?.into_owned(),
+            tiploc_suffix: owned(r.tiploc_suffix()?),
+            scheduled_departure_time: r.scheduled_departure_time()?,
+            public_departure: r.public_departure()?,
+            platform: owned(r.platform()?),
+            line: owned(r.line()?),
+            eng_allowance: r.eng_allowance()?,
+            path_allowance: r.path_allowance()?,
+            activity: r.activity()?,
+            perf_allowance: r.perf_allowance()?,
+        })
+    }
+
+    fn build(self) -> Result<LocationOrigin, BuildError> {
+        let b = LocationOriginBuilder::new(
+            &self.tiploc,
+            self.scheduled_departure_time,
+            self.public_departure,
+        )
+        .activity(&self.activity);
+        let b = opt(
+            b,
+            self.tiploc_suffix.as_deref(),
+            LocationOriginBuilder::tiploc_suffix,
+        );
+        let b = opt(b, self.platform.as_deref(), LocationOriginBuilder::platform);
+        let b = opt(b, self.line.as_deref(), LocationOriginBuilder::line);
+        let b = opt(b, self.eng_allowance, LocationOriginBuilder::eng_allowance);
+        let b = opt(
+            b,
+            self.path_allowance,
+            LocationOriginBuilder::path_allowance,
+        );
+        let b = opt(
+            b,
+            self.perf_allowance,
+            LocationOriginBuilder::perf_allowance,
+        );
+        b.build()
+    }
+}
+
+serde_via!(LocationOrigin, LocationOriginFields);
+
+#[derive(Serialize, Deserialize)]
+struct LocationIntermediateFields {
+    tiploc: Tiploc<'static>,
+    tiploc_suffix: Option<String>,
+    scheduled_arrival_time: Option<NaiveTime>,
+    scheduled_departure_time: Option<NaiveTime>,
+    scheduled_pass: Option<NaiveTime>,
+    public_arrival: Option<NaiveTime>,
+    public_departure: Option<NaiveTime>,
+    platform: Option<String>,
+    line: Option<String>,
+    path: Option<String>,
+    activity: Activities,
+    eng_allowance: Option<Allowance>,
+    path_allowance: Option<Allowance>,
+    perf_allowance: Option<Allowance>,
+}
+
+impl LocationIntermediateFields {
+    fn of(r: &LocationIntermediate) -> Result<Self, CIFParseError> {
+        Ok(LocationIntermediateFields {
+            tiploc: r.tiploc()?.into_owned(),
+            tiploc_suffix: owned(r.tiploc_suffix()?),
+            scheduled_arrival_time: r.scheduled_arrival_time()?,
+            scheduled_departure_time: r.scheduled_departure_time()?,
+            scheduled_pass: r.scheduled_pass()?,
+            public_arrival: r.public_arrival()?,
+            public_departure: r.public_departure()?,
+            platform: owned(r.platform()?),
+            line: owned(r.line()?),
+            path: owned(r.path()?),
+            activity: r.activity()?,
+            eng_allowance: r.eng_allowance()?,
+            path_allowance: r.path_allowance()?,
+            perf_allowance: r.perf_allowance()?,
+        })
+    }
+
+    fn build(self) -> Result<LocationIntermediate, BuildError> {
+        type B = LocationIntermediateBuilder;
+        let b = B::new(&self.tiploc).activity(&self.activity);
+        let b = opt(b, self.tiploc_suffix.as_deref(), B::tiploc_suffix);
+        let b = opt(b, self.scheduled_arrival_time, B::scheduled_arrival_time);
+        let b = opt(
+            b,
+            self.scheduled_departure_time,
+            B::scheduled_departure_time,
+        );
+        let b = opt(b, self.scheduled_pass, B::scheduled_pass);
+        let b = opt(b, self.public_arrival, B::public_arrival);
+        let b = opt(b, self.public_departure, B::public_departure);
+        let b = opt(b, self.platform.as_deref(), B::platform);
+        let b = opt(b, self.line.as_deref(), B::line);
+        let b = opt(b, self.path.as_deref(), B::path);
+        let b = opt(b, self.eng_allowance, B::eng_allowance);
+        let b = opt(b, self.path_allowance, B::path_allowance);
+        let b = opt(b, self.perf_allowance, B::perf_allowance);
+        b.build()
+    }
+}
+
+serde_via!(LocationIntermediate, LocationIntermediateFields);
+
+#[derive(Serialize, Deserialize)]
+struct LocationTerminatingFields {
+    tiploc: Tiploc<'static>,
+    tiploc_suffix: Option<String>,
+    scheduled_arrival_time: NaiveTime,
+    public_arrival: NaiveTime,
+    platform: Option<String>,
+    path: Option<String>,
+    activity: Activities,
+}
+
+impl LocationTerminatingFields {
+    fn of(r: &LocationTerminating) -> Result<Self, CIFParseError> {
+        Ok(LocationTerminatingFields {
+            tiploc: r.tiploc()?.into_owned(),
+            tiploc_suffix: owned(r.tiploc_suffix()?),
+            scheduled_arrival_time: r.scheduled_arrival_time()?,
+            public_arrival: r.public_arrival()?,
+            platform: owned(r.platform()?),
+            path: owned(r.path()?),
+            activity: r.activity()?,
+        })
+    }
+
+    fn build(self) -> Result<LocationTerminating, BuildError> {
+        type B = LocationTerminatingBuilder;
+        let b = B::new(
+            &self.tiploc,
+            self.scheduled_arrival_time,
+            self.public_arrival,
+        )
+        .activity(&self.activity);
+        let b = opt(b, self.tiploc_suffix.as_deref(), B::tiploc_suffix);
+        let b = opt(b, self.platform.as_deref(), B::platform);
+        let b = opt(b, self.path.as_deref(), B::path);
+        b.build()
+    }
+}
+
+serde_via!(LocationTerminating, LocationTerminatingFields);
+
+#[derive(Serialize, Deserialize)]
+struct ChangeEnRouteFields {
+    tiploc: Tiploc<'static>,
+    tiploc_suffix: Option<String>,
+    train_category: Option<TrainCategory>,
+    train_identity: String,
+    headcode: Option<String>,
+    course_indicator: String,
+    service_code: String,
+    biz_sector: Option<String>,
+    power_type: Option<PowerType>,
+    timing_load: Option<String>,
+    speed: Option<Speed>,
+    operating_chars: OperatingCharacteristics,
+    class: Option<SeatingClass>,
+    sleepers: Option<SleeperClass>,
+    reservations: Option<ReservationPolicy>,
+    connect: Option<String>,
+    catering: CateringCode,
+    branding: Option<Branding>,
+    traction: Option<String>,
+    uic_code: Option<String>,
+    retail_id: Option<String>,
+}
+
+impl ChangeEnRouteFields {
+    fn of(r: &ChangeEnRoute) -> Result<Self, CIFParseError> {
+        Ok(ChangeEnRouteFields {
+            tiploc: r.tiploc()?.into_owned(),
+            tiploc_suffix: owned(r.tiploc_suffix()?),
+            train_category: r.train_category()?,
+            train_identity: r.train_identity()?.to_owned(),
+            headcode: owned(r.headcode()?),
+            course_indicator: r.course_indicator()?.to_owned(),
+            service_code: r.service_code()?.to_owned(),
+            biz_sector: owned(r.biz_sector()?),
+            power_type: r.power_type()?,
+            timing_load: owned(r.timing_load()?),
+            speed: r.speed()?,
+            operating_chars: r.operating_chars()?,
+            class: r.class()?,
+            sleepers: r.sleepers()?,
+            reservations: r.reservations()?,
+            connect: owned(r.connect()?),
+            catering: r.catering()?,
+            branding: r.branding()?,
+            traction: owned(r.traction()?),
+            uic_code: owned(r.uic_code()?),
+            retail_id: owned(r.retail_id()?),
+        })
+    }
+
+    fn build(self) -> Result<ChangeEnRoute, BuildError> {
+        type B = ChangeEnRouteBuilder;
+        let b = B::new(
+            &self.tiploc,
+            &self.train_identity,
+            &self.course_indicator,
+            &self.service_code,
+        )
+        .operating_chars(self.operating_chars)
+        .catering(self.catering);
+        let b = opt(b, self.tiploc_suffix.as_deref(), B::tiploc_suffix);
+        let b = opt(b, self.train_category.as_ref(), B::train_category);
+        let b = opt(b, self.headcode.as_deref(), B::headcode);
+        let b = opt(b, self.biz_sector.as_deref(), B::biz_sector);
+        let b = opt(b, self.power_type.as_ref(), B::power_type);
+        let b = opt(b, self.timing_load.as_deref(), B::timing_load);
+        let b = opt(b, self.speed, B::speed);
+        let b = opt(b, self.class.as_ref(), B::class);
+        let b = opt(b, self.sleepers.as_ref(), B::sleepers);
+        let b = opt(b, self.reservations.as_ref(), B::reservations);
+        let b = opt(b, self.connect.as_deref(), B::connect);
+        let b = opt(b, self.branding.as_ref(), B::branding);
+        let b = opt(b, self.traction.as_deref(), B::traction);
+        let b = opt(b, self.uic_code.as_deref(), B::uic_code);
+        let b = opt(b, self.retail_id.as_deref(), B::retail_id);
+        b.build()
+    }
+}
+
+serde_via!(ChangeEnRoute, ChangeEnRouteFields);
+
+#[derive(Serialize, Deserialize)]
+struct TrailerFields {}
+
+impl TrailerFields {
+    fn of(_: &Trailer) -> Result<Self, CIFParseError> {
+        Ok(TrailerFields {})
+    }
+
+    fn build(self) -> Result<Trailer, BuildError> {
+        TrailerBuilder::new().build()
+    }
+}
+
+serde_via!(Trailer, TrailerFields);
+
+#[cfg(test)]
+mod test {
+    use fallible_iterator::FallibleIterator;
+    use serde_json::json;
+
+    use crate::{Reader, Record};
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample.cif");
+    static LARGER_SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample-larger.cif");
+
+    #[test]
+    fn should_serialize_decoded_fields() {
+        let data: &[u8] = b"\
+LTLEEDS   0954 000012A   TF                                                     \n";
+        let record = Reader::new(data).next().unwrap().unwrap();
+        let value = serde_json::to_value(&record).expect("serialize");
+        assert_eq!(
+            value,
+            json!({
+                "LocationTerminating": {
+                    "tiploc": "LEEDS",
+                    "tiploc_suffix": null,
+                    "scheduled_arrival_time": "09:54:00",
+                    "public_arrival": "00:00:00",
+                    "platform": "12A",
+                    "path": null,
+                    "activity": ["TF"],
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn should_rebuild_records_from_json() {
+        for data in &[SAMPLE_FILE, LARGER_SAMPLE_FILE] {
+            let records: Vec<Record> = Reader::new(*data).collect().expect("read");
+            let json = serde_json::to_string(&records).expect("serialize");
+            let rebuilt: Vec<Record> = serde_json::from_str(&json).expect("deserialize");
+            assert_eq!(rebuilt, records);
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_fields() {
+        let e = serde_json::from_value::<Record>(json!({
+            "TiplocDelete": { "tiploc": "NOT A TIPLOC" }
+        }))
+        .unwrap_err();
+        assert!(e.to_string().contains("tiploc"), "{}", e);
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use bytes::{BufMut, BytesMut};
+use chrono::NaiveDate;
+use fallible_iterator::FallibleIterator;
+use thiserror::Error;
+
+use crate::header::FullOrUpdate;
+use crate::helpers::CIF_LINE_LEN;
+use crate::{
+    resolve_on_date, Assembled, AssemblerError, Association, CIFParseError, Effective, Reader,
+    Record, Schedule, ScheduleAssembler, Stp, TiplocAmend, TiplocInsert, TransactionType,
+};
+
+#[derive(Error, Debug)]
+pub enum TimetableError {
+    #[error("Assembling schedules: {0}")]
+    Assembler(#[from] AssemblerError),
+    #[error("Parsing record: {0}")]
+    Parse(#[from] CIFParseError),
+    #[error("Schedule {0:?} already exists")]
+    DuplicateSchedule(ScheduleKey),
+    #[error("Schedule {0:?} does not exist")]
+    MissingSchedule(ScheduleKey),
+    #[error("Association {0:?} already exists")]
+    DuplicateAssociation(AssociationKey),
+    #[error("Association {0:?} does not exist")]
+    MissingAssociation(AssociationKey),
+    #[error("TIPLOC {0} already exists")]
+    DuplicateTiploc(String),
+    #[error("TIPLOC {0} does not exist")]
+    MissingTiploc(String),
+}
+
+pub type TimetableResult<T> = std::result::Result<T, TimetableError>;
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ScheduleKey {
+    pub uid: String,
+    pub start_date: NaiveDate,
+    pub stp: Stp,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct AssociationKey {
+    pub main_uid: String,
+    pub associated_uid: String,
+    pub location: String,
+    pub start_date: NaiveDate,
+    pub stp: Stp,
+}
+
+/// An in-memory copy of the timetable, built from a full extract and kept
+/// current by applying update extracts in turn.
+#[derive(Debug, Clone, Default)]
+pub struct Timetable {
+    tiplocs: BTreeMap<String, TiplocInsert>,
+    associations: BTreeMap<AssociationKey, Association>,
+    schedules: BTreeMap<ScheduleKey, Schedule>,
+}
+
+impl ScheduleKey {
+    pub fn of_schedule(schedule: &Schedule) -> Result<Self, CIFParseError> {
+        let bs = schedule.schedule();
+        Ok(ScheduleKey {
+            uid: bs.uid()?.to_owned(),
+            start_date: bs.start_date()?,
+            stp: bs.stp()?,
+        })
+    }
+}
+
+impl AssociationKey {
+    pub fn of_association(assoc: &Association) -> Result<Self, CIFParseError> {
+        Ok(AssociationKey {
+            main_uid: assoc.main_uid()?.to_owned(),
+            associated_uid: assoc.associated_uid()?.to_owned(),
+            location: assoc.location()?.as_ref().to_owned(),
+            start_date: assoc.start_date()?,
+            stp: assoc.stp()?,
+        })
+    }
+}
+
+impl Timetable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies every record from `reader`. A full extract replaces the
+    /// current contents of the timetable; an update is applied on top.
+    pub fn apply<R: Read>(&mut self, reader: Reader<R>) -> TimetableResult<()> {
+        let mut assembler = ScheduleAssembler::new(reader);
+        while let Some(item) = assembler.next()? {
+            let applied = match item {
+                Assembled::Schedule(schedule) => self.apply_schedule(schedule),
+                Assembled::Other(record) => self.apply_record(record),
+            };
+            match (applied, assembler.position()) {
+                (Err(TimetableError::Parse(e)), Some(position)) => {
+                    return Err(TimetableError::Parse(e.at(position)))
+                }
+                (applied, _) => applied?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tiploc(&self, tiploc: &str) -> Option<&TiplocInsert> {
+        self.tiplocs.get(tiploc)
+    }
+    pub fn tiplocs(&self) -> impl Iterator<Item = &TiplocInsert> {
+        self.tiplocs.values()
+    }
+    pub fn association(&self, key: &AssociationKey) -> Option<&Association> {
+        self.associations.get(key)
+    }
+    pub fn associations(&self) -> impl Iterator<Item = (&AssociationKey, &Association)> {
+        self.associations.iter()
+    }
+    pub fn schedule(&self, key: &ScheduleKey) -> Option<&Schedule> {
+        self.schedules.get(key)
+    }
+    pub fn schedules(&self) -> impl Iterator<Item = (&ScheduleKey, &Schedule)> {
+        self.schedules.iter()
+    }
+    /// The effective schedule for each train running on `date`.
+    pub fn schedules_on(
+        &self,
+        date: NaiveDate,
+    ) -> Result<BTreeMap<&str, Effective<'_>>, CIFParseError> {
+        resolve_on_date(self.schedules.values(), date)
+    }
+
+    fn apply_record(&mut self, record: Record) -> TimetableResult<()> {
+        match record {
+            Record::Header(header) if header.update()? == FullOrUpdate::Full => {
+                *self = Self::default();
+            }
+            Record::TiplocInsert(insert) => {
+                let tiploc = insert.tiploc()?.as_ref().to_owned();
+                if self.tiplocs.contains_key(&tiploc) {
+                    return Err(TimetableError::DuplicateTiploc(tiploc));
+                }
+                self.tiplocs.insert(tiploc, insert);
+            }
+            Record::TiplocAmend(amend) => {
+                let tiploc = amend.tiploc()?.as_ref().to_owned();
+                if self.tiplocs.remove(&tiploc).is_none() {
+                    return Err(TimetableError::MissingTiploc(tiploc));
+                }
+                let (tiploc, insert) = amended_insert(&amend)?;
+                self.tiplocs.insert(tiploc, insert);
+            }
+            Record::TiplocDelete(delete) => {
+                let tiploc = delete.tiploc()?.as_ref().to_owned();
+                if self.tiplocs.remove(&tiploc).is_none() {
+                    return Err(TimetableError::MissingTiploc(tiploc));
+                }
+            }
+            Record::Association(assoc) => {
+                let key = AssociationKey::of_association(&assoc)?;
+                match assoc.transaction_type()? {
+                    TransactionType::New => {
+                        if self.associations.contains_key(&key) {
+                            return Err(TimetableError::DuplicateAssociation(key));
+                        }
+                        self.associations.insert(key, assoc);
+                    }
+                    TransactionType::Delete => {
+                        if self.associations.remove(&key).is_none() {
+                            return Err(TimetableError::MissingAssociation(key));
+                        }
+                    }
+                    TransactionType::Revise => {
+                        if !self.associations.contains_key(&key) {
+                            return Err(TimetableError::MissingAssociation(key));
+                        }
+                        self.associations.insert(key, assoc);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn apply_schedule(&mut self, schedule: Schedule) -> TimetableResult<()> {
+        let key = ScheduleKey::of_schedule(&schedule)?;
+        match schedule.schedule().transaction_type()? {
+            TransactionType::New => {
+                if self.schedules.contains_key(&key) {
+                    return Err(TimetableError::DuplicateSchedule(key));
+                }
+                self.schedules.insert(key, schedule);
+            }
+            TransactionType::Delete => {
+                if self.schedules.remove(&key).is_none() {
+                    return Err(TimetableError::MissingSchedule(key));
+                }
+            }
+            TransactionType::Revise => {
+                if !self.schedules.contains_key(&key) {
+                    return Err(TimetableError::MissingSchedule(key));
+                }
+                self.schedules.insert(key, schedule);
+            }
+        }
+        Ok(())
+    }
+}
+
+// The TI and TA records share their layout up to the new TIPLOC field, so we
+// keep amended TIPLOCs as an insert record under their (possibly new) name.
+fn amended_insert(amend: &TiplocAmend) -> Result<(String, TiplocInsert), CIFParseError> {
+    let tiploc = match amend.new_tiploc()? {
+        Some(tiploc) => tiploc.as_ref().to_owned(),
+        None => amend.tiploc()?.as_ref().to_owned(),
+    };
+    let mut buf = BytesMut::with_capacity(CIF_LINE_LEN);
+    buf.put_slice(b"TI");
+    buf.put_slice(format!("{:<7}", tiploc).as_bytes());
+    buf.put_slice(&amend.buf()[9..72]);
+    buf.put_bytes(b' ', 8);
+    buf.put_u8(b'\n');
+    Ok((tiploc, TiplocInsert::from_record(buf.freeze())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static SAMPLE_FILE: &[u8] = include_bytes!("../tests/sample.cif");
+
+    fn key(uid: &str, start_date: NaiveDate, stp: Stp) -> ScheduleKey {
+        ScheduleKey {
+            uid: uid.to_owned(),
+            start_date,
+            stp,
+        }
+    }
+
+    fn loaded() -> Timetable {
+        let mut timetable = Timetable::new();
+        timetable.apply(Reader::new(SAMPLE_FILE)).expect("apply");
+        timetable
+    }
+
+    #[test]
+    fn should_load_full_extract() {
+        let timetable = loaded();
+        assert_eq!(timetable.tiplocs().count(), 4);
+        assert_eq!(timetable.associations().count(), 2);
+        assert_eq!(timetable.schedules().count(), 3);
+
+        let start = NaiveDate::from_ymd_opt(2020, 5, 17).unwrap();
+        let schedule = timetable
+            .schedule(&key("C00046", start, Stp::Permanent))
+            .expect("C00046");
+        assert_eq!(schedule.intermediates().len(), 2);
+        assert!(timetable
+            .schedule(&key("C00046", start, Stp::Cancellation))
+            .is_some());
+    }
+
+    #[test]
+    fn should_apply_update() {
+        let mut timetable = loaded();
+        let update: &[u8] = b"\
+HDTPS.UDFROC1.PD2006202006201947DFROC2FDFROC2EUA200620200621                    \n\
+TDAACHEN                                                                        \n\
+TAABCWM  00385964VABERCWMBOI                78128   0                   ABCWMX  \n\
+AADC01360C01363200517                YORK     T                                C\n\
+BSDC00090200517                                                                P\n\
+BSRC000462005172012060000001 PEE5J99    111841820 DMUE   090                   P\n\
+BX         NTY                                                                  \n\
+LOHOLBSDG 0944 0000          TB                                                 \n\
+LTLEEDS   0954 000012A   TF                                                     \n\
+ZZ                                                                              \n";
+
+        timetable.apply(Reader::new(update)).expect("apply update");
+
+        assert!(timetable.tiploc("AACHEN").is_none());
+        assert!(timetable.tiploc("ABCWM").is_none());
+        let renamed = timetable.tiploc("ABCWMX").expect("renamed tiploc");
+        assert_eq!(renamed.tiploc().unwrap().as_ref(), "ABCWMX");
+        assert_eq!(renamed.tps_description().unwrap(), "ABERCWMBOI");
+        assert_eq!(renamed.stanox().unwrap(), "78128");
+        let inserted: &[u8] =
+            b"TIABCWMX 00385964VABERCWMBOI                78128   0                           \n";
+        match Reader::new(inserted).read_next().expect("read") {
+            Some(Record::TiplocInsert(insert)) => assert_eq!(*renamed, insert),
+            other => panic!("Expected TIPLOC insert, got {:?}", other),
+        }
+
+        assert_eq!(timetable.associations().count(), 1);
+
+        let start = NaiveDate::from_ymd_opt(2020, 5, 17).unwrap();
+        assert!(timetable
+            .schedule(&key("C00090", start, Stp::Permanent))
+            .is_none());
+        let revised = timetable
+            .schedule(&key("C00046", start, Stp::Permanent))
+            .expect("C00046");
+        assert_eq!(revised.schedule().identity().unwrap(), Some("5J99"));
+        assert!(revised.intermediates().is_empty());
+    }
+
+    #[test]
+    fn should_resolve_cancellation_on_date() {
+        let timetable = loaded();
+        let date = NaiveDate::from_ymd_opt(2020, 5, 31).unwrap();
+        let resolved = timetable.schedules_on(date).expect("resolve");
+        assert!(matches!(resolved["C00046"], Effective::Cancelled(_)));
+        assert!(matches!(resolved["C00090"], Effective::Runs(_)));
+    }
+
+    #[test]
+    fn should_reset_on_full_extract() {
+        let mut timetable = loaded();
+        timetable.apply(Reader::new(SAMPLE_FILE)).expect("reload");
+        assert_eq!(timetable.schedules().count(), 3);
+    }
+
+    #[test]
+    fn should_reject_revision_of_missing_schedule() {
+        let mut timetable = Timetable::new();
+        let update: &[u8] = b"\
+HDTPS.UDFROC1.PD2006202006201947DFROC2FDFROC2EUA200620200621                    \n\
+BSRC000462005172012060000001 PEE5J99    111841820 DMUE   090                   P\n";
+
+        let e = timetable.apply(Reader::new(update)).unwrap_err();
+        assert!(
+            matches!(e, TimetableError::MissingSchedule(ref k) if k.uid == "C00046"),
+            "{:?} should be a missing schedule",
+            e
+        );
+    }
+
+    #[test]
+    fn should_report_where_unparseable_record_is() {
+        let mut timetable = Timetable::new();
+        let update: &[u8] = b"\
+HDTPS.UDFROC1.PD2006202006201947DFROC2FDFROC2EUA200620200621                    \n\
+BSNC000462005X72012060000001 PEE5J99    111841820 DMUE   090                   P\n";
+
+        let e = timetable.apply(Reader::new(update)).unwrap_err();
+        match e {
+            TimetableError::Parse(CIFParseError::Field(ref field)) => {
+                assert_eq!(field.field, "start_date");
+                assert_eq!(
+                    field.position,
+                    Some(crate::Position {
+                        offset: 81,
+                        line: 2
+                    })
+                );
+            }
+            ref other => panic!("Expected parse error, got {:?}", other),
+        }
+        assert!(e.to_string().contains("Line 2 (byte 81)"), "{}", e);
+    }
+}
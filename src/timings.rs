@@ -0,0 +1,224 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+use crate::{CIFParseError, Schedule, Tiploc};
+
+/// The times at a single location in a schedule, resolved against the date
+/// that the train starts running.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CallTimes {
+    pub tiploc: Tiploc<'static>,
+    /// The number of days after the run date on which the train reaches this
+    /// location.
+    pub day_offset: u32,
+    pub arrival: Option<NaiveDateTime>,
+    pub departure: Option<NaiveDateTime>,
+    pub pass: Option<NaiveDateTime>,
+    pub public_arrival: Option<NaiveDateTime>,
+    pub public_departure: Option<NaiveDateTime>,
+}
+
+// Tracks the current day as we walk through the working times of a schedule,
+// moving onto the next day whenever the clock goes backwards.
+struct Clock {
+    run_date: NaiveDate,
+    day_offset: u32,
+    last: Option<NaiveTime>,
+}
+
+impl Clock {
+    fn new(run_date: NaiveDate) -> Self {
+        Clock {
+            run_date,
+            day_offset: 0,
+            last: None,
+        }
+    }
+
+    fn date(&self) -> NaiveDate {
+        self.run_date + Duration::days(i64::from(self.day_offset))
+    }
+
+    fn offset_of(&self, at: NaiveDateTime) -> u32 {
+        (at.date() - self.run_date).num_days() as u32
+    }
+
+    fn advance(&mut self, time: NaiveTime) -> NaiveDateTime {
+        if let Some(last) = self.last {
+            if time < last {
+                self.day_offset += 1;
+            }
+        }
+        self.last = Some(time);
+        self.date().and_time(time)
+    }
+
+    // Public times may be rounded either side of the working time, so we
+    // pick whichever day puts them closest to it.
+    fn public(&self, working: Option<NaiveDateTime>, time: NaiveTime) -> NaiveDateTime {
+        let working = working.unwrap_or_else(|| self.date().and_time(time));
+        [-1, 0, 1]
+            .iter()
+            .map(|days| (working.date() + Duration::days(*days)).and_time(time))
+            .min_by_key(|candidate| (*candidate - working).num_seconds().abs())
+            .expect("candidates")
+    }
+}
+
+// CIF uses a public time of `0000` to mean there is no public time, except
+// for calls that really are around midnight.
+fn public_time(time: Option<NaiveTime>, working: Option<NaiveTime>) -> Option<NaiveTime> {
+    let time = time?;
+    let is_midnight = time == NaiveTime::MIN;
+    let near_midnight = working.is_some_and(|w| w.hour() == 23 || w.hour() == 0);
+    if is_midnight && !near_midnight {
+        None
+    } else {
+        Some(time)
+    }
+}
+
+impl Schedule {
+    /// Computes date-times for every location in the schedule, for a train
+    /// that starts running on `run_date`.
+    pub fn call_times(&self, run_date: NaiveDate) -> Result<Vec<CallTimes>, CIFParseError> {
+        let mut clock = Clock::new(run_date);
+        let mut calls = Vec::new();
+
+        if let Some(origin) = self.origin() {
+            let dep = origin.scheduled_departure_time()?;
+            let departure = clock.advance(dep);
+            let public_dep = public_time(Some(origin.public_departure()?), Some(dep));
+            calls.push(CallTimes {
+                tiploc: origin.tiploc()?.into_owned(),
+                day_offset: clock.day_offset,
+                arrival: None,
+                departure: Some(departure),
+                pass: None,
+                public_arrival: None,
+                public_departure: public_dep.map(|t| clock.public(Some(departure), t)),
+            });
+        }
+
+        for intermediate in self.intermediates() {
+            let location = intermediate.location();
+            let arr = location.scheduled_arrival_time()?;
+            let pass = location.scheduled_pass()?;
+            let dep = location.scheduled_departure_time()?;
+
+            let arrival = arr.map(|t| clock.advance(t));
+            let pass = pass.map(|t| clock.advance(t));
+            let departure = dep.map(|t| clock.advance(t));
+            let day_offset = arrival
+                .or(pass)
+                .or(departure)
+                .map_or(clock.day_offset, |first| clock.offset_of(first));
+            let public_arr = public_time(location.public_arrival()?, arr);
+            let public_dep = public_time(location.public_departure()?, dep);
+
+            calls.push(CallTimes {
+                tiploc: location.tiploc()?.into_owned(),
+                day_offset,
+                arrival,
+                departure,
+                pass,
+                public_arrival: public_arr.map(|t| clock.public(arrival, t)),
+                public_departure: public_dep.map(|t| clock.public(departure, t)),
+            });
+        }
+
+        if let Some(terminating) = self.terminating() {
+            let arr = terminating.scheduled_arrival_time()?;
+            let arrival = clock.advance(arr);
+            let public_arr = public_time(Some(terminating.public_arrival()?), Some(arr));
+            calls.push(CallTimes {
+                tiploc: terminating.tiploc()?.into_owned(),
+                day_offset: clock.day_offset,
+                arrival: Some(arrival),
+                departure: None,
+                pass: None,
+                public_arrival: public_arr.map(|t| clock.public(Some(arrival), t)),
+                public_departure: None,
+            });
+        }
+
+        Ok(calls)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fallible_iterator::FallibleIterator;
+
+    use super::*;
+    use crate::{Assembled, Reader, ScheduleAssembler};
+
+    fn assemble(data: &[u8]) -> Schedule {
+        let mut items = ScheduleAssembler::new(Reader::new(data));
+        match items.next().expect("assemble") {
+            Some(Assembled::Schedule(schedule)) => schedule,
+            other => panic!("Expected schedule, got {:?}", other),
+        }
+    }
+
+    fn at(date: NaiveDate, h: u32, m: u32, s: u32) -> NaiveDateTime {
+        date.and_hms_opt(h, m, s).unwrap()
+    }
+
+    #[test]
+    fn should_roll_over_midnight() {
+        let schedule = assemble(
+            b"\
+BSNC000462005172012060000001 PEE5J11    111841820 DMUE   090                   P\n\
+LOWLOE    2320 23206  FL     TB                                                 \n\
+LIWLOE    2327 2328      23272328C        T                                     \n\
+LIKETRSJ            0001H00000000                                               \n\
+LTTUNWELL 0015 00161     TF                                                     \n",
+        );
+        let date = NaiveDate::from_ymd_opt(2020, 5, 17).unwrap();
+        let next = date.succ_opt().unwrap();
+        let calls = schedule.call_times(date).expect("call times");
+        assert_eq!(calls.len(), 4);
+
+        assert_eq!(calls[0].tiploc, Tiploc::from("WLOE"));
+        assert_eq!(calls[0].departure, Some(at(date, 23, 20, 0)));
+        assert_eq!(calls[0].public_departure, Some(at(date, 23, 20, 0)));
+
+        assert_eq!(calls[1].day_offset, 0);
+        assert_eq!(calls[1].arrival, Some(at(date, 23, 27, 0)));
+        assert_eq!(calls[1].public_departure, Some(at(date, 23, 28, 0)));
+
+        assert_eq!(calls[2].day_offset, 1);
+        assert_eq!(calls[2].pass, Some(at(next, 0, 1, 30)));
+        assert_eq!(calls[2].public_arrival, None);
+        assert_eq!(calls[2].public_departure, None);
+
+        assert_eq!(calls[3].day_offset, 1);
+        assert_eq!(calls[3].arrival, Some(at(next, 0, 15, 0)));
+        assert_eq!(calls[3].public_arrival, Some(at(next, 0, 16, 0)));
+    }
+
+    #[test]
+    fn public_time_should_stay_close_to_working_time() {
+        let schedule = assemble(
+            b"\
+BSNC000462005172012060000001 PEE5J11    111841820 DMUE   090                   P\n\
+LOWLOE    2320 23206  FL     TB                                                 \n\
+LTTUNWELL 0000H23591     TF                                                     \n",
+        );
+        let date = NaiveDate::from_ymd_opt(2020, 5, 17).unwrap();
+        let next = date.succ_opt().unwrap();
+        let calls = schedule.call_times(date).expect("call times");
+        assert_eq!(calls[1].day_offset, 1);
+        assert_eq!(calls[1].arrival, Some(at(next, 0, 0, 30)));
+        assert_eq!(calls[1].public_arrival, Some(at(date, 23, 59, 0)));
+    }
+
+    #[test]
+    fn should_skip_schedule_without_locations() {
+        let schedule = assemble(
+            b"BSNC000462005172006210000001            1                                      C\n",
+        );
+        let date = NaiveDate::from_ymd_opt(2020, 5, 17).unwrap();
+        assert!(schedule.call_times(date).expect("call times").is_empty());
+    }
+}
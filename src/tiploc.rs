@@ -7,6 +7,9 @@ impl<'a> Tiploc<'a> {
     pub fn of_str(s: &'a str) -> Self {
         Tiploc(s.into())
     }
+    pub fn into_owned(self) -> Tiploc<'static> {
+        Tiploc(self.0.into_owned().into())
+    }
 }
 impl Tiploc<'static> {
     pub fn of_string(s: String) -> Self {
@@ -32,6 +35,20 @@ impl<'a> From<&'a str> for Tiploc<'a> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tiploc<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Tiploc<'static> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Tiploc::of_string)
+    }
+}
+
 impl AsRef<str> for Tiploc<'_> {
     fn as_ref(&self) -> &str {
         &self.0
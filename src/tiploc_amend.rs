@@ -3,8 +3,8 @@ use std::fmt;
 use bytes::Bytes;
 
 use crate::{
-    errors::CIFParseError,
-    helpers::{string_of_slice, string_of_slice_opt},
+    errors::{BuildError, CIFParseError},
+    helpers::{parse_field, string_of_slice, string_of_slice_opt, RecordBuf},
     Tiploc,
 };
 
@@ -21,37 +21,59 @@ impl TiplocAmend {
         &self.record
     }
 
-    pub fn tiploc(&self) -> Result<Tiploc, CIFParseError> {
-        let s = string_of_slice(&self.record[2..9])?;
-        Ok(Tiploc::of_string(s.to_owned()))
+    pub fn tiploc(&self) -> Result<Tiploc<'_>, CIFParseError> {
+        parse_field(&self.record, "tiploc", 2..9, |s| {
+            let s = string_of_slice(s)?;
+            Ok(Tiploc::of_string(s.to_owned()))
+        })
+    }
+    pub fn capitals(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "capitals", 9..11, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn nlc(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[11..17])?)
+        parse_field(&self.record, "nlc", 11..17, |s| Ok(string_of_slice(s)?))
     }
     pub fn nlc_check(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[17..18])?)
+        parse_field(&self.record, "nlc_check", 17..18, |s| {
+            Ok(string_of_slice(s)?)
+        })
     }
     pub fn tps_description(&self) -> Result<&str, CIFParseError> {
-        let s = string_of_slice(&self.record[18..44])?;
-        Ok(s)
+        parse_field(&self.record, "tps_description", 18..44, |s| {
+            let s = string_of_slice(s)?;
+            Ok(s)
+        })
     }
     pub fn stanox(&self) -> Result<&str, CIFParseError> {
-        Ok(string_of_slice(&self.record[44..49])?)
+        parse_field(&self.record, "stanox", 44..49, |s| Ok(string_of_slice(s)?))
+    }
+    pub fn po_mcp_code(&self) -> Result<Option<&str>, CIFParseError> {
+        parse_field(&self.record, "po_mcp_code", 49..53, |s| {
+            Ok(string_of_slice_opt(s)?)
+        })
     }
     pub fn crs(&self) -> Result<Option<&str>, CIFParseError> {
-        let s = string_of_slice_opt(&self.record[53..56])?;
-        Ok(s)
+        parse_field(&self.record, "crs", 53..56, |s| {
+            let s = string_of_slice_opt(s)?;
+            Ok(s)
+        })
     }
     pub fn nlc_desc(&self) -> Result<Option<&str>, CIFParseError> {
-        let s = string_of_slice_opt(&self.record[56..72])?;
-        Ok(s)
+        parse_field(&self.record, "nlc_desc", 56..72, |s| {
+            let s = string_of_slice_opt(s)?;
+            Ok(s)
+        })
     }
-    pub fn new_tiploc(&self) -> Result<Option<Tiploc>, CIFParseError> {
-        if let Some(s) = string_of_slice_opt(&self.record[72..79])? {
-            Ok(Some(Tiploc::of_string(s.to_owned())))
-        } else {
-            Ok(None)
-        }
+    pub fn new_tiploc(&self) -> Result<Option<Tiploc<'_>>, CIFParseError> {
+        parse_field(&self.record, "new_tiploc", 72..79, |s| {
+            if let Some(s) = string_of_slice_opt(s)? {
+                Ok(Some(Tiploc::of_string(s.to_owned())))
+            } else {
+                Ok(None)
+            }
+        })
     }
 }
 
@@ -59,10 +81,12 @@ impl fmt::Debug for TiplocAmend {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s = f.debug_struct("TiplocAmend");
         s.field("tiploc", &self.tiploc());
+        s.field("capitals", &self.capitals());
         s.field("nlc", &self.nlc());
         s.field("nlc_check", &self.nlc_check());
         s.field("tps_description", &self.tps_description());
         s.field("stanox", &self.stanox());
+        s.field("po_mcp_code", &self.po_mcp_code());
         s.field("crs", &self.crs());
         s.field("nlc_desc", &self.nlc_desc());
         s.field("new_tiploc", &self.new_tiploc());
@@ -70,6 +94,53 @@ impl fmt::Debug for TiplocAmend {
     }
 }
 
+pub struct TiplocAmendBuilder {
+    record: RecordBuf,
+}
+
+impl TiplocAmendBuilder {
+    pub fn new(
+        tiploc: &Tiploc<'_>,
+        nlc: &str,
+        nlc_check: &str,
+        tps_description: &str,
+        stanox: &str,
+    ) -> Self {
+        let mut record = RecordBuf::new(b"TA");
+        record.put_tiploc("tiploc", 2..9, tiploc);
+        record.put("nlc", 11..17, nlc);
+        record.put("nlc_check", 17..18, nlc_check);
+        record.put("tps_description", 18..44, tps_description);
+        record.put("stanox", 44..49, stanox);
+        TiplocAmendBuilder { record }
+    }
+
+    pub fn capitals(mut self, capitals: &str) -> Self {
+        self.record.put("capitals", 9..11, capitals);
+        self
+    }
+    pub fn po_mcp_code(mut self, po_mcp_code: &str) -> Self {
+        self.record.put("po_mcp_code", 49..53, po_mcp_code);
+        self
+    }
+    pub fn crs(mut self, crs: &str) -> Self {
+        self.record.put("crs", 53..56, crs);
+        self
+    }
+    pub fn nlc_desc(mut self, nlc_desc: &str) -> Self {
+        self.record.put("nlc_desc", 56..72, nlc_desc);
+        self
+    }
+    pub fn new_tiploc(mut self, tiploc: &Tiploc<'_>) -> Self {
+        self.record.put_tiploc("new_tiploc", 72..79, tiploc);
+        self
+    }
+
+    pub fn build(self) -> Result<TiplocAmend, BuildError> {
+        Ok(TiplocAmend::from_record(self.record.finish()?))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
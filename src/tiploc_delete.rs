@@ -0,0 +1,68 @@
+use std::fmt;
+
+use bytes::Bytes;
+
+use crate::{
+    errors::{BuildError, CIFParseError},
+    helpers::{parse_field, string_of_slice, RecordBuf},
+    Tiploc,
+};
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct TiplocDelete {
+    record: Bytes,
+}
+
+impl TiplocDelete {
+    pub(crate) fn from_record(record: Bytes) -> Self {
+        Self { record }
+    }
+    pub fn buf(&self) -> &Bytes {
+        &self.record
+    }
+
+    pub fn tiploc(&self) -> Result<Tiploc<'_>, CIFParseError> {
+        parse_field(&self.record, "tiploc", 2..9, |s| {
+            let s = string_of_slice(s)?;
+            Ok(Tiploc::of_string(s.to_owned()))
+        })
+    }
+}
+
+impl fmt::Debug for TiplocDelete {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("TiplocDelete");
+        s.field("tiploc", &self.tiploc());
+        s.finish()
+    }
+}
+
+pub struct TiplocDeleteBuilder {
+    record: RecordBuf,
+}
+
+impl TiplocDeleteBuilder {
+    pub fn new(tiploc: &Tiploc<'_>) -> Self {
+        let mut record = RecordBuf::new(b"TD");
+        record.put_tiploc("tiploc", 2..9, tiploc);
+        TiplocDeleteBuilder { record }
+    }
+
+    pub fn build(self) -> Result<TiplocDelete, BuildError> {
+        Ok(TiplocDelete::from_record(self.record.finish()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_tiploc_delete() {
+        let delete =
+            b"TDAACHEN                                                                        ";
+        assert_eq!(80, delete.len());
+        let example = TiplocDelete::from_record(Bytes::from(delete.as_ref()));
+        assert_eq!(example.tiploc().unwrap(), Tiploc::of_str("AACHEN"));
+    }
+}
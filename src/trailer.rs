@@ -2,6 +2,8 @@ use std::fmt;
 
 use bytes::Bytes;
 
+use crate::{helpers::RecordBuf, BuildError};
+
 #[derive(Clone, Eq, PartialEq)]
 pub struct Trailer {
     record: Bytes,
@@ -23,6 +25,20 @@ impl fmt::Debug for Trailer {
     }
 }
 
+#[derive(Default)]
+pub struct TrailerBuilder;
+
+impl TrailerBuilder {
+    pub fn new() -> Self {
+        TrailerBuilder
+    }
+
+    pub fn build(self) -> Result<Trailer, BuildError> {
+        let record = RecordBuf::new(b"ZZ").finish()?;
+        Ok(Trailer::from_record(record))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
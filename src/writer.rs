@@ -0,0 +1,74 @@
+use std::io::{self, Write};
+
+use crate::Record;
+
+// The width of a record, not counting the newline.
+const CIF_RECORD_LEN: usize = 80;
+
+/// Writes records out as a CIF file, one 80 column line per record.
+pub struct Writer<W> {
+    dst: W,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(dst: W) -> Self {
+        Writer { dst }
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        let buf = record.buf();
+        let line = &buf[..std::cmp::min(buf.len(), CIF_RECORD_LEN)];
+        self.dst.write_all(line)?;
+        // Records built outside of the reader may be missing trailing blanks.
+        for _ in line.len()..CIF_RECORD_LEN {
+            self.dst.write_all(b" ")?;
+        }
+        self.dst.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.dst.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.dst
+    }
+
+    pub fn into_inner(self) -> W {
+        self.dst
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use fallible_iterator::FallibleIterator;
+
+    use super::*;
+    use crate::{Reader, Trailer, TrailerBuilder};
+
+    #[test]
+    fn should_write_reader_records_unchanged() {
+        let data: &[u8] = b"\
+BSNC000462005172006210000001            1                                      C\n\
+ZZ                                                                              \n";
+        let mut writer = Writer::new(Vec::new());
+        let mut reader = Reader::new(data);
+        while let Some(record) = reader.next().expect("read") {
+            writer.write_record(&record).expect("write");
+        }
+        assert_eq!(writer.into_inner(), data);
+    }
+
+    #[test]
+    fn should_pad_short_records() {
+        let mut writer = Writer::new(Vec::new());
+        let short = Trailer::from_record(Bytes::from_static(b"ZZ"));
+        writer.write_record(&Record::Trailer(short)).expect("write");
+        let built = TrailerBuilder::new().build().expect("build");
+        writer.write_record(&Record::Trailer(built)).expect("write");
+        let out = writer.into_inner();
+        assert_eq!(out.len(), 162);
+        assert_eq!(out[..81], out[81..]);
+    }
+}
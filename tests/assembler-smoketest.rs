@@ -0,0 +1,44 @@
+use cif_parser::{Assembled, Reader, ScheduleAssembler};
+use fallible_iterator::FallibleIterator;
+
+static SAMPLE_FILE: &[u8] = include_bytes!("sample-larger.cif");
+
+#[test]
+fn should_assemble_file() {
+    tracing_subscriber::fmt::try_init().unwrap_or_default();
+
+    let mut nschedules = 0;
+    let mut nwith_locations = 0;
+    let mut nintermediates = 0;
+    let mut nchanges = 0;
+    let mut nother = 0;
+
+    ScheduleAssembler::new(Reader::new(SAMPLE_FILE))
+        .for_each(|item| {
+            match item {
+                Assembled::Schedule(schedule) => {
+                    nschedules += 1;
+                    if schedule.origin().is_some() {
+                        assert!(schedule.terminating().is_some());
+                        nwith_locations += 1;
+                    }
+                    nintermediates += schedule.intermediates().len();
+                    nchanges += schedule
+                        .intermediates()
+                        .iter()
+                        .filter(|i| i.change_en_route().is_some())
+                        .count();
+                }
+                Assembled::Other(_) => nother += 1,
+            }
+            Ok(())
+        })
+        .expect("success");
+
+    assert_eq!(nschedules, 113);
+    assert_eq!(nwith_locations, 70);
+    assert_eq!(nintermediates, 2545);
+    assert_eq!(nchanges, 12);
+    // Header, associations and trailer
+    assert_eq!(nother, 1 + 62 + 1);
+}
@@ -1,6 +1,6 @@
 use std::{cmp, collections::BTreeMap};
 
-use cif_parser::{Reader, Record};
+use cif_parser::{Reader, ReaderOptions, Record};
 use fallible_iterator::FallibleIterator;
 // This file just has to be larger than our default buffer size, to show that
 // we refill correctly.
@@ -11,6 +11,7 @@ enum Kind {
     Header,
     TiplocInsert,
     TiplocAmend,
+    TiplocDelete,
     Association,
     Schedule,
     ScheduleExtra,
@@ -20,6 +21,7 @@ enum Kind {
     ChangeEnRoute,
     Trailer,
     Unrecognised(String),
+    Malformed,
 }
 
 #[test]
@@ -33,6 +35,7 @@ fn should_read_file() {
             Record::Header(_) => Ok(Kind::Header),
             Record::TiplocInsert(_) => Ok(Kind::TiplocInsert),
             Record::TiplocAmend(_) => Ok(Kind::TiplocAmend),
+            Record::TiplocDelete(_) => Ok(Kind::TiplocDelete),
             Record::Association(_) => Ok(Kind::Association),
             Record::Schedule(_) => Ok(Kind::Schedule),
             Record::ScheduleExtra(_) => Ok(Kind::ScheduleExtra),
@@ -44,6 +47,7 @@ fn should_read_file() {
             Record::Unrecognised(bs) => Ok(Kind::Unrecognised(
                 String::from_utf8_lossy(&bs[0..cmp::min(bs.len(), 2)]).into_owned(),
             )),
+            Record::Malformed { .. } => Ok(Kind::Malformed),
         })
         .for_each(|kind| {
             *nitems.entry(kind).or_default() += 1;
@@ -64,3 +68,26 @@ fn should_read_file() {
 
     assert_eq!(expected, nitems);
 }
+
+#[test]
+fn should_recover_from_malformed_lines() {
+    // Drop a byte from the middle of a line, and mangle another line's
+    // newline, so that it runs on into the next.
+    let mut data = SAMPLE_FILE.to_vec();
+    data.remove(81 * 100 + 40);
+    data[81 * 200 + 80 - 1] = b'X'; // Shifted back by the removal.
+
+    let mut rdr = Reader::with_options(&data[..], ReaderOptions::new().recover(true));
+    let mut good = 0;
+    let mut malformed = Vec::new();
+    while let Some(record) = rdr.next().expect("read") {
+        match record {
+            Record::Malformed { line_no, .. } => malformed.push(line_no),
+            _ => good += 1,
+        }
+    }
+
+    assert_eq!(malformed, vec![101, 201]);
+    assert_eq!(rdr.malformed_count(), 2);
+    assert_eq!(good, SAMPLE_FILE.len() / 81 - 3);
+}
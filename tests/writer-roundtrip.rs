@@ -0,0 +1,39 @@
+// Records are rebuilt from their decoded fields by way of serde, which
+// drives the record builders.
+#![cfg(feature = "serde")]
+
+use anyhow::Result;
+use cif_parser::*;
+use fallible_iterator::FallibleIterator;
+
+static SAMPLE_FILE: &[u8] = include_bytes!("sample.cif");
+static LARGER_SAMPLE_FILE: &[u8] = include_bytes!("sample-larger.cif");
+
+fn rebuild(record: &Record) -> Result<Record> {
+    Ok(serde_json::from_value(serde_json::to_value(record)?)?)
+}
+
+fn round_trip(data: &[u8]) -> Result<()> {
+    let mut reader = Reader::new(data);
+    let mut writer = Writer::new(Vec::new());
+    while let Some(record) = reader.next()? {
+        let rebuilt = rebuild(&record)?;
+        assert_eq!(
+            String::from_utf8_lossy(rebuilt.buf()),
+            String::from_utf8_lossy(record.buf()),
+        );
+        writer.write_record(&rebuilt)?;
+    }
+    assert!(writer.into_inner() == data, "Output differs from input");
+    Ok(())
+}
+
+#[test]
+fn should_round_trip_sample() -> Result<()> {
+    round_trip(SAMPLE_FILE)
+}
+
+#[test]
+fn should_round_trip_larger_sample() -> Result<()> {
+    round_trip(LARGER_SAMPLE_FILE)
+}